@@ -0,0 +1,153 @@
+#ifndef PPA6_H
+#define PPA6_H
+
+/* Generated by cbindgen from ppa6-ffi/src/lib.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#if defined(__unix__) && !defined(PPA6_UNIX)
+#define PPA6_UNIX
+#endif
+
+/**
+ * Result of the functions of this library.
+ */
+typedef enum Ppa6Error {
+  PPA6_ERROR_OK = 0,
+  PPA6_ERROR_NOT_FOUND = -1,
+  PPA6_ERROR_PERMISSION_DENIED = -2,
+  PPA6_ERROR_TIMEOUT = -3,
+  PPA6_ERROR_DISCONNECTED = -4,
+  PPA6_ERROR_BUSY = -5,
+  PPA6_ERROR_PAPER_OUT = -6,
+  PPA6_ERROR_COVER_OPEN = -7,
+  PPA6_ERROR_OVERHEATED = -8,
+  PPA6_ERROR_INVALID_ARGUMENT = -9,
+  PPA6_ERROR_BUFFER_TOO_SMALL = -10,
+  PPA6_ERROR_OTHER = -99,
+} Ppa6Error;
+
+/**
+ * A connected printer, created by [`ppa6_find()`] and freed by [`ppa6_free()`].
+ */
+typedef struct Ppa6Printer Ppa6Printer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Describe the last error of the calling thread, or NULL if nothing failed yet.
+ * The string is valid until the next call of this library on the same thread.
+ */
+const char *ppa6_last_error(void);
+
+/**
+ * Find any connected printer and store it in `out`, see [`Printer::find()`].
+ *
+ * # Safety
+ * `out` must be valid for writes.
+ */
+Ppa6Error ppa6_find(Ppa6Printer **out);
+
+#if defined(PPA6_UNIX)
+/**
+ * Use the open file descriptor of a USB printer, e.g. from Android's `UsbManager.openDevice()`,
+ * and store the printer in `out`, see [`UsbBackend::from_fd()`].
+ *
+ * # Safety
+ * `fd` must stay open, until the printer is freed, and `out` must be valid for writes.
+ */
+Ppa6Error ppa6_open_fd(int fd, Ppa6Printer **out);
+#endif
+
+/**
+ * Disconnect and free `printer`, NULL is ignored.
+ *
+ * # Safety
+ * `printer` must be NULL or come from [`ppa6_find()`], and must not be used afterwards.
+ */
+void ppa6_free(Ppa6Printer *printer);
+
+/**
+ * Reset the printer, see [`Printer::reset()`].
+ *
+ * # Safety
+ * `p` must come from [`ppa6_find()`].
+ */
+Ppa6Error ppa6_reset(Ppa6Printer *p);
+
+/**
+ * Set the concentration (0-2), see [`Printer::set_concentration()`].
+ *
+ * # Safety
+ * `p` must come from [`ppa6_find()`].
+ */
+Ppa6Error ppa6_set_concentration(Ppa6Printer *p, uint8_t c);
+
+/**
+ * Print packed pixels, `len` bytes of rows, that are `width` pixels wide,
+ * see [`Printer::print_image()`].
+ *
+ * # Safety
+ * `p` must come from [`ppa6_find()`], `pixels` must be valid for reads of `len` bytes.
+ */
+Ppa6Error ppa6_print_image(Ppa6Printer *p, const uint8_t *pixels, size_t len, uint16_t width);
+
+/**
+ * Print ASCII text, see [`Printer::print_text()`].
+ *
+ * # Safety
+ * `p` must come from [`ppa6_find()`], `text` must be a NUL-terminated string.
+ */
+Ppa6Error ppa6_print_text(Ppa6Printer *p, const char *text);
+
+/**
+ * Feed `rows` rows of paper, see [`Printer::push()`].
+ *
+ * # Safety
+ * `p` must come from [`ppa6_find()`].
+ */
+Ppa6Error ppa6_push(Ppa6Printer *p, uint8_t rows);
+
+/**
+ * Get the battery level in percent, see [`Printer::get_battery()`].
+ *
+ * # Safety
+ * `p` must come from [`ppa6_find()`], `out` must be valid for writes.
+ */
+Ppa6Error ppa6_get_battery(Ppa6Printer *p, uint8_t *out);
+
+/**
+ * Copy the printer's name into `buf`, which holds `len` bytes, see [`Printer::get_name()`].
+ *
+ * # Safety
+ * `p` must come from [`ppa6_find()`], `buf` must be valid for writes of `len` bytes.
+ */
+Ppa6Error ppa6_get_name(Ppa6Printer *p, char *buf, size_t len);
+
+/**
+ * Copy the printer's serial number into `buf`, which holds `len` bytes, see [`Printer::get_serial()`].
+ *
+ * # Safety
+ * `p` must come from [`ppa6_find()`], `buf` must be valid for writes of `len` bytes.
+ */
+Ppa6Error ppa6_get_serial(Ppa6Printer *p, char *buf, size_t len);
+
+/**
+ * Copy the printer's firmware version into `buf`, which holds `len` bytes,
+ * see [`Printer::get_firmware_ver()`].
+ *
+ * # Safety
+ * `p` must come from [`ppa6_find()`], `buf` must be valid for writes of `len` bytes.
+ */
+Ppa6Error ppa6_get_firmware_ver(Ppa6Printer *p, char *buf, size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* PPA6_H */
@@ -0,0 +1,303 @@
+//! C interface of [`ppa6`], so C and C++ programs can print without running `ppa6-print`.
+//!
+//! The header is `include/ppa6.h`, regenerate it with `cbindgen` after changing this file.
+//! All functions, that can fail, return a [`Ppa6Error`], zero meaning success,
+//! and [`ppa6_last_error()`] describes the last error of the calling thread.
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, CStr, CString},
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr, slice,
+};
+
+use ppa6::{Error, Printer, Result, UsbBackend};
+
+/// A connected printer, created by [`ppa6_find()`] and freed by [`ppa6_free()`].
+pub struct Ppa6Printer(Printer);
+
+/// Result of the functions of this library.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ppa6Error {
+    Ok = 0,
+    NotFound = -1,
+    PermissionDenied = -2,
+    Timeout = -3,
+    Disconnected = -4,
+    Busy = -5,
+    PaperOut = -6,
+    CoverOpen = -7,
+    Overheated = -8,
+    InvalidArgument = -9,
+    BufferTooSmall = -10,
+    Other = -99,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(msg: String) {
+    let msg = CString::new(msg).unwrap_or_else(|_| c"invalid error message".into());
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(msg));
+}
+
+impl From<&Error> for Ppa6Error {
+    fn from(e: &Error) -> Self {
+        match e {
+            Error::NotFound => Self::NotFound,
+            Error::PermissionDenied => Self::PermissionDenied,
+            Error::Timeout => Self::Timeout,
+            Error::Disconnected => Self::Disconnected,
+            Error::DeviceBusy { .. } | Error::DeviceLocked { .. } => Self::Busy,
+            Error::PaperOut => Self::PaperOut,
+            Error::CoverOpen => Self::CoverOpen,
+            Error::Overheated => Self::Overheated,
+            Error::InvalidArgument(_) | Error::DocumentTooLong { .. } => Self::InvalidArgument,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Run `f`, turning errors and panics into error codes.
+fn call(f: impl FnOnce() -> Result<()>) -> Ppa6Error {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => Ppa6Error::Ok,
+        Ok(Err(e)) => {
+            set_last_error(e.to_string());
+            Ppa6Error::from(&e)
+        }
+        Err(panic) => {
+            let msg = panic
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown");
+            set_last_error(format!("panic in ppa6: {msg}"));
+            Ppa6Error::Other
+        }
+    }
+}
+
+/// Get the printer behind `printer`, or fail with [`Ppa6Error::InvalidArgument`].
+///
+/// # Safety
+/// `printer` must be null or come from [`ppa6_find()`].
+unsafe fn printer<'a>(printer: *mut Ppa6Printer) -> Result<&'a mut Printer> {
+    unsafe { printer.as_mut() }
+        .map(|p| &mut p.0)
+        .ok_or_else(|| Error::InvalidArgument("printer is NULL".into()))
+}
+
+/// Copy `s` with a terminating NUL into `buf`, which holds `len` bytes.
+///
+/// # Safety
+/// `buf` must be valid for writes of `len` bytes.
+unsafe fn copy_string(s: &str, buf: *mut c_char, len: usize) -> Result<()> {
+    if buf.is_null() || len <= s.len() {
+        return Err(Error::InvalidArgument(format!(
+            "buffer too small, {} bytes are needed",
+            s.len() + 1
+        )));
+    }
+    unsafe {
+        ptr::copy_nonoverlapping(s.as_ptr().cast(), buf, s.len());
+        *buf.add(s.len()) = 0;
+    }
+    Ok(())
+}
+
+/// Describe the last error of the calling thread, or NULL if nothing failed yet.
+/// The string is valid until the next call of this library on the same thread.
+#[no_mangle]
+pub extern "C" fn ppa6_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+/// Find any connected printer and store it in `out`, see [`Printer::find()`].
+///
+/// # Safety
+/// `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ppa6_find(out: *mut *mut Ppa6Printer) -> Ppa6Error {
+    call(|| {
+        if out.is_null() {
+            return Err(Error::InvalidArgument("out is NULL".into()));
+        }
+        let printer = Box::new(Ppa6Printer(Printer::find()?));
+        unsafe { *out = Box::into_raw(printer) };
+        Ok(())
+    })
+}
+
+/// Use the open file descriptor of a USB printer, e.g. from Android's `UsbManager.openDevice()`,
+/// and store the printer in `out`, see [`UsbBackend::from_fd()`].
+///
+/// # Safety
+/// `fd` must stay open, until the printer is freed, and `out` must be valid for writes.
+#[cfg(unix)]
+#[no_mangle]
+pub unsafe extern "C" fn ppa6_open_fd(fd: c_int, out: *mut *mut Ppa6Printer) -> Ppa6Error {
+    call(|| {
+        if out.is_null() {
+            return Err(Error::InvalidArgument("out is NULL".into()));
+        }
+        let backend = unsafe { UsbBackend::from_fd(fd)? };
+        let printer = Box::new(Ppa6Printer(Printer::new(backend)));
+        unsafe { *out = Box::into_raw(printer) };
+        Ok(())
+    })
+}
+
+/// Disconnect and free `printer`, NULL is ignored.
+///
+/// # Safety
+/// `printer` must be NULL or come from [`ppa6_find()`], and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn ppa6_free(printer: *mut Ppa6Printer) {
+    if !printer.is_null() {
+        drop(unsafe { Box::from_raw(printer) });
+    }
+}
+
+/// Reset the printer, see [`Printer::reset()`].
+///
+/// # Safety
+/// `p` must come from [`ppa6_find()`].
+#[no_mangle]
+pub unsafe extern "C" fn ppa6_reset(p: *mut Ppa6Printer) -> Ppa6Error {
+    call(|| unsafe { printer(p) }?.reset())
+}
+
+/// Set the concentration (0-2), see [`Printer::set_concentration()`].
+///
+/// # Safety
+/// `p` must come from [`ppa6_find()`].
+#[no_mangle]
+pub unsafe extern "C" fn ppa6_set_concentration(p: *mut Ppa6Printer, c: u8) -> Ppa6Error {
+    call(|| unsafe { printer(p) }?.set_concentration(c))
+}
+
+/// Print packed pixels, `len` bytes of rows, that are `width` pixels wide,
+/// see [`Printer::print_image()`].
+///
+/// # Safety
+/// `p` must come from [`ppa6_find()`], `pixels` must be valid for reads of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ppa6_print_image(
+    p: *mut Ppa6Printer,
+    pixels: *const u8,
+    len: usize,
+    width: u16,
+) -> Ppa6Error {
+    call(|| {
+        if pixels.is_null() {
+            return Err(Error::InvalidArgument("pixels is NULL".into()));
+        }
+        let pixels = unsafe { slice::from_raw_parts(pixels, len) };
+        unsafe { printer(p) }?.print_image(pixels, width)
+    })
+}
+
+/// Print ASCII text, see [`Printer::print_text()`].
+///
+/// # Safety
+/// `p` must come from [`ppa6_find()`], `text` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ppa6_print_text(p: *mut Ppa6Printer, text: *const c_char) -> Ppa6Error {
+    call(|| {
+        if text.is_null() {
+            return Err(Error::InvalidArgument("text is NULL".into()));
+        }
+        let text = unsafe { CStr::from_ptr(text) }.to_string_lossy();
+        unsafe { printer(p) }?.print_text(&text)
+    })
+}
+
+/// Feed `rows` rows of paper, see [`Printer::push()`].
+///
+/// # Safety
+/// `p` must come from [`ppa6_find()`].
+#[no_mangle]
+pub unsafe extern "C" fn ppa6_push(p: *mut Ppa6Printer, rows: u8) -> Ppa6Error {
+    call(|| unsafe { printer(p) }?.push(rows))
+}
+
+/// Get the battery level in percent, see [`Printer::get_battery()`].
+///
+/// # Safety
+/// `p` must come from [`ppa6_find()`], `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ppa6_get_battery(p: *mut Ppa6Printer, out: *mut u8) -> Ppa6Error {
+    call(|| {
+        let level = unsafe { printer(p) }?.get_battery()?;
+        match unsafe { out.as_mut() } {
+            Some(out) => *out = level,
+            None => return Err(Error::InvalidArgument("out is NULL".into())),
+        }
+        Ok(())
+    })
+}
+
+/// Get a string of the printer, and copy it into `buf`.
+///
+/// # Safety
+/// See [`printer()`] and [`copy_string()`].
+unsafe fn get_string(
+    p: *mut Ppa6Printer,
+    buf: *mut c_char,
+    len: usize,
+    get: impl FnOnce(&mut Printer) -> Result<String>,
+) -> Ppa6Error {
+    let mut too_small = false;
+    let r = call(|| {
+        let s = get(unsafe { printer(p) }?)?;
+        too_small = buf.is_null() || len <= s.len();
+        unsafe { copy_string(&s, buf, len) }
+    });
+    match r {
+        Ppa6Error::InvalidArgument if too_small => Ppa6Error::BufferTooSmall,
+        r => r,
+    }
+}
+
+/// Copy the printer's name into `buf`, which holds `len` bytes, see [`Printer::get_name()`].
+///
+/// # Safety
+/// `p` must come from [`ppa6_find()`], `buf` must be valid for writes of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ppa6_get_name(
+    p: *mut Ppa6Printer,
+    buf: *mut c_char,
+    len: usize,
+) -> Ppa6Error {
+    unsafe { get_string(p, buf, len, Printer::get_name) }
+}
+
+/// Copy the printer's serial number into `buf`, which holds `len` bytes, see [`Printer::get_serial()`].
+///
+/// # Safety
+/// `p` must come from [`ppa6_find()`], `buf` must be valid for writes of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ppa6_get_serial(
+    p: *mut Ppa6Printer,
+    buf: *mut c_char,
+    len: usize,
+) -> Ppa6Error {
+    unsafe { get_string(p, buf, len, Printer::get_serial) }
+}
+
+/// Copy the printer's firmware version into `buf`, which holds `len` bytes,
+/// see [`Printer::get_firmware_ver()`].
+///
+/// # Safety
+/// `p` must come from [`ppa6_find()`], `buf` must be valid for writes of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ppa6_get_firmware_ver(
+    p: *mut Ppa6Printer,
+    buf: *mut c_char,
+    len: usize,
+) -> Ppa6Error {
+    unsafe { get_string(p, buf, len, Printer::get_firmware_ver) }
+}
@@ -0,0 +1,94 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use ppa6::{Document, Framing};
+use std::time::{Duration, Instant};
+
+use crate::{open_printer, Cli, FramingArg};
+
+/// Blank rows fed between two runs, so the prints can be told apart.
+const GAP: u8 = 48;
+
+#[derive(Args, Clone)]
+pub struct BenchArgs {
+    /// Number of rows of the reference document.
+    #[arg(long, default_value_t = 240)]
+    rows: usize,
+
+    /// Chunk heights to try, in rows.
+    #[arg(long, value_delimiter = ',', default_value = "24,48")]
+    chunk_rows: Vec<u16>,
+
+    /// Delays between chunks to try, in milliseconds.
+    #[arg(long, value_delimiter = ',', default_value = "0,50")]
+    delays: Vec<u64>,
+
+    /// Framings to try.
+    #[arg(long, value_delimiter = ',', default_value = "chunk,job")]
+    framings: Vec<FramingArg>,
+}
+
+/// A reference document with solid black, a checkerboard and white space,
+/// which is dense enough to make the printer slow down.
+fn reference(rows: usize) -> Result<Document> {
+    let stride = 384 / 8;
+    let mut pixels = vec![0u8; rows * stride];
+    for (y, row) in pixels.chunks_mut(stride).enumerate() {
+        for (x, byte) in row.iter_mut().enumerate() {
+            *byte = match x {
+                0..16 => 0xff,
+                16..32 if (x + y / 8) % 2 == 0 => 0xff,
+                _ => 0x00,
+            };
+        }
+    }
+    Ok(Document::new(pixels, 384)?)
+}
+
+/// Print a reference document with every combination of chunking strategies,
+/// and write the timings as CSV to stdout, to choose the defaults for a model.
+pub fn bench(cli: &Cli, args: &BenchArgs) -> Result<()> {
+    let doc = reference(args.rows)?;
+    let mut printer = open_printer(cli)?;
+    let model = printer.detect_model()?;
+
+    println!("model,framing,chunk_rows,delay_ms,seconds,rows_per_second");
+    for &framing in &args.framings {
+        for &chunk_rows in &args.chunk_rows {
+            for &delay in &args.delays {
+                printer.set_framing(match framing {
+                    FramingArg::Chunk => Framing::PerChunk,
+                    FramingArg::Job => Framing::PerJob,
+                });
+
+                let name = match framing {
+                    FramingArg::Chunk => "chunk",
+                    FramingArg::Job => "job",
+                };
+                log::info!("{name} framing, {chunk_rows} rows per chunk, {delay}ms delay...");
+
+                let start = Instant::now();
+                printer
+                    .print_image_chunked_ext(
+                        doc.pixels(),
+                        doc.width(),
+                        chunk_rows,
+                        Duration::from_millis(delay),
+                        |_| {},
+                    )
+                    .with_context(|| format!("{name}, {chunk_rows} rows, {delay}ms"))?;
+                let secs = start.elapsed().as_secs_f64();
+                println!(
+                    "{model},{name},{chunk_rows},{delay},{secs:.3},{:.1}",
+                    doc.height() as f64 / secs
+                );
+
+                printer.push(GAP)?;
+            }
+        }
+    }
+
+    if let Some(stats) = printer.transport_stats() {
+        log::debug!("transport: {stats:?}");
+    }
+    Ok(())
+}
@@ -0,0 +1,685 @@
+//! A CUPS filter, which turns CUPS and PWG raster pages into printer commands,
+//! so standard filter chains like `pdftoraster | ppa6-cups` work, see `etc/ppa6.ppd`.
+//!
+//! Other formats of the library's content handlers, e.g. PBM bitmaps, are printed as a single page.
+//!
+//! The `[[transform]]`s of the config file in `$PPA6_CONFIG` are applied to every page.
+//!
+//! The commands are written to stdout, the CUPS backend (usually `usb://`) sends them to the printer.
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use image::{
+    imageops::{dither, ColorMap, FilterType},
+    DynamicImage, GrayImage, Luma,
+};
+use ppa6::{
+    content::{ContentHandler, Registry},
+    transform::Chain,
+    Backend, Concentration, Document, PrintOptions, Printer,
+};
+use std::{
+    fs::File,
+    io::{ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+// only the transforms are used here
+#[allow(dead_code)]
+#[path = "../config.rs"]
+mod config;
+
+/// Width of the print head in pixels.
+const WIDTH: u32 = 384;
+
+/// Resolution of the print head in dots per inch.
+const DPI: u32 = 203;
+
+/// Size of the page header after the sync word, see `cups_page_header2_t`.
+const HEADER_LEN: usize = 1796;
+
+/// Offsets of the header fields used here.
+const HW_RESOLUTION: usize = 276;
+const NUM_COPIES: usize = 340;
+const CUPS_WIDTH: usize = 372;
+const CUPS_HEIGHT: usize = 376;
+const CUPS_BITS_PER_COLOR: usize = 384;
+const CUPS_BITS_PER_PIXEL: usize = 388;
+const CUPS_BYTES_PER_LINE: usize = 392;
+const CUPS_COLOR_ORDER: usize = 396;
+const CUPS_COLOR_SPACE: usize = 400;
+
+/// Color spaces, see `cups_cspace_t`.
+const CSPACE_W: u32 = 0;
+const CSPACE_RGB: u32 = 1;
+const CSPACE_K: u32 = 3;
+const CSPACE_SW: u32 = 18;
+const CSPACE_SRGB: u32 = 19;
+const CSPACE_ADOBERGB: u32 = 20;
+
+/// Arguments of a CUPS filter, see `filter(7)`.
+#[derive(Parser)]
+struct Cli {
+    /// Job ID.
+    job: String,
+
+    /// User, who submitted the job.
+    user: String,
+
+    /// Job title.
+    title: String,
+
+    /// Number of copies, copies are taken from the raster headers instead.
+    copies: String,
+
+    /// Job options, e.g. `concentration=dark dither=threshold threshold=128 feed=50`.
+    options: String,
+
+    /// Raster file, stdin is read if it's missing.
+    file: Option<PathBuf>,
+}
+
+/// The job options, that this filter understands.
+struct Options {
+    concentration: Option<Concentration>,
+    threshold: u8,
+    dither: bool,
+    feed: u8,
+}
+
+impl Options {
+    fn parse(s: &str) -> Result<Self> {
+        let mut opts = Self {
+            concentration: None,
+            threshold: 0x80,
+            dither: true,
+            feed: 0,
+        };
+        for opt in s.split_whitespace() {
+            let (key, value) = opt.split_once('=').unwrap_or((opt, ""));
+            let value = value.trim_matches(['"', '\'']);
+            match key {
+                "concentration" => {
+                    opts.concentration = Some(
+                        value
+                            .parse()
+                            .map_err(|e| anyhow::anyhow!("concentration={value}: {e}"))?,
+                    )
+                }
+                "threshold" => opts.threshold = value.parse().context("invalid threshold")?,
+                "dither" => match value {
+                    "floyd-steinberg" => opts.dither = true,
+                    "threshold" | "none" => opts.dither = false,
+                    _ => bail!("dither={value}: expected floyd-steinberg or threshold"),
+                },
+                "feed" => opts.feed = value.parse().context("invalid feed")?,
+                _ => {}
+            }
+        }
+        Ok(opts)
+    }
+}
+
+/// A page header, see `cups_page_header2_t`.
+struct Header {
+    resolution: [u32; 2],
+    copies: u32,
+    width: u32,
+    height: u32,
+    bits_per_color: u32,
+    bits_per_pixel: u32,
+    bytes_per_line: u32,
+    color_order: u32,
+    color_space: u32,
+}
+
+/// A CUPS (v1, v2, or v3) or PWG raster stream.
+struct Raster<R> {
+    r: R,
+    big_endian: bool,
+    compressed: bool,
+}
+
+impl<R: Read> Raster<R> {
+    fn new(mut r: R) -> Result<Self> {
+        let mut sync = [0u8; 4];
+        r.read_exact(&mut sync).context("empty raster stream")?;
+        let (big_endian, compressed) = match &sync {
+            b"RaSt" => (true, false),
+            b"tSaR" => (false, false),
+            b"RaS2" => (true, true),
+            b"2SaR" => (false, true),
+            b"RaS3" => (true, false),
+            b"3SaR" => (false, false),
+            _ => bail!("not a CUPS or PWG raster stream: {sync:x?}"),
+        };
+        Ok(Self {
+            r,
+            big_endian,
+            compressed,
+        })
+    }
+
+    /// Read the next page header, `None` at the end of the stream.
+    fn header(&mut self) -> Result<Option<Header>> {
+        let mut buf = vec![0u8; HEADER_LEN];
+        let mut n = 0;
+        while n < buf.len() {
+            match self.r.read(&mut buf[n..]) {
+                Ok(0) if n == 0 => return Ok(None),
+                Ok(0) => bail!("truncated page header"),
+                Ok(k) => n += k,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let u32_at = |off: usize| {
+            let b = [buf[off], buf[off + 1], buf[off + 2], buf[off + 3]];
+            if self.big_endian {
+                u32::from_be_bytes(b)
+            } else {
+                u32::from_le_bytes(b)
+            }
+        };
+        Ok(Some(Header {
+            resolution: [u32_at(HW_RESOLUTION), u32_at(HW_RESOLUTION + 4)],
+            copies: u32_at(NUM_COPIES),
+            width: u32_at(CUPS_WIDTH),
+            height: u32_at(CUPS_HEIGHT),
+            bits_per_color: u32_at(CUPS_BITS_PER_COLOR),
+            bits_per_pixel: u32_at(CUPS_BITS_PER_PIXEL),
+            bytes_per_line: u32_at(CUPS_BYTES_PER_LINE),
+            color_order: u32_at(CUPS_COLOR_ORDER),
+            color_space: u32_at(CUPS_COLOR_SPACE),
+        }))
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        let mut b = [0u8];
+        self.r.read_exact(&mut b).context("truncated page")?;
+        Ok(b[0])
+    }
+
+    /// Decode one compressed line into `line`, returns how often it's repeated.
+    ///
+    /// Every line starts with a repeat count, followed by runs of pixels:
+    /// `0..=127` repeats the next pixel `n + 1` times, `128..=255` copies `257 - n` pixels.
+    fn decode_line(&mut self, line: &mut [u8], bpp: usize) -> Result<usize> {
+        let repeat = self.byte()? as usize + 1;
+        let mut x = 0;
+        while x < line.len() {
+            let n = self.byte()? as usize;
+            if n < 128 {
+                let mut pixel = vec![0u8; bpp];
+                self.r.read_exact(&mut pixel).context("truncated page")?;
+                for _ in 0..=n {
+                    let end = (x + bpp).min(line.len());
+                    line[x..end].copy_from_slice(&pixel[..end - x]);
+                    x = end;
+                }
+            } else {
+                let count = ((257 - n) * bpp).min(line.len() - x);
+                self.r
+                    .read_exact(&mut line[x..x + count])
+                    .context("truncated page")?;
+                x += count;
+            }
+        }
+        Ok(repeat)
+    }
+
+    /// Read the pixels of a page, and convert them to grayscale.
+    fn page(&mut self, h: &Header) -> Result<GrayImage> {
+        let gray = match (h.color_space, h.bits_per_pixel) {
+            (CSPACE_W | CSPACE_SW | CSPACE_K, 1 | 8 | 16) => true,
+            (CSPACE_RGB | CSPACE_SRGB | CSPACE_ADOBERGB, 24 | 48) if h.color_order == 0 => false,
+            (cs, bpp) => bail!(
+                "unsupported raster: color space {cs}, {bpp} bits per pixel, color order {}",
+                h.color_order
+            ),
+        };
+        let bpp = (h.bits_per_pixel as usize).div_ceil(8);
+        let stride = h.bytes_per_line as usize;
+        if stride < (h.width as usize * h.bits_per_pixel as usize).div_ceil(8) {
+            bail!("invalid raster: {stride} bytes per line are too few");
+        }
+
+        let mut img = GrayImage::new(h.width, h.height);
+        let mut line = vec![0u8; stride];
+        let mut y = 0;
+        while y < h.height {
+            let repeat = if self.compressed {
+                self.decode_line(&mut line, bpp)?
+            } else {
+                self.r.read_exact(&mut line).context("truncated page")?;
+                1
+            };
+            for _ in 0..repeat.min((h.height - y) as usize) {
+                for x in 0..h.width {
+                    let v = pixel(h, &line, x as usize, gray, self.big_endian);
+                    img.put_pixel(x, y, Luma([v]));
+                }
+                y += 1;
+            }
+        }
+        Ok(img)
+    }
+}
+
+/// Brightness of pixel `x` of `line`, 255 is white.
+fn pixel(h: &Header, line: &[u8], x: usize, gray: bool, big_endian: bool) -> u8 {
+    // the most significant byte of 16-bit samples
+    let sample = |i: usize| match h.bits_per_color {
+        16 if big_endian => line[i * 2],
+        16 => line[i * 2 + 1],
+        _ => line[i],
+    };
+    let v = if !gray {
+        let [r, g, b] = [0, 1, 2].map(|c| sample(x * 3 + c) as u32);
+        ((r * 299 + g * 587 + b * 114) / 1000) as u8
+    } else if h.bits_per_pixel == 1 {
+        if line[x / 8] & (0x80 >> (x % 8)) != 0 {
+            0xff
+        } else {
+            0x00
+        }
+    } else {
+        sample(x)
+    };
+
+    // in the K color space, 0 is white
+    if h.color_space == CSPACE_K {
+        !v
+    } else {
+        v
+    }
+}
+
+struct BlackWhiteMap(u8);
+
+impl ColorMap for BlackWhiteMap {
+    type Color = Luma<u8>;
+
+    fn index_of(&self, color: &Self::Color) -> usize {
+        (color.0[0] >= self.0) as usize
+    }
+
+    fn map_color(&self, color: &mut Self::Color) {
+        color.0[0] = if color.0[0] >= self.0 { 0xff } else { 0x00 };
+    }
+}
+
+/// Scale pages wider than the print head down, pad narrower ones with white, and make them black and white.
+fn document(img: GrayImage, opts: &Options) -> Result<Document> {
+    let mut img = if img.width() > WIDTH {
+        let height = (img.height() as u64 * WIDTH as u64 / img.width() as u64).max(1) as u32;
+        DynamicImage::ImageLuma8(img)
+            .resize_exact(WIDTH, height, FilterType::Triangle)
+            .into_luma8()
+    } else {
+        let mut padded = GrayImage::from_pixel(WIDTH, img.height(), Luma([0xff]));
+        image::imageops::replace(&mut padded, &img, 0, 0);
+        padded
+    };
+
+    if opts.dither {
+        dither(&mut img, &BlackWhiteMap(opts.threshold));
+    }
+
+    let pixels = img
+        .rows()
+        .flat_map(|row| {
+            row.collect::<Vec<_>>()
+                .chunks(8)
+                .map(|px| {
+                    px.iter().enumerate().fold(0u8, |acc, (i, p)| {
+                        acc | (((p.0[0] < opts.threshold) as u8) << (7 - i))
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    Ok(Document::new(pixels, WIDTH as u16)?)
+}
+
+/// CUPS and PWG raster streams, every page is scaled to the width of the print head.
+struct RasterHandler<'a>(&'a Options);
+
+impl ContentHandler for RasterHandler<'_> {
+    fn name(&self) -> &str {
+        "CUPS raster"
+    }
+
+    fn mime_types(&self) -> &[&str] {
+        &["application/vnd.cups-raster", "image/pwg-raster"]
+    }
+
+    fn sniff(&self, data: &[u8]) -> bool {
+        [b"RaSt", b"tSaR", b"RaS2", b"2SaR", b"RaS3", b"3SaR"]
+            .iter()
+            .any(|sync| data.starts_with(*sync))
+    }
+
+    /// All pages below each other, [`run()`] prints them one by one instead.
+    fn render(&self, data: &[u8], _width: u16) -> ppa6::Result<Document> {
+        let other = |e: anyhow::Error| ppa6::Error::Other(e.into());
+        let mut raster = Raster::new(data).map_err(other)?;
+        let mut pixels = Vec::new();
+        while let Some(header) = raster.header().map_err(other)? {
+            let img = raster.page(&header).map_err(other)?;
+            let doc = document(img, self.0).map_err(other)?;
+            pixels.extend_from_slice(doc.pixels());
+        }
+        Document::new(pixels, WIDTH as u16)
+    }
+}
+
+/// Writes the commands to stdout, for the CUPS backend.
+struct Stdout(std::io::Stdout);
+
+impl Backend for Stdout {
+    fn send(&mut self, buf: &[u8], _timeout: Duration) -> ppa6::Result<()> {
+        self.0.write_all(buf)?;
+        self.0.flush()?;
+        Ok(())
+    }
+
+    fn recv(&mut self, _buf: &mut [u8], _timeout: Duration) -> ppa6::Result<usize> {
+        // there is no back channel
+        Err(ppa6::Error::Timeout)
+    }
+}
+
+fn print(
+    printer: &mut Printer,
+    doc: Document,
+    copies: usize,
+    opts: &Options,
+    chain: &Chain,
+) -> Result<()> {
+    printer.print(
+        &chain.apply(doc)?,
+        &PrintOptions {
+            copies,
+            feed: opts.feed,
+            concentration: opts.concentration,
+            ..PrintOptions::default()
+        },
+    )?;
+    Ok(())
+}
+
+fn run(cli: &Cli) -> Result<()> {
+    let opts = Options::parse(&cli.options)?;
+    let mut data = Vec::new();
+    match &cli.file {
+        Some(path) => File::open(path)
+            .and_then(|mut f| f.read_to_end(&mut data))
+            .with_context(|| format!("{}", path.display()))?,
+        None => std::io::stdin().lock().read_to_end(&mut data)?,
+    };
+
+    let mut registry = Registry::with_builtins();
+    registry.register(RasterHandler(&opts));
+    // CUPS passes the MIME type of the input
+    let mime = std::env::var("CONTENT_TYPE").ok();
+    let Some(handler) = registry.resolve(&data, mime.as_deref(), cli.file.as_deref()) else {
+        bail!(
+            "cannot print {}, expected a CUPS or PWG raster",
+            mime.as_deref().unwrap_or("this input")
+        );
+    };
+    let mut printer = Printer::new(Stdout(std::io::stdout()));
+
+    // filters run without a home directory, set it with `SetEnv PPA6_CONFIG` in cupsd.conf
+    let chain = match std::env::var_os("PPA6_CONFIG") {
+        Some(path) => config::load(Some(Path::new(&path)))?.chain(),
+        None => Chain::new(),
+    };
+
+    if handler.name() != RasterHandler(&opts).name() {
+        eprintln!("INFO: printing {} input", handler.name());
+        let doc = handler.render(&data, WIDTH as u16)?;
+        let copies = cli.copies.trim().parse().unwrap_or(1usize).max(1);
+        print(&mut printer, doc, copies, &opts, &chain)?;
+        eprintln!("PAGE: 1 {copies}");
+        return Ok(());
+    }
+
+    let mut raster = Raster::new(&data[..])?;
+    let mut num = 0;
+    while let Some(header) = raster.header()? {
+        num += 1;
+        if header.resolution != [DPI, DPI] {
+            eprintln!(
+                "WARNING: page {num} has {}x{} dpi, the printer has {DPI} dpi",
+                header.resolution[0], header.resolution[1]
+            );
+        }
+        eprintln!(
+            "INFO: printing page {num}, {}x{} pixels",
+            header.width, header.height
+        );
+        let img = raster.page(&header)?;
+        if img.height() == 0 {
+            continue;
+        }
+
+        let copies = header.copies.max(1) as usize;
+        print(&mut printer, document(img, &opts)?, copies, &opts, &chain)?;
+        eprintln!("PAGE: {num} {copies}");
+    }
+
+    if num == 0 {
+        eprintln!("WARNING: job {} ({:?}) has no pages", cli.job, cli.title);
+    }
+    Ok(())
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(&cli) {
+        eprintln!("ERROR: {e:#}");
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A page header with `fields` at their offsets, in the byte order of `sync`.
+    fn header(sync: &[u8; 4], fields: &[(usize, u32)]) -> Vec<u8> {
+        let big_endian = sync[0] == b'R';
+        let mut buf = vec![0u8; HEADER_LEN];
+        for &(off, v) in fields {
+            let b = if big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            };
+            buf[off..off + 4].copy_from_slice(&b);
+        }
+        buf
+    }
+
+    fn raster(sync: &[u8; 4], fields: &[(usize, u32)], pixels: &[u8]) -> Vec<u8> {
+        [&sync[..], &header(sync, fields), pixels].concat()
+    }
+
+    fn pages(data: &[u8]) -> Vec<(Header, GrayImage)> {
+        let mut raster = Raster::new(data).unwrap();
+        let mut pages = Vec::new();
+        while let Some(h) = raster.header().unwrap() {
+            let img = raster.page(&h).unwrap();
+            pages.push((h, img));
+        }
+        pages
+    }
+
+    fn luma(img: &GrayImage) -> Vec<u8> {
+        img.pixels().map(|p| p.0[0]).collect()
+    }
+
+    #[test]
+    fn uncompressed_black_and_white() {
+        // 10x2, 1 bit per pixel, black is 1 in the K color space
+        let fields = [
+            (HW_RESOLUTION, 203),
+            (HW_RESOLUTION + 4, 203),
+            (NUM_COPIES, 2),
+            (CUPS_WIDTH, 10),
+            (CUPS_HEIGHT, 2),
+            (CUPS_BITS_PER_COLOR, 1),
+            (CUPS_BITS_PER_PIXEL, 1),
+            (CUPS_BYTES_PER_LINE, 2),
+            (CUPS_COLOR_SPACE, CSPACE_K),
+        ];
+        let data = raster(b"RaS3", &fields, &[0xc0, 0x40, 0xff, 0xff]);
+        for data in [data.clone(), [&data[..], &data[4..]].concat()] {
+            let pages = pages(&data);
+            let (h, img) = &pages[0];
+            assert_eq!((h.resolution, h.copies), ([203, 203], 2));
+            assert_eq!(img.dimensions(), (10, 2));
+            let mut expected = vec![0, 0, 255, 255, 255, 255, 255, 255, 255, 0];
+            expected.extend([0; 10]);
+            assert_eq!(luma(img), expected);
+        }
+        assert_eq!(pages(&[&data[..], &data[4..]].concat()).len(), 2);
+    }
+
+    #[test]
+    fn compressed_rgb() {
+        // 4x3, 8-bit RGB, little endian v2
+        let fields = [
+            (CUPS_WIDTH, 4),
+            (CUPS_HEIGHT, 3),
+            (CUPS_BITS_PER_COLOR, 8),
+            (CUPS_BITS_PER_PIXEL, 24),
+            (CUPS_BYTES_PER_LINE, 12),
+            (CUPS_COLOR_SPACE, CSPACE_SRGB),
+        ];
+        let lines = [
+            // line repeated twice: white pixel 3 times, then 1 copied black pixel
+            &[1, 2, 255, 255, 255, 255, 0, 0, 0][..],
+            // 2 copied pixels (pure red and green), then a gray one twice
+            &[0, 255, 255, 0, 0, 0, 255, 0, 1, 100, 100, 100],
+        ]
+        .concat();
+        let pages = pages(&raster(b"2SaR", &fields, &lines));
+        let (_, img) = &pages[0];
+        assert_eq!(
+            luma(img),
+            [255, 255, 255, 0, 255, 255, 255, 0, 76, 149, 100, 100]
+        );
+    }
+
+    #[test]
+    fn sixteen_bit_gray() {
+        let fields = [
+            (CUPS_WIDTH, 2),
+            (CUPS_HEIGHT, 1),
+            (CUPS_BITS_PER_COLOR, 16),
+            (CUPS_BITS_PER_PIXEL, 16),
+            (CUPS_BYTES_PER_LINE, 4),
+            (CUPS_COLOR_SPACE, CSPACE_SW),
+        ];
+        let big = pages(&raster(b"RaSt", &fields, &[0x12, 0x34, 0xab, 0xcd]));
+        assert_eq!(luma(&big[0].1), [0x12, 0xab]);
+        let little = pages(&raster(b"tSaR", &fields, &[0x34, 0x12, 0xcd, 0xab]));
+        assert_eq!(luma(&little[0].1), [0x12, 0xab]);
+    }
+
+    #[test]
+    fn invalid_rasters() {
+        assert!(Raster::new(&b"PNG\0"[..]).is_err());
+        assert!(Raster::new(&b""[..]).is_err());
+
+        let mut truncated = Raster::new(&b"RaS2\0\0\0"[..]).unwrap();
+        assert!(truncated.header().is_err());
+
+        let fields = [
+            (CUPS_WIDTH, 8),
+            (CUPS_HEIGHT, 2),
+            (CUPS_BITS_PER_PIXEL, 8),
+            (CUPS_BYTES_PER_LINE, 8),
+            (CUPS_COLOR_SPACE, CSPACE_W),
+        ];
+        // one line is missing
+        let data = raster(b"RaSt", &fields, &[0; 8]);
+        let mut r = Raster::new(&data[..]).unwrap();
+        let h = r.header().unwrap().unwrap();
+        assert!(r.page(&h).is_err());
+
+        // CMYK isn't supported, and lines must hold the pixels
+        for (off, v) in [(CUPS_COLOR_SPACE, 6), (CUPS_BYTES_PER_LINE, 4)] {
+            let mut fields = fields.to_vec();
+            fields.push((off, v));
+            let data = raster(b"RaSt", &fields, &[0; 16]);
+            let mut r = Raster::new(&data[..]).unwrap();
+            let h = r.header().unwrap().unwrap();
+            assert!(r.page(&h).is_err());
+        }
+    }
+
+    #[test]
+    fn options() {
+        let opts = Options::parse("job-uuid=x concentration=2 dither='threshold' feed=30").unwrap();
+        assert_eq!(opts.concentration, Some(Concentration::from(2)));
+        assert!(!opts.dither);
+        assert_eq!((opts.threshold, opts.feed), (0x80, 30));
+        assert!(Options::parse("dither=bayer").is_err());
+        assert!(Options::parse("feed=-1").is_err());
+    }
+
+    #[test]
+    fn pages_are_padded_and_packed() {
+        let opts = Options::parse("dither=none").unwrap();
+        let img = GrayImage::from_fn(10, 1, |x, _| Luma([if x < 4 { 0 } else { 255 }]));
+        let doc = document(img, &opts).unwrap();
+        assert_eq!(doc.width(), 384);
+        assert_eq!(doc.pixels()[0], 0xf0);
+        assert!(doc.pixels()[1..].iter().all(|&b| b == 0));
+
+        let wide = GrayImage::from_pixel(768, 4, Luma([0]));
+        let doc = document(wide, &opts).unwrap();
+        assert_eq!((doc.width(), doc.height()), (384, 2));
+        assert!(doc.pixels().iter().all(|&b| b == 0xff));
+    }
+
+    #[test]
+    fn registry() {
+        let opts = Options::parse("dither=none").unwrap();
+        let mut registry = Registry::with_builtins();
+        registry.register(RasterHandler(&opts));
+        let name = |data: &[u8], mime: Option<&str>| {
+            registry
+                .resolve(data, mime, None)
+                .map(|h| h.name().to_owned())
+        };
+
+        let fields = [
+            (CUPS_WIDTH, 8),
+            (CUPS_HEIGHT, 1),
+            (CUPS_BITS_PER_COLOR, 8),
+            (CUPS_BITS_PER_PIXEL, 8),
+            (CUPS_BYTES_PER_LINE, 8),
+            (CUPS_COLOR_SPACE, CSPACE_SW),
+        ];
+        let page = raster(b"RaS3", &fields, &[0; 8]);
+        let data = [&page[..], &page[4..]].concat();
+        assert_eq!(name(&data, None).as_deref(), Some("CUPS raster"));
+        assert_eq!(
+            name(b"P4 8 1 \xff", Some("image/x-portable-bitmap")).as_deref(),
+            Some("PBM")
+        );
+        assert_eq!(name(b"%PDF-1.7", None), None);
+
+        // both pages, black and padded to the print head
+        let doc = RasterHandler(&opts).render(&data, WIDTH as u16).unwrap();
+        assert_eq!((doc.width(), doc.height()), (WIDTH as u16, 2));
+        assert_eq!(doc.pixels()[0], 0xff);
+        assert_eq!(doc.pixels()[1], 0x00);
+    }
+}
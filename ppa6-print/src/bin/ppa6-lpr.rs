@@ -0,0 +1,151 @@
+//! A minimal `lpr(1)` replacement, which forwards jobs to a `ppa6-print --spool` FIFO.
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use std::{
+    fs::OpenOptions,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+#[derive(Parser)]
+#[command(disable_help_flag = true)]
+struct Cli {
+    /// Files to be printed, stdin is read if none are given.
+    files: Vec<PathBuf>,
+
+    /// Name of the printer, jobs are written to `/var/run/<printer>.fifo`.
+    /// Defaults to $PRINTER, or `ppa6`. Only letters, digits, `_` and `-` are allowed.
+    #[arg(short = 'P')]
+    printer: Option<String>,
+
+    /// Number of copies.
+    #[arg(short = '#', default_value_t = 1)]
+    copies: usize,
+
+    /// Job title, only used for logging.
+    #[arg(short = 'T')]
+    title: Option<String>,
+
+    /// Job name, only used for logging.
+    #[arg(short = 'J')]
+    job: Option<String>,
+
+    /// Ignored, for compatibility with lpr.
+    #[arg(short = 'h')]
+    no_banner: bool,
+
+    /// Ignored, for compatibility with lpr.
+    #[arg(short = 'l')]
+    literal: bool,
+
+    /// Ignored, for compatibility with lpr.
+    #[arg(short = 'm')]
+    mail: bool,
+
+    /// Ignored, for compatibility with lpr.
+    #[arg(short = 's')]
+    symlink: bool,
+
+    /// Ignored, for compatibility with lpr.
+    #[arg(short = 'C')]
+    class: Option<String>,
+
+    /// Ignored, for compatibility with lpr.
+    #[arg(short = 'U')]
+    user: Option<String>,
+
+    /// Print help.
+    #[arg(long, action = clap::ArgAction::Help)]
+    help: Option<bool>,
+}
+
+/// Turn the input into spool lines, see `ppa6-print --spool`.
+fn jobs(cli: &Cli) -> Result<Vec<String>> {
+    if !cli.files.is_empty() {
+        return cli
+            .files
+            .iter()
+            .map(|file| {
+                // the spooler runs in a different working directory
+                let path = file
+                    .canonicalize()
+                    .with_context(|| format!("{}", file.display()))?;
+                match path.to_str() {
+                    Some(s) if !s.contains('\n') => Ok(format!("file:{s}")),
+                    _ => bail!("{}: unsupported file name", path.display()),
+                }
+            })
+            .collect();
+    }
+
+    let mut data = Vec::new();
+    std::io::stdin().read_to_end(&mut data)?;
+    let Ok(text) = String::from_utf8(data) else {
+        bail!("binary data on stdin is not supported, pass the file as an argument");
+    };
+    // a line like `file:/etc/shadow` must not become a file job
+    Ok(text.lines().map(|line| format!("text:{line}")).collect())
+}
+
+/// Check that `name` can't point outside of `/var/run`, e.g. `../etc/foo`.
+fn check_printer(name: &str) -> Result<()> {
+    let valid = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    if name.is_empty() || !name.chars().all(valid) {
+        bail!("invalid printer name {name:?}, only letters, digits, `_` and `-` are allowed");
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    let printer = cli
+        .printer
+        .clone()
+        .or_else(|| std::env::var("PRINTER").ok())
+        .unwrap_or_else(|| "ppa6".to_owned());
+    check_printer(&printer)?;
+    let fifo = Path::new("/var/run").join(format!("{printer}.fifo"));
+
+    let jobs = jobs(&cli)?;
+    if let Some(title) = cli.title.as_ref().or(cli.job.as_ref()) {
+        eprintln!("ppa6-lpr: spooling {title:?} to {}", fifo.display());
+    }
+
+    let mut spool = OpenOptions::new()
+        .write(true)
+        .open(&fifo)
+        .with_context(|| format!("cannot open spool {}", fifo.display()))?;
+
+    for _ in 0..cli.copies {
+        for job in &jobs {
+            // one write per job, so that concurrent writers don't interleave lines
+            spool.write_all(format!("{job}\n").as_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn printer_names() {
+        for name in ["ppa6", "PPA6_kitchen-2"] {
+            assert!(check_printer(name).is_ok(), "{name}");
+        }
+        for name in [
+            "",
+            "../ppa6",
+            "a/b",
+            "ppa6.fifo",
+            "/dev/null",
+            "ppa\n6",
+            "drucker\u{e4}",
+        ] {
+            assert!(check_printer(name).is_err(), "{name}");
+        }
+    }
+}
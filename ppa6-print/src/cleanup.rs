@@ -0,0 +1,88 @@
+use image::{imageops::blur, GrayImage, Luma};
+
+/// Blur radius of the estimated background, in pixels.
+const BACKGROUND_SIGMA: f32 = 12.0;
+
+/// Side of the window for adaptive thresholding, in pixels.
+const WINDOW: u32 = 24;
+
+/// Pixels darker than the mean of their window by this many percent are black.
+const SENSITIVITY: u64 = 12;
+
+/// Black pixels with fewer black neighbours than this are removed as noise.
+const MIN_NEIGHBOURS: usize = 2;
+
+/// Clean up a photo of a paper document: even out lighting, binarize, and remove specks.
+/// The result is pure black and white, so it doesn't need dithering.
+pub fn cleanup(img: &GrayImage) -> GrayImage {
+    log::trace!("flattening background...");
+    let img = flatten(img);
+    log::trace!("thresholding...");
+    let img = bradley(&img);
+    log::trace!("despeckling...");
+    despeckle(&img)
+}
+
+/// Divide by a heavily blurred copy, which removes shadows and uneven lighting.
+fn flatten(img: &GrayImage) -> GrayImage {
+    let background = blur(img, BACKGROUND_SIGMA);
+    GrayImage::from_fn(img.width(), img.height(), |x, y| {
+        let p = img.get_pixel(x, y).0[0] as u32;
+        let bg = background.get_pixel(x, y).0[0].max(1) as u32;
+        Luma([(p * 255 / bg).min(255) as u8])
+    })
+}
+
+/// Bradley's adaptive thresholding, using an integral image.
+fn bradley(img: &GrayImage) -> GrayImage {
+    let (w, h) = img.dimensions();
+    let stride = w as usize + 1;
+    let mut sums = vec![0u64; stride * (h as usize + 1)];
+    for y in 0..h as usize {
+        let mut row = 0u64;
+        for x in 0..w as usize {
+            row += img.get_pixel(x as u32, y as u32).0[0] as u64;
+            sums[(y + 1) * stride + x + 1] = sums[y * stride + x + 1] + row;
+        }
+    }
+
+    let r = WINDOW / 2;
+    GrayImage::from_fn(w, h, |x, y| {
+        let (x0, y0) = (x.saturating_sub(r) as usize, y.saturating_sub(r) as usize);
+        let (x1, y1) = ((x + r + 1).min(w) as usize, (y + r + 1).min(h) as usize);
+        let area = ((x1 - x0) * (y1 - y0)) as u64;
+        let sum = sums[y1 * stride + x1] + sums[y0 * stride + x0]
+            - sums[y0 * stride + x1]
+            - sums[y1 * stride + x0];
+        let p = img.get_pixel(x, y).0[0] as u64;
+        let black = p * area * 100 < sum * (100 - SENSITIVITY);
+        Luma([if black { 0x00 } else { 0xff }])
+    })
+}
+
+/// Remove black pixels, that are (almost) isolated.
+fn despeckle(img: &GrayImage) -> GrayImage {
+    let (w, h) = img.dimensions();
+    let black = |x: i64, y: i64| {
+        x >= 0
+            && y >= 0
+            && x < w as i64
+            && y < h as i64
+            && img.get_pixel(x as u32, y as u32).0[0] == 0
+    };
+    GrayImage::from_fn(w, h, |x, y| {
+        let (x, y) = (x as i64, y as i64);
+        if !black(x, y) {
+            return Luma([0xff]);
+        }
+        let neighbours = (-1..=1)
+            .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+            .filter(|&(dx, dy)| (dx, dy) != (0, 0) && black(x + dx, y + dy))
+            .count();
+        Luma([if neighbours < MIN_NEIGHBOURS {
+            0xff
+        } else {
+            0x00
+        }])
+    })
+}
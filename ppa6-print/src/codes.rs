@@ -0,0 +1,73 @@
+use anyhow::{bail, Result};
+use clap::Args;
+use image::GrayImage;
+use std::io::Read;
+
+use crate::{
+    stack,
+    text::{text, TextStyle},
+    Cli,
+};
+
+#[derive(Args, Clone)]
+pub struct CodesArgs {
+    /// Service or account the codes belong to, printed in the header.
+    #[arg(long)]
+    title: Option<String>,
+
+    /// Number of columns for the codes.
+    #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u8).range(1..=4))]
+    columns: u8,
+}
+
+/// Render a sheet of 2FA backup codes, read from stdin one per line.
+/// Each code gets a checkbox to tick off when it has been used.
+pub fn codes(cli: &Cli, args: &CodesArgs) -> Result<GrayImage> {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+    let codes: Vec<&str> = input
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect();
+    if codes.is_empty() {
+        bail!("no backup codes on stdin");
+    }
+
+    let mut header = cli.font.clone();
+    header.style = vec![TextStyle::Inverted];
+    header.columns = 1;
+    header.fit = true;
+    header.fit_lines = None;
+    let title = match &args.title {
+        Some(title) => format!(" BACKUP CODES \n {title} "),
+        None => " BACKUP CODES ".to_owned(),
+    };
+
+    let mut warning = cli.font.clone();
+    warning.style.clear();
+    warning.columns = 1;
+    warning.fit = false;
+    warning.fit_lines = None;
+    warning.size = warning.size.min(16.0);
+    let warning_text = "Keep this sheet secret. Every code can only be used once, \
+        tick it off after use.\n";
+
+    let mut list = cli.font.clone();
+    list.style.clear();
+    list.mono = true;
+    list.columns = args.columns;
+    list.fit = false;
+    list.fit_lines = None;
+    let list_text = codes
+        .iter()
+        .map(|code| format!("[ ] {code}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(stack(&[
+        text(&header, title.as_bytes())?,
+        text(&warning, warning_text.as_bytes())?,
+        text(&list, list_text.as_bytes())?,
+    ]))
+}
@@ -0,0 +1,140 @@
+use anyhow::{bail, Context, Result};
+use ppa6::transform::{Chain, Mirror, Stamp, ThermalLimit, Trim};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// Settings from the config file, see `--config`.
+#[derive(Deserialize, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Path of the file, if one was loaded.
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+
+    /// Default print settings, named like the command line options, e.g. `concentration = "dark"`.
+    /// Options given on the command line take precedence.
+    #[serde(default)]
+    pub print: toml::Table,
+
+    /// Named sets of print settings, selected with `--profile`, as `[profile.NAME]` tables.
+    /// A profile can build on another one with `extends = "NAME"`,
+    /// the profile `default` is used, if no other one is selected.
+    #[serde(default)]
+    pub profile: BTreeMap<String, toml::Table>,
+
+    /// Print settings for a kind of input, as `[type.NAME]` tables, e.g. `[type.png]`, `[type."image/*"]`,
+    /// or `[type.text]`, see `settings::apply_type()`. They take precedence over the profile.
+    #[serde(default, rename = "type")]
+    pub types: BTreeMap<String, toml::Table>,
+
+    /// Transforms applied to every job, in order, as `[[transform]]` tables.
+    #[serde(default, rename = "transform")]
+    pub transforms: Vec<TransformConfig>,
+}
+
+/// A [`ppa6::transform::Transform`], selected by its `kind`, e.g.
+///
+/// ```toml
+/// [[transform]]
+/// kind = "thermal-limit"
+/// max-coverage = 0.4
+/// ```
+#[derive(Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum TransformConfig {
+    Trim {
+        #[serde(default)]
+        top: usize,
+        #[serde(default)]
+        bottom: usize,
+    },
+    Stamp {
+        text: String,
+    },
+    #[serde(rename_all = "kebab-case")]
+    ThermalLimit {
+        max_coverage: f32,
+        #[serde(default = "default_band_height")]
+        band_height: usize,
+    },
+    Mirror,
+}
+
+fn default_band_height() -> usize {
+    ThermalLimit::default().band_height
+}
+
+impl Config {
+    /// Parse a config file, and check the values, that serde can't.
+    pub fn parse(data: &str) -> Result<Self> {
+        let config: Self = toml::from_str(data)?;
+        for t in &config.transforms {
+            if let TransformConfig::ThermalLimit {
+                max_coverage,
+                band_height,
+            } = t
+            {
+                if !(*max_coverage > 0.0 && *max_coverage <= 1.0) {
+                    bail!("thermal-limit: max-coverage must be above 0 and at most 1, not {max_coverage}");
+                }
+                if *band_height == 0 {
+                    bail!("thermal-limit: band-height must be at least 1");
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// The transforms as a chain.
+    pub fn chain(&self) -> Chain {
+        let mut chain = Chain::new();
+        for t in &self.transforms {
+            match t {
+                TransformConfig::Trim { top, bottom } => chain.push(Trim {
+                    top: *top,
+                    bottom: *bottom,
+                }),
+                TransformConfig::Stamp { text } => chain.push(Stamp(text.clone())),
+                TransformConfig::ThermalLimit {
+                    max_coverage,
+                    band_height,
+                } => chain.push(ThermalLimit {
+                    max_coverage: *max_coverage,
+                    band_height: *band_height,
+                }),
+                TransformConfig::Mirror => chain.push(Mirror),
+            };
+        }
+        chain
+    }
+}
+
+/// `$XDG_CONFIG_HOME/ppa6/config.toml`, or `~/.config/ppa6/config.toml`.
+pub fn default_path() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(dir.join("ppa6").join("config.toml"))
+}
+
+/// Load the config file at `path`, or at the default path, if it exists.
+pub fn load(path: Option<&Path>) -> Result<Config> {
+    let path = match path {
+        Some(path) => path.to_owned(),
+        None => match default_path().filter(|p| p.exists()) {
+            Some(path) => path,
+            None => return Ok(Config::default()),
+        },
+    };
+
+    log::debug!("loading config from {}", path.display());
+    let data = std::fs::read_to_string(&path)
+        .with_context(|| format!("cannot read config {}", path.display()))?;
+    let mut config =
+        Config::parse(&data).with_context(|| format!("invalid config {}", path.display()))?;
+    config.path = Some(path);
+    Ok(config)
+}
@@ -0,0 +1,91 @@
+use anyhow::Result;
+use clap::Args;
+use ppa6::{Band, Document};
+use std::{path::PathBuf, time::Duration};
+
+use crate::{document, read_input, render, Cli};
+
+/// Rows per millimeter, the printer has 203dpi.
+const ROWS_PER_MM: f64 = 8.0;
+
+/// Paper speed while printing, in mm/s.
+const PRINT_SPEED: f64 = 15.0;
+
+/// Paper speed while feeding blank paper, in mm/s.
+const FEED_SPEED: f64 = 25.0;
+
+/// Chunk size and pause used by `Printer::print_image_chunked()`.
+const CHUNK_ROWS: usize = 24;
+const CHUNK_DELAY: Duration = Duration::from_millis(50);
+
+/// Rough battery use in percent per meter of paper, for moving the paper and for burning black pixels.
+const BATTERY_PER_METER: f64 = 2.0;
+const BATTERY_PER_BLACK_METER: f64 = 40.0;
+
+#[derive(Args, Clone)]
+pub struct EstimateArgs {
+    /// Path to the file, that would be printed.
+    file: PathBuf,
+}
+
+/// Expected cost of printing a document.
+struct Estimate {
+    rows: usize,
+    coverage: f64,
+    time: Duration,
+    battery: f64,
+}
+
+impl Estimate {
+    fn new(doc: &Document, feed: usize) -> Self {
+        let mut time = Duration::ZERO;
+        for band in doc.bands(CHUNK_ROWS) {
+            time += match band {
+                Band::Ink(rows) => {
+                    let chunks = rows.len().div_ceil(CHUNK_ROWS) as u32;
+                    Duration::from_secs_f64(rows.len() as f64 / ROWS_PER_MM / PRINT_SPEED)
+                        + CHUNK_DELAY * chunks
+                }
+                Band::Blank(n) => Duration::from_secs_f64(n as f64 / ROWS_PER_MM / FEED_SPEED),
+            };
+        }
+        time += Duration::from_secs_f64(feed as f64 / ROWS_PER_MM / FEED_SPEED);
+
+        let black: u64 = doc.pixels().iter().map(|b| b.count_ones() as u64).sum();
+        let pixels = (doc.pixels().len() * 8).max(1);
+        let coverage = black as f64 / pixels as f64;
+
+        let rows = doc.height() + feed;
+        let meters = rows as f64 / ROWS_PER_MM / 1000.0;
+        let battery = meters * BATTERY_PER_METER
+            + doc.height() as f64 / ROWS_PER_MM / 1000.0 * coverage * BATTERY_PER_BLACK_METER;
+
+        Self {
+            rows,
+            coverage,
+            time,
+            battery,
+        }
+    }
+}
+
+/// Rough battery use in percent for printing `doc` and feeding `feed` rows afterwards.
+pub fn battery(doc: &Document, feed: usize) -> f64 {
+    Estimate::new(doc, feed).battery
+}
+
+/// Print an estimate of paper length, coverage, time and battery use, without printing.
+pub fn estimate(cli: &Cli, args: &EstimateArgs) -> Result<()> {
+    let (data, mime) = read_input(cli, &args.file)?;
+    let doc = document(cli, render(cli, &data, mime.as_deref(), Some(&args.file))?)?;
+    let feed = if cli.feed { 0x60 } else { 0 };
+    let est = Estimate::new(&doc, feed);
+
+    let copies = cli.num as f64;
+    let length = est.rows as f64 / ROWS_PER_MM * copies;
+    println!("Paper:    {:.0}mm ({} rows)", length, est.rows * cli.num);
+    println!("Coverage: {:.1}% black", est.coverage * 100.0);
+    println!("Time:     ~{:.0?}", est.time.mul_f64(copies));
+    println!("Battery:  ~{:.1}%", est.battery * copies);
+    Ok(())
+}
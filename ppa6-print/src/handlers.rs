@@ -0,0 +1,195 @@
+use image::GrayImage;
+use ppa6::{
+    content::{ContentHandler, EscPosHandler, PbmHandler, Registry},
+    Document, Error,
+};
+
+use crate::{
+    markdown::markdown, pages, picture, rasterize, receipt, separated, text::text, to_gray, Cli,
+};
+
+/// Convert a rendered image into a document, see [`rasterize()`].
+fn to_document(cli: &Cli, img: anyhow::Result<GrayImage>) -> ppa6::Result<Document> {
+    img.and_then(|img| rasterize(cli, &img))
+        .map_err(|e| Error::Other(e.into()))
+}
+
+/// Images, rotated, resized, and dithered as set on the command line.
+struct ImageHandler<'a>(&'a Cli);
+
+impl ContentHandler for ImageHandler<'_> {
+    fn name(&self) -> &str {
+        "image"
+    }
+
+    fn mime_types(&self) -> &[&str] {
+        &["image/*"]
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &[
+            "png", "jpg", "jpeg", "gif", "bmp", "pgm", "ppm", "pam", "pnm", "tif", "tiff", "webp",
+            "ico", "tga", "qoi",
+        ]
+    }
+
+    fn sniff(&self, data: &[u8]) -> bool {
+        image::guess_format(data).is_ok()
+    }
+
+    fn render(&self, data: &[u8], _width: u16) -> ppa6::Result<Document> {
+        to_document(self.0, picture(self.0, data))
+    }
+}
+
+/// Text, rendered with the font set on the command line.
+struct TextHandler<'a>(&'a Cli);
+
+impl ContentHandler for TextHandler<'_> {
+    fn name(&self) -> &str {
+        "text"
+    }
+
+    fn mime_types(&self) -> &[&str] {
+        &["text/*"]
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["txt", "text", "log", "csv"]
+    }
+
+    /// UTF-8 without control characters, other than whitespace.
+    fn sniff(&self, data: &[u8]) -> bool {
+        std::str::from_utf8(data).is_ok_and(|s| {
+            !s.chars()
+                .any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r' | '\x0c'))
+        })
+    }
+
+    fn render(&self, data: &[u8], _width: u16) -> ppa6::Result<Document> {
+        to_document(self.0, text(&self.0.font, data))
+    }
+}
+
+/// Markdown, rendered with the font set on the command line, see [`markdown()`].
+struct MarkdownHandler<'a>(&'a Cli);
+
+impl ContentHandler for MarkdownHandler<'_> {
+    fn name(&self) -> &str {
+        "markdown"
+    }
+
+    fn mime_types(&self) -> &[&str] {
+        &["text/markdown", "text/x-markdown"]
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["md", "markdown"]
+    }
+
+    fn render(&self, data: &[u8], _width: u16) -> ppa6::Result<Document> {
+        to_document(self.0, markdown(&self.0.font, data))
+    }
+}
+
+/// PDFs, every page like an image, see [`pages::pdf_pages()`].
+struct PdfHandler<'a>(&'a Cli);
+
+impl ContentHandler for PdfHandler<'_> {
+    fn name(&self) -> &str {
+        "PDF"
+    }
+
+    fn mime_types(&self) -> &[&str] {
+        &["application/pdf"]
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["pdf"]
+    }
+
+    fn sniff(&self, data: &[u8]) -> bool {
+        data.starts_with(b"%PDF-")
+    }
+
+    fn render(&self, data: &[u8], _width: u16) -> ppa6::Result<Document> {
+        let cli = self.0;
+        let img = pages::pdf_pages(data, &cli.pages, cli.width())
+            .and_then(|pages| separated(cli, pages.into_iter().map(|p| to_gray(cli, p)).collect()));
+        to_document(cli, img)
+    }
+}
+
+/// Receipts in the JSON format of `ppa6-print receipt`, recognized by their content.
+struct ReceiptHandler<'a>(&'a Cli);
+
+impl ContentHandler for ReceiptHandler<'_> {
+    fn name(&self) -> &str {
+        "receipt"
+    }
+
+    fn mime_types(&self) -> &[&str] {
+        &[]
+    }
+
+    fn sniff(&self, data: &[u8]) -> bool {
+        receipt::is_receipt(data)
+    }
+
+    fn render(&self, data: &[u8], _width: u16) -> ppa6::Result<Document> {
+        to_document(self.0, receipt::render(self.0, data, None))
+    }
+}
+
+/// Packed rows, as sent to [`listen()`](crate::listen()) by clients, that render themselves.
+/// There is no way to recognize them, so it takes anything.
+struct RawHandler;
+
+impl ContentHandler for RawHandler {
+    fn name(&self) -> &str {
+        "raw"
+    }
+
+    fn mime_types(&self) -> &[&str] {
+        &[]
+    }
+
+    fn sniff(&self, _data: &[u8]) -> bool {
+        true
+    }
+
+    fn render(&self, data: &[u8], width: u16) -> ppa6::Result<Document> {
+        // pad the last row, if the client sent a partial one
+        let mut pixels = data.to_vec();
+        pixels.resize(pixels.len().next_multiple_of(width as usize / 8), 0x00);
+        Document::new(pixels, width)
+    }
+}
+
+fn register<'a>(registry: &mut Registry<'a>, cli: &'a Cli) {
+    // PBM comes after the images, so that P4 bitmaps are printed as they are, without dithering
+    registry
+        .register(TextHandler(cli))
+        .register(MarkdownHandler(cli))
+        .register(ReceiptHandler(cli))
+        .register(ImageHandler(cli))
+        .register(PdfHandler(cli))
+        .register(EscPosHandler::default())
+        .register(PbmHandler);
+}
+
+/// The content handlers of the library, and those of the command line.
+/// Every way to print a file goes through them, so files are printed the same way everywhere.
+pub fn registry(cli: &Cli) -> Registry<'_> {
+    let mut registry = Registry::new();
+    register(&mut registry, cli);
+    registry
+}
+
+/// Like [`registry()`], but anything, that isn't recognized, is printed as packed rows.
+pub fn raw_registry(cli: &Cli) -> Registry<'_> {
+    let mut registry = Registry::new();
+    registry.register(RawHandler);
+    register(&mut registry, cli);
+    registry
+}
@@ -0,0 +1,78 @@
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use std::process::Command;
+use uuid::Uuid;
+
+/// Shell commands run around every job, e.g. to switch on a smart plug powering the printer.
+/// Like the other print settings, they can be set in the config file, e.g. `pre-job = "..."`.
+///
+/// The job's metadata is passed in the environment variables
+/// `PPA6_JOB_ID`, `PPA6_JOB_NAME`, `PPA6_COPIES` and, for `--on-error`, `PPA6_ERROR`.
+#[derive(Args, Clone, Default)]
+pub struct HookArgs {
+    /// Shell command to run before a job. If it fails, the job is not printed.
+    #[arg(long, value_name = "CMD", env = "PPA6_PRE_JOB")]
+    pub pre_job: Option<String>,
+
+    /// Shell command to run after a job was printed.
+    #[arg(long, value_name = "CMD", env = "PPA6_POST_JOB")]
+    pub post_job: Option<String>,
+
+    /// Shell command to run after a job failed.
+    #[arg(long, value_name = "CMD", env = "PPA6_ON_ERROR")]
+    pub on_error: Option<String>,
+}
+
+impl HookArgs {
+    /// Run `f` as job `id`, surrounded by the hooks.
+    pub fn run<T>(
+        &self,
+        id: Uuid,
+        name: &str,
+        copies: usize,
+        f: impl FnOnce() -> Result<T>,
+    ) -> Result<T> {
+        let env = [
+            ("PPA6_JOB_ID", id.to_string()),
+            ("PPA6_JOB_NAME", name.to_owned()),
+            ("PPA6_COPIES", copies.to_string()),
+        ];
+
+        let res = match &self.pre_job {
+            Some(cmd) => hook("pre-job", cmd, &env).and_then(|()| f()),
+            None => f(),
+        };
+
+        // the job already succeeded or failed, so hooks failing now is only worth a warning
+        let post = match &res {
+            Ok(_) => self
+                .post_job
+                .as_ref()
+                .map(|cmd| hook("post-job", cmd, &env)),
+            Err(e) => self.on_error.as_ref().map(|cmd| {
+                let mut env = env.to_vec();
+                env.push(("PPA6_ERROR", format!("{e:#}")));
+                hook("on-error", cmd, &env)
+            }),
+        };
+        if let Some(Err(e)) = post {
+            log::warn!("[{id}] {e:#}");
+        }
+
+        res
+    }
+}
+
+fn hook(name: &str, cmd: &str, env: &[(&str, String)]) -> Result<()> {
+    log::trace!("running {name} hook: {cmd}");
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .status()
+        .with_context(|| format!("cannot run {name} hook"))?;
+    if !status.success() {
+        bail!("{name} hook failed: {status}");
+    }
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use image::GrayImage;
+
+/// Smallest x-height in pixels, that is still legible on paper (about 0.75mm).
+const MIN_X_HEIGHT: usize = 6;
+
+/// Lines of similar height, needed to consider an image a text document.
+const MIN_LINES: usize = 3;
+
+/// Estimate the x-height of text in `img`, if it looks like a text document.
+///
+/// Rows with some, but not too much ink form lines of text.
+/// Their median height is about twice the x-height, because of ascenders and descenders.
+pub fn x_height(img: &GrayImage, threshold: u8) -> Option<usize> {
+    let width = img.width() as usize;
+    let inked: Vec<bool> = img
+        .rows()
+        .map(|row| {
+            let black = row.filter(|p| p.0[0] < threshold).count();
+            black * 100 >= width && black * 100 <= width * 60
+        })
+        .collect();
+
+    let mut lines: Vec<usize> = inked
+        .split(|&ink| !ink)
+        .map(|run| run.len())
+        .filter(|&h| h > 1)
+        .collect();
+    if lines.len() < MIN_LINES {
+        return None;
+    }
+
+    lines.sort_unstable();
+    let median = lines[lines.len() / 2];
+    let similar = lines
+        .iter()
+        .filter(|&&h| h * 2 >= median && h * 2 <= median * 3)
+        .count();
+    if similar < MIN_LINES || similar * 2 < lines.len() {
+        return None;
+    }
+    Some(median / 2)
+}
+
+/// Warn, if `img` looks like a text document, that is too small to read after scaling.
+pub fn check(img: &GrayImage, threshold: u8) {
+    let Some(x_height) = x_height(img, threshold) else {
+        return;
+    };
+    log::debug!("estimated x-height: {x_height}px");
+    if x_height < MIN_X_HEIGHT {
+        log::warn!(
+            "text will be about {x_height}px high, which is probably unreadable, \
+             try `--rotate 90`, or cropping the image into several parts"
+        );
+    }
+}
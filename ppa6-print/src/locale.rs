@@ -0,0 +1,181 @@
+/// Order of day, month and year in a date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DateOrder {
+    /// `2025-01-31`
+    Iso,
+    /// `01/31/2025`
+    Mdy,
+    /// `31/01/2025`, with `sep` between the parts.
+    Dmy(char),
+}
+
+/// Digits after the decimal separator of amounts of `currency`, an ISO 4217 code.
+/// Symbols and unknown codes get 2.
+pub fn decimals(currency: &str) -> usize {
+    match currency {
+        "JPY" | "KRW" | "ISK" | "HUF" | "CLP" | "VND" | "PYG" | "UGX" | "XAF" | "XOF" | "¥" => 0,
+        "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+        _ => 2,
+    }
+}
+
+/// Number, currency and date conventions of a locale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Locale {
+    decimal: char,
+    group: Option<char>,
+    symbol_first: bool,
+    date: DateOrder,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self {
+            decimal: '.',
+            group: Some(','),
+            symbol_first: true,
+            date: DateOrder::Iso,
+        }
+    }
+}
+
+impl Locale {
+    /// Look up a locale by its POSIX or BCP 47 tag, e.g. `de_AT.UTF-8` or `en-GB`.
+    /// Unknown languages get English number formatting and ISO dates.
+    pub fn from_tag(tag: &str) -> Self {
+        let tag = tag.split(['.', '@']).next().unwrap_or_default();
+        let mut parts = tag.split(['_', '-']);
+        let lang = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let region = parts.next().unwrap_or_default().to_ascii_uppercase();
+
+        let dmy = |decimal, group, sep| Self {
+            decimal,
+            group,
+            symbol_first: false,
+            date: DateOrder::Dmy(sep),
+        };
+
+        match (lang.as_str(), region.as_str()) {
+            ("en", "US") => Self {
+                date: DateOrder::Mdy,
+                ..Self::default()
+            },
+            ("en", "GB" | "IE" | "AU" | "NZ") => Self {
+                date: DateOrder::Dmy('/'),
+                ..Self::default()
+            },
+            ("de" | "it", "CH") => dmy('.', Some('\''), '.'),
+            ("de", _) => dmy(',', Some('.'), '.'),
+            ("nl" | "da" | "tr" | "id", _) => dmy(',', Some('.'), '-'),
+            ("it" | "es" | "pt", _) => dmy(',', Some('.'), '/'),
+            ("fr", _) => dmy(',', Some(' '), '/'),
+            ("sv", _) => Self {
+                date: DateOrder::Iso,
+                ..dmy(',', Some(' '), '-')
+            },
+            ("nb" | "fi" | "pl" | "cs" | "ru" | "uk", _) => dmy(',', Some(' '), '.'),
+            _ => Self::default(),
+        }
+    }
+
+    /// The locale of the environment, according to `LC_ALL`, `LC_NUMERIC` or `LANG`.
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .into_iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|s| !s.is_empty())
+            .map_or_else(Self::default, |tag| Self::from_tag(&tag))
+    }
+
+    /// Format `x` with `decimals` digits after the decimal separator.
+    pub fn number(&self, x: f64, decimals: usize) -> String {
+        let s = format!("{:.decimals$}", x.abs());
+        let (int, frac) = s.split_once('.').unwrap_or((&s, ""));
+
+        let mut out = String::new();
+        if x < 0.0 && s.bytes().any(|b| b.is_ascii_digit() && b != b'0') {
+            out.push('-');
+        }
+        for (i, c) in int.chars().enumerate() {
+            if i > 0 && (int.len() - i) % 3 == 0 {
+                out.extend(self.group);
+            }
+            out.push(c);
+        }
+        if !frac.is_empty() {
+            out.push(self.decimal);
+            out += frac;
+        }
+        out
+    }
+
+    /// Format `x` as an amount of `currency`, which is either an ISO 4217 code, or a symbol.
+    pub fn money(&self, x: f64, currency: &str) -> String {
+        let amount = self.number(x, decimals(currency));
+        let symbol = match currency {
+            "" => return amount,
+            "EUR" => "€",
+            "USD" => "$",
+            "GBP" => "£",
+            "JPY" => "¥",
+            s => s,
+        };
+
+        // codes get a space, symbols only if they go after the amount
+        let space = if symbol.chars().count() > 1 || !self.symbol_first {
+            " "
+        } else {
+            ""
+        };
+        if self.symbol_first {
+            format!("{symbol}{space}{amount}")
+        } else {
+            format!("{amount}{space}{symbol}")
+        }
+    }
+
+    /// Reformat an ISO 8601 date (`2025-01-31`), optionally followed by a time (`T12:34:56`).
+    /// Anything, that doesn't look like that is returned as-is.
+    pub fn date(&self, iso: &str) -> String {
+        let (date, time) = iso.split_once(['T', ' ']).unwrap_or((iso, ""));
+        let mut parts = date.splitn(3, '-');
+        let (Some(y), Some(m), Some(d)) = (parts.next(), parts.next(), parts.next()) else {
+            return iso.to_owned();
+        };
+        if ![y, m, d]
+            .iter()
+            .all(|s| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()))
+        {
+            return iso.to_owned();
+        }
+
+        let date = match self.date {
+            DateOrder::Iso => date.to_owned(),
+            DateOrder::Mdy => format!("{m}/{d}/{y}"),
+            DateOrder::Dmy(sep) => format!("{d}{sep}{m}{sep}{y}"),
+        };
+
+        // drop seconds and time zone
+        let time = time.get(..5).unwrap_or(time);
+        if time.is_empty() {
+            date
+        } else {
+            format!("{date} {time}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn money() {
+        let en = Locale::from_tag("en_US");
+        assert_eq!(en.money(1234.5, "USD"), "$1,234.50");
+        assert_eq!(en.money(1234.5, "JPY"), "¥1,234");
+        assert_eq!(en.money(1.2345, "KWD"), "KWD 1.234");
+        assert_eq!(Locale::from_tag("de_AT").money(-0.5, "EUR"), "-0,50 €");
+        assert_eq!(en.money(3.0, ""), "3.00");
+    }
+}
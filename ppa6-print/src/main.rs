@@ -1,30 +1,94 @@
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use clap_num::maybe_hex;
 use clap_verbosity::Verbosity;
-use cosmic_text::{Attrs, Buffer, Color, FontSystem, Metrics, Shaping, SwashCache};
 use image::{
+    codecs::gif::GifDecoder,
     imageops::{dither, ColorMap, FilterType},
-    DynamicImage, GrayImage, ImageFormat, ImageReader, Luma, RgbImage,
+    AnimationDecoder, DynamicImage, GrayImage, ImageFormat, ImageReader, Luma,
+};
+use indicatif::{ProgressBar, ProgressStyle};
+use power::Power;
+use ppa6::{
+    Concentration, Document, EmulatorBackend, EndOfImage, FileBackend, Framing, Model,
+    PrintOptions, Printer, TcpBackend, ThermalPolicy,
 };
-use ppa6::{FileBackend, Printer};
 use rayon::prelude::*;
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
 use std::{
-    io::{Cursor, Read},
+    fs::File,
+    io::{BufRead, BufReader, Cursor, Read},
+    net::{TcpListener, TcpStream},
+    ops::RangeInclusive,
     path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
+use text::{text, TextArgs};
+use uuid::Uuid;
+
+mod bench;
+mod cleanup;
+mod codes;
+mod config;
+mod estimate;
+mod handlers;
+mod hooks;
+mod legibility;
+mod locale;
+mod markdown;
+mod pages;
+mod power;
+mod qr;
+mod receipt;
+mod ruler;
+mod settings;
+mod setup;
+mod text;
+mod update;
+mod web;
 
-#[derive(Parser)]
+#[derive(Parser, Clone)]
+#[command(
+    subcommand_negates_reqs = true,
+    override_usage = "ppa6-print [OPTIONS] <FILE>\n       ppa6-print [OPTIONS] <COMMAND>"
+)]
 struct Cli {
-    /// Path to the file to be printed.
-    file: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Path to the file to be printed, a `data:` URI,
+    /// or an http(s):// URL (requires the `net` feature).
+    #[arg(required_unless_present_any = ["spool", "listen", "explain"])]
+    file: Option<PathBuf>,
+
+    /// Read newline-delimited jobs from a spool (usually a FIFO created with mkfifo(1)).
+    /// Lines of the form `file:/absolute/path` print that file, with the permissions of ppa6-print,
+    /// every other line is printed as text, after removing a `text:` prefix.
+    #[arg(long, conflicts_with_all = ["file", "show"])]
+    spool: Option<PathBuf>,
+
+    /// Listen for raw raster jobs on a TCP address, like a JetDirect printer (usually port 9100).
+    /// Each connection is one job of packed rows as wide as `--model`, 1 bit per pixel, MSB first.
+    #[arg(long, value_name = "ADDR", conflicts_with_all = ["file", "show", "spool"])]
+    listen: Option<String>,
+
+    /// Format of the data read from stdin, if `file` is `-`.
+    #[arg(long, value_enum, default_value_t = StdinFormat::Raw)]
+    stdin_format: StdinFormat,
 
     /// Path to the device file.
-    #[arg(short, long)]
+    #[arg(short, long, env = "PPA6_DEVICE")]
     device: Option<PathBuf>,
 
+    /// Address of a relay sharing the printer over the network, like a port 9100 printer,
+    /// e.g. `raspberrypi.local:9100`.
+    #[arg(long, value_name = "ADDR", env = "PPA6_TCP")]
+    tcp: Option<String>,
+
     /// Number of copies.
-    #[arg(short, long, default_value_t = 1)]
+    #[arg(short, long, default_value_t = 1, env = "PPA6_NUM")]
     num: usize,
 
     /// Show the image instead of printing.
@@ -32,53 +96,328 @@ struct Cli {
     show: bool,
 
     /// Feed the printer.
-    #[arg(short, long)]
+    #[arg(short, long, env = "PPA6_FEED")]
     feed: bool,
 
     /// Invert the printed image.
-    #[arg(short, long)]
+    #[arg(short, long, env = "PPA6_INVERT")]
     invert: bool,
 
     /// Rotate the image by 0, 90, 180, or 270 degrees.
-    #[arg(short, long, default_value_t = 0)]
+    #[arg(short, long, default_value_t = 0, env = "PPA6_ROTATE")]
     rotate: usize,
 
     /// Threshold for dithering.
-    #[arg(short = 'T', long, default_value_t = 0x80, value_parser = maybe_hex::<u8>)]
+    #[arg(
+        short = 'T',
+        long,
+        default_value_t = 0x80,
+        value_parser = maybe_hex::<u8>,
+        env = "PPA6_THRESHOLD"
+    )]
     threshold: u8,
 
+    /// How gray is turned into black and white pixels.
+    #[arg(
+        long,
+        value_name = "HOW",
+        default_value = "floyd-steinberg",
+        env = "PPA6_DITHER"
+    )]
+    dither: Dither,
+
     /// Treat `file` as a text file.
     #[arg(short, long)]
     text: bool,
 
-    /// Font size for `--text`. Anything below 12 starts to be difficult to read.
-    #[arg(short = 'S', long, default_value_t = 18.0)]
-    size: f32,
+    /// Image format of `file`. With `auto`, the format is guessed from the content,
+    /// and files that aren't images, but valid UTF-8, are printed as text.
+    #[arg(long, default_value = "auto")]
+    format: InputFormat,
+
+    /// Print only this frame of an animated GIF, starting at 0.
+    #[arg(long, conflicts_with_all = ["frames", "contact_sheet"])]
+    frame: Option<usize>,
+
+    /// Print every frame of an animated GIF, separated by dashed lines.
+    #[arg(long, conflicts_with = "contact_sheet")]
+    frames: Option<FramesArg>,
 
-    /// Font weight for `--text`. Good numbers are 600 and 800.
-    #[arg(short, long, default_value_t = 800)]
-    weight: u16,
+    /// Print the frames of an animated GIF as a grid.
+    #[arg(long)]
+    contact_sheet: bool,
 
-    /// Line Height Factor. This gets multiplied with the font size to get the line height.
-    #[arg(short, long, default_value_t = 1.0)]
-    line_height: f32,
+    /// Print only these pages of a multi-page TIFF or a PDF, e.g. `1-3,5`, default: all.
+    #[arg(long, value_delimiter = ',', value_parser = pages::parse_range)]
+    pages: Vec<RangeInclusive<usize>>,
+
+    /// Channel of colored images, that decides how dark a pixel is.
+    /// `red` makes red ink disappear, `green` or `blue` keep it dark.
+    #[arg(long, default_value = "luma", env = "PPA6_CHANNEL")]
+    channel: Channel,
+
+    #[command(flatten)]
+    font: TextArgs,
 
     /// Adjust brightness, positive values increase brightness, negative values decrease brightness
-    #[arg(short, long, default_value_t = 0)]
+    #[arg(short, long, default_value_t = 0, env = "PPA6_BRIGHTEN")]
     brighten: i32,
 
     /// Adjust constrast, positive values increase contrast, negative values decrease contrast
-    #[arg(short, long, default_value_t = 0.0)]
+    #[arg(short, long, default_value_t = 0.0, env = "PPA6_CONTRAST")]
     contrast: f32,
 
-    /// Adjust the printer's concentration. Only values between `0..=2` are allowed.
-    #[arg(short = 'C', long)]
-    concentration: Option<u8>,
+    /// Clean up photos of paper documents, like receipts or notes,
+    /// by evening out the lighting and removing noise, instead of dithering.
+    #[arg(long, env = "PPA6_SCAN_CLEANUP")]
+    scan_cleanup: bool,
+
+    /// Don't remove blank rows from the top and bottom of the document.
+    #[arg(long, env = "PPA6_NO_TRIM")]
+    no_trim: bool,
+
+    /// Number of blank rows to keep at the bottom, when trimming the document (8 rows are 1mm).
+    #[arg(long, default_value_t = 16, env = "PPA6_MARGIN")]
+    margin: usize,
+
+    /// Adjust the printer's concentration: `light`, `medium`, `dark`,
+    /// or a raw level between `0..=2`.
+    #[arg(short = 'C', long, env = "PPA6_CONCENTRATION")]
+    concentration: Option<Concentration>,
+
+    /// Printer model, that images and text are rendered for, default: `a6`.
+    /// Images rendered for a narrower model are printed at the left of wider paper.
+    #[arg(long, value_enum, env = "PPA6_MODEL")]
+    model: Option<ModelArg>,
+
+    /// Lock the printer against other ppa6 processes, waiting up to this many seconds
+    /// for them to finish. Parallel jobs using this flag are printed one after another.
+    #[arg(long, value_name = "SECS", env = "PPA6_WAIT")]
+    wait: Option<u64>,
+
+    /// Disconnect from the printer after this many seconds without a job (with `--spool` or `--listen`),
+    /// so it can go to sleep and save its battery. It is reconnected for the next job.
+    #[arg(long, value_name = "SECS", env = "PPA6_IDLE")]
+    idle: Option<u64>,
+
+    /// Check for paper, an open lid and overheating before every chunk, if the printer reports them.
+    #[arg(long, env = "PPA6_CHECK_STATUS")]
+    check_status: bool,
+
+    /// Refuse jobs, that would drain the battery below this many percent, unless the printer is charging.
+    /// The battery use is a rough estimate, see the `estimate` subcommand.
+    #[arg(long, value_name = "PERCENT", env = "PPA6_MIN_BATTERY")]
+    min_battery: Option<u8>,
+
+    /// Only warn about low battery with `--min-battery`, instead of refusing the job.
+    #[arg(long, requires = "min_battery", env = "PPA6_LOW_BATTERY_WARN")]
+    low_battery_warn: bool,
+
+    /// When to send the mysterious end-of-image command, which the Windows driver sends after every chunk.
+    /// Sending it less often is faster, but check the output.
+    #[arg(
+        long,
+        value_name = "WHEN",
+        default_value = "always",
+        env = "PPA6_END_OF_IMAGE"
+    )]
+    end_of_image: EndOfImageArg,
+
+    /// Wait longer after dark chunks and shorter after light ones, instead of a fixed 50ms.
+    #[arg(long, env = "PPA6_ADAPTIVE_DELAY")]
+    adaptive_delay: bool,
+
+    /// If the printer is disconnected while printing, wait for it to come back
+    /// and continue where it stopped.
+    #[arg(long, env = "PPA6_RESUME")]
+    resume: bool,
+
+    /// Stamp the start of the job ID and the date (UTC) into the corner of each copy,
+    /// to match printed strips back to the log.
+    #[arg(long, env = "PPA6_STAMP")]
+    stamp: bool,
+
+    /// How images are framed: every chunk as a separate image, or the whole job as one image,
+    /// which saves protocol overhead, like the Windows driver does.
+    #[arg(
+        long,
+        value_name = "HOW",
+        default_value = "chunk",
+        env = "PPA6_FRAMING"
+    )]
+    framing: FramingArg,
+
+    /// Print to a virtual printer instead, and write what would have been printed into this directory,
+    /// as one PNG per job, named after the job ID.
+    #[arg(long, value_name = "DIR", env = "PPA6_DRY_RUN")]
+    dry_run: Option<PathBuf>,
+
+    /// The virtual printer of `--dry-run`.
+    #[arg(skip)]
+    emulator: Option<EmulatorBackend>,
+
+    /// Hand the printer back to the kernel driver (e.g. usblp) after printing.
+    #[arg(long, env = "PPA6_RELEASE")]
+    release: bool,
+
+    #[command(flatten)]
+    hooks: hooks::HookArgs,
+
+    #[command(flatten)]
+    power: power::PowerArgs,
+
+    /// Config file, default: `$XDG_CONFIG_HOME/ppa6/config.toml`, or `~/.config/ppa6/config.toml`.
+    #[arg(long, value_name = "FILE", env = "PPA6_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Settings loaded from `--config`.
+    #[arg(skip)]
+    settings: config::Config,
+
+    /// Profile of the config file, default: `default`, if there is one.
+    #[arg(long, value_name = "NAME", env = "PPA6_PROFILE")]
+    profile: Option<String>,
+
+    /// Print the effective settings and where each one came from, e.g. the config file,
+    /// instead of printing.
+    #[arg(long)]
+    explain: bool,
+
+    /// Sources of the effective settings.
+    #[arg(skip)]
+    effective: settings::Effective,
 
     #[command(flatten)]
     verbose: Verbosity,
 }
 
+#[derive(Subcommand, Clone)]
+enum Command {
+    /// Print a QR code, e.g. for Wi-Fi credentials or a contact card.
+    Qr(qr::QrArgs),
+
+    /// Print a sheet of 2FA backup codes, read from stdin one per line.
+    Codes(codes::CodesArgs),
+
+    /// Estimate paper length, black coverage, time and battery use of printing a file, without printing it.
+    Estimate(estimate::EstimateArgs),
+
+    /// Print a receipt from a JSON file with the merchant, item lines, taxes, and footer.
+    Receipt(receipt::ReceiptArgs),
+
+    /// Print a millimetre and inch ruler, to measure things or to check the paper feed.
+    Ruler(ruler::RulerArgs),
+
+    /// Print a web page as text, e.g. `web --readability <URL>` to read an article on paper.
+    Web(web::WebArgs),
+
+    /// Set up the printer step by step: connect it, print a test, choose the concentration,
+    /// and write the config file.
+    Setup(setup::SetupArgs),
+
+    /// Check for a new release, and replace this binary with it (requires the `self-update` feature).
+    /// The release manifest is signed, and the binary is checked against it.
+    SelfUpdate(update::UpdateArgs),
+
+    /// Print a reference document with different chunking strategies, and write the timings as CSV.
+    #[command(hide = true)]
+    BenchPrint(bench::BenchArgs),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum InputFormat {
+    /// Guess from the content.
+    Auto,
+    Png,
+    Jpeg,
+    /// Netpbm formats (PBM, PGM, PPM, PAM).
+    Pbm,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Dither {
+    /// Error diffusion, best for photos.
+    FloydSteinberg,
+    /// A regular pattern, better for large gray areas and graphics.
+    Ordered,
+    /// No dithering, every pixel is cut at the threshold, best for line art and text.
+    Threshold,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Channel {
+    /// Brightness of all colors.
+    Luma,
+    Red,
+    Green,
+    Blue,
+    /// Only the shape of opaque parts, images without transparency fall back to `luma`.
+    Alpha,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum FramesArg {
+    All,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum EndOfImageArg {
+    /// After every chunk.
+    Always,
+    /// Once after each job.
+    Once,
+    /// Never.
+    Never,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum FramingArg {
+    /// One image per chunk.
+    Chunk,
+    /// One image per job.
+    Job,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ModelArg {
+    /// PeriPage A6, 384px wide.
+    A6,
+    /// PeriPage A6+, 576px wide.
+    A6Plus,
+    /// PeriPage A8, 384px wide.
+    A8,
+    /// PeriPage A9, 576px wide.
+    A9,
+    /// PeriPage A9 Max, 832px wide.
+    A9Max,
+}
+
+impl Cli {
+    /// The model chosen with `--model`.
+    fn model(&self) -> Model {
+        match self.model {
+            None | Some(ModelArg::A6) => Model::A6,
+            Some(ModelArg::A6Plus) => Model::A6Plus,
+            Some(ModelArg::A8) => Model::A8,
+            Some(ModelArg::A9) => Model::A9,
+            Some(ModelArg::A9Max) => Model::A9Max,
+        }
+    }
+
+    /// Width of rendered images in pixels, the print head of [`Cli::model()`].
+    fn width(&self) -> u32 {
+        self.model().capabilities().width as u32
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum StdinFormat {
+    /// Binary data, as-is.
+    Raw,
+    /// Base64-encoded data, whitespace is ignored.
+    Base64,
+}
+
 struct BlackWhiteMap(u8);
 
 impl ColorMap for BlackWhiteMap {
@@ -109,19 +448,19 @@ impl ColorMap for BlackWhiteMap {
     }
 }
 
-fn resize(img: GrayImage) -> GrayImage {
+fn resize(img: GrayImage, width: u32) -> GrayImage {
     let (w, h) = img.dimensions();
 
-    if w == 384 {
+    if w == width {
         return img;
     }
 
     let w = w as f32;
     let h = h as f32;
-    let s = 384.0 / w;
+    let s = width as f32 / w;
 
     DynamicImage::ImageLuma8(img)
-        .resize(384, (h * s) as u32 + 1, FilterType::Gaussian)
+        .resize(width, (h * s) as u32 + 1, FilterType::Gaussian)
         .into_luma8()
 }
 
@@ -135,18 +474,142 @@ fn rotate(img: GrayImage, deg: usize) -> GrayImage {
     }
 }
 
+/// Columns of `--contact-sheet`.
+const SHEET_COLUMNS: u32 = 3;
+
+/// Put frames into a grid, with white space between them.
+fn contact_sheet(frames: &[GrayImage]) -> GrayImage {
+    const GAP: u32 = 8;
+    let w = frames.iter().map(|f| f.width()).max().unwrap_or(1);
+    let h = frames.iter().map(|f| f.height()).max().unwrap_or(1);
+    let cols = SHEET_COLUMNS.min(frames.len() as u32).max(1);
+    let rows = (frames.len() as u32).div_ceil(cols);
+    let mut out =
+        GrayImage::from_pixel(cols * (w + GAP) - GAP, rows * (h + GAP) - GAP, Luma([0xff]));
+    for (i, frame) in frames.iter().enumerate() {
+        let (col, row) = (i as u32 % cols, i as u32 / cols);
+        let (x, y) = (col * (w + GAP), row * (h + GAP));
+        image::imageops::replace(&mut out, frame, x as i64, y as i64);
+    }
+    out
+}
+
+/// A dashed line between frames.
+fn separator(width: u32) -> GrayImage {
+    GrayImage::from_fn(width, 12, |x, y| {
+        let black = (5..7).contains(&y) && x % 16 < 8;
+        Luma([if black { 0x00 } else { 0xff }])
+    })
+}
+
+/// Convert to grayscale, using the channel chosen with `--channel`.
+fn to_gray(cli: &Cli, img: DynamicImage) -> GrayImage {
+    // every pixel of an image without alpha is opaque, which would print a black page
+    if cli.channel == Channel::Alpha && !img.color().has_alpha() {
+        log::warn!("the image has no alpha channel, using the brightness instead");
+        return img.into_luma8();
+    }
+
+    let channel = match cli.channel {
+        Channel::Luma => return img.into_luma8(),
+        Channel::Red => 0,
+        Channel::Green => 1,
+        Channel::Blue => 2,
+        Channel::Alpha => 3,
+    };
+    let img = img.into_rgba8();
+    GrayImage::from_fn(img.width(), img.height(), |x, y| {
+        let value = img.get_pixel(x, y).0[channel];
+        // opaque pixels are black
+        Luma([if cli.channel == Channel::Alpha {
+            0xff - value
+        } else {
+            value
+        }])
+    })
+}
+
+/// Adjust frames or pages, and put them below each other, separated by dashed lines.
+fn separated(cli: &Cli, imgs: Vec<GrayImage>) -> Result<GrayImage> {
+    let mut out = Vec::new();
+    for (i, img) in imgs.into_iter().enumerate() {
+        if i > 0 {
+            out.push(separator(cli.width()));
+        }
+        out.push(adjust(cli, img)?);
+    }
+    Ok(stack(&out))
+}
+
+/// Decode every frame of an animated GIF.
+fn gif_frames(cli: &Cli, data: &[u8]) -> Result<Vec<GrayImage>> {
+    let decoder = GifDecoder::new(Cursor::new(data))?;
+    let frames = decoder
+        .into_frames()
+        .collect_frames()
+        .context("cannot decode GIF frames")?;
+    log::debug!("GIF has {} frames", frames.len());
+    Ok(frames
+        .into_iter()
+        .map(|frame| to_gray(cli, DynamicImage::ImageRgba8(frame.into_buffer())))
+        .collect())
+}
+
 fn picture(cli: &Cli, data: &[u8]) -> Result<GrayImage> {
     log::trace!("parsing...");
-    let img = ImageReader::new(Cursor::new(data))
-        .with_guessed_format()?
-        .decode()?
-        .into_luma8();
+    let animated = cli.frame.is_some() || cli.frames.is_some() || cli.contact_sheet;
+    if animated && image::guess_format(data).ok() == Some(ImageFormat::Gif) {
+        let frames = gif_frames(cli, data)?;
+        if cli.contact_sheet {
+            return adjust(cli, contact_sheet(&frames));
+        }
+        if let Some(n) = cli.frame {
+            let count = frames.len();
+            let frame = frames
+                .into_iter()
+                .nth(n)
+                .with_context(|| format!("frame {n} out of range, the GIF has {count} frames"))?;
+            return adjust(cli, frame);
+        }
 
+        return separated(cli, frames);
+    }
+    if animated {
+        log::warn!("--frame, --frames, and --contact-sheet only apply to GIFs");
+    }
+
+    if cli.format == InputFormat::Auto && image::guess_format(data).ok() == Some(ImageFormat::Tiff)
+    {
+        let pages = pages::tiff_pages(data, &cli.pages)?;
+        let pages = pages.into_iter().map(|page| to_gray(cli, page)).collect();
+        return separated(cli, pages);
+    }
+    if !cli.pages.is_empty() {
+        log::warn!("--pages only applies to TIFFs and PDFs");
+    }
+
+    let reader = ImageReader::new(Cursor::new(data));
+    let reader = match cli.format {
+        InputFormat::Auto => reader.with_guessed_format()?,
+        InputFormat::Png => with_format(reader, ImageFormat::Png),
+        InputFormat::Jpeg => with_format(reader, ImageFormat::Jpeg),
+        InputFormat::Pbm => with_format(reader, ImageFormat::Pnm),
+    };
+    let format = reader.format();
+    let img = reader.decode().with_context(|| match format {
+        Some(format) => format!("cannot decode {format:?} image"),
+        None => "cannot decode image".into(),
+    })?;
+    adjust(cli, to_gray(cli, img))
+}
+
+/// Rotate, resize, and dither a decoded image.
+fn adjust(cli: &Cli, img: GrayImage) -> Result<GrayImage> {
     log::trace!("rotating...");
     let img = rotate(img, cli.rotate);
 
     log::trace!("resizing...");
-    let mut img = DynamicImage::ImageLuma8(resize(img));
+    let mut img = DynamicImage::ImageLuma8(resize(img, cli.width()));
 
     if cli.brighten != 0 {
         log::trace!("brightening...");
@@ -159,92 +622,243 @@ fn picture(cli: &Cli, data: &[u8]) -> Result<GrayImage> {
     }
 
     let mut img = img.into_luma8();
-    assert_eq!(img.width(), 384);
+    assert_eq!(img.width(), cli.width());
+    legibility::check(&img, cli.threshold);
+
+    if cli.scan_cleanup {
+        return Ok(cleanup::cleanup(&img));
+    }
 
     log::trace!("dithering...");
-    dither(&mut img, &BlackWhiteMap(cli.threshold));
+    match cli.dither {
+        Dither::FloydSteinberg => dither(&mut img, &BlackWhiteMap(cli.threshold)),
+        Dither::Ordered => ordered_dither(&mut img, cli.threshold),
+        Dither::Threshold => img
+            .pixels_mut()
+            .for_each(|p| BlackWhiteMap(cli.threshold).map_color(p)),
+    }
     Ok(img)
 }
 
-// TODO: parse ANSI escape sequences
-fn text(cli: &Cli, data: &[u8]) -> Result<GrayImage> {
-    let text = String::from_utf8(data.to_vec())?;
+/// Dither with a 4x4 Bayer matrix around `threshold`.
+fn ordered_dither(img: &mut GrayImage, threshold: u8) {
+    const BAYER: [[i32; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+    for (x, y, p) in img.enumerate_pixels_mut() {
+        let offset = BAYER[y as usize % 4][x as usize % 4] * 16 + 8 - 128;
+        let white = p.0[0] as i32 + offset >= threshold as i32;
+        p.0[0] = if white { 0xff } else { 0x00 };
+    }
+}
 
-    let mut font_system = FontSystem::new();
-    let mut cache = SwashCache::new();
-    let metrics = Metrics::new(cli.size, cli.size * cli.line_height);
-    let mut buffer = Buffer::new(&mut font_system, metrics);
-    let mut buffer = buffer.borrow_with(&mut font_system);
-    buffer.set_size(Some(340.0), None);
-    let mut attrs = Attrs::new();
-    attrs.weight.0 = cli.weight;
+#[cfg(feature = "net")]
+fn fetch(url: &str) -> Result<(Vec<u8>, Option<String>)> {
+    log::trace!("fetching {url}...");
+    let resp = ureq::get(url).call()?;
+    let mime = resp
+        .header("content-type")
+        .and_then(|ct| ct.split(';').next())
+        .map(|ct| ct.trim().to_ascii_lowercase());
+    log::debug!("content type of {url}: {mime:?}");
 
-    buffer.set_text(&text, attrs, Shaping::Advanced);
-    buffer.shape_until_scroll(true);
+    let mut data = Vec::new();
+    resp.into_reader().read_to_end(&mut data)?;
+    Ok((data, mime))
+}
 
-    let mut pixels = Vec::new();
-    let mut height = 0;
+fn decode_base64(data: &[u8]) -> Result<Vec<u8>> {
+    let data: Vec<u8> = data
+        .iter()
+        .copied()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect();
+    Ok(BASE64.decode(data)?)
+}
 
-    buffer.draw(&mut cache, Color::rgb(0xff, 0, 0), |x, y, w, h, color| {
-        let a = color.a();
-        if x < 0 || y < 0 || x > 384 || w != 1 || h != 1 || a == 0 {
-            return;
+/// Decode `%XX` escapes, anything else, including a `%` without two hex digits, is kept as it is.
+fn percent_decode(s: &str) -> Vec<u8> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .filter(|h| h.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|h| u8::from_str_radix(std::str::from_utf8(h).ok()?, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
         }
+    }
+    out
+}
 
-        let x = x as usize;
-        let y = y as usize;
+/// Decode a `data:[<mime>][;base64],<data>` URI.
+/// Without a MIME type, it is `text/plain`, as in RFC 2397.
+fn parse_data_uri(uri: &str) -> Result<(Vec<u8>, Option<String>)> {
+    let Some((header, payload)) = uri.strip_prefix("data:").and_then(|s| s.split_once(',')) else {
+        bail!("malformed data URI");
+    };
+
+    let (header, base64) = match header.strip_suffix(";base64") {
+        Some(header) => (header, true),
+        None => (header, false),
+    };
+    let mime = header
+        .split(';')
+        .next()
+        .filter(|m| !m.is_empty())
+        .map_or_else(|| "text/plain".to_owned(), str::to_ascii_lowercase);
+
+    let data = if base64 {
+        decode_base64(payload.as_bytes())?
+    } else {
+        percent_decode(payload)
+    };
+    Ok((data, Some(mime)))
+}
 
-        if y >= height {
-            height = y + 1;
-            pixels.resize(3 * 384 * height, 0xff);
+/// Read the input data and, if known, its MIME type.
+fn read_input(cli: &Cli, file: &Path) -> Result<(Vec<u8>, Option<String>)> {
+    if file == Path::new("-") {
+        let mut data = Vec::new();
+        std::io::stdin().read_to_end(&mut data)?;
+        if cli.stdin_format == StdinFormat::Base64 {
+            data = decode_base64(&data)?;
         }
+        return Ok((data, None));
+    }
 
-        let scale = |c: u8| {
-            let c = c as f32 / 255.0;
-            let a = a as f32 / 255.0;
-            let c = (c * a) + (1.0 * (1.0 - a));
-            (c * 255.0).clamp(0.0, 255.0) as u8
-        };
+    if let Some(uri) = file.to_str().filter(|s| s.starts_with("data:")) {
+        return parse_data_uri(uri);
+    }
 
-        pixels[(y * 384 + x) * 3 + 0] = scale(color.r());
-        pixels[(y * 384 + x) * 3 + 1] = scale(color.g());
-        pixels[(y * 384 + x) * 3 + 2] = scale(color.b());
-    });
+    if let Some(url) = file
+        .to_str()
+        .filter(|s| s.starts_with("http://") || s.starts_with("https://"))
+    {
+        #[cfg(feature = "net")]
+        {
+            let (data, mime) = fetch(url)?;
+            match mime.as_deref() {
+                Some("application/pdf") => bail!("{url}: PDF documents are not supported"),
+                Some(m) if m.starts_with("image/") || m.starts_with("text/") => {}
+                Some(m) => log::warn!("{url}: unexpected content type {m}, trying anyway"),
+                None => {}
+            }
+            return Ok((data, mime));
+        }
 
-    let img = DynamicImage::ImageRgb8(RgbImage::from_vec(384, height as u32, pixels).unwrap());
-    Ok(img.into_luma8())
+        #[cfg(not(feature = "net"))]
+        bail!("cannot fetch {url}: ppa6-print was built without the `net` feature");
+    }
+
+    Ok((std::fs::read(file)?, None))
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
-    env_logger::builder()
-        .filter_level(cli.verbose.log_level_filter())
-        .init();
+/// Render the input with the handler for its MIME type, the extension of `path`, or its content,
+/// see [`handlers::registry()`].
+fn render(cli: &Cli, data: &[u8], mime: Option<&str>, path: Option<&Path>) -> Result<Document> {
+    if cli.text {
+        return rasterize(cli, &text(&cli.font, data)?);
+    }
+    if cli.format != InputFormat::Auto {
+        return rasterize(cli, &picture(cli, data)?);
+    }
 
-    let data = if cli.file == Path::new("-") {
-        let mut data = Vec::new();
-        std::io::stdin().read_to_end(&mut data)?;
-        data
-    } else {
-        std::fs::read(&cli.file)?
+    let registry = handlers::registry(cli);
+    let Some(handler) = registry.resolve(data, mime, path) else {
+        let mime = mime.unwrap_or_else(|| sniff(data));
+        bail!("cannot print {mime} data, it is neither an image nor text, try --format");
     };
-    let img = if cli.text {
-        text(&cli, &data)
-    } else {
-        picture(&cli, &data)
-    }?;
+    log::debug!("printing as {}", handler.name());
+    Ok(handler.render(data, cli.width() as u16)?)
+}
 
-    if cli.show {
-        let temppath = Path::new("/tmp/ppa6-preview.png");
-        img.save_with_format(temppath, ImageFormat::Png)?;
-        open::that(temppath)?;
-        return Ok(());
+/// Names of the `[type.NAME]` sections of the config, that apply to the input, least specific first:
+/// the handler, the MIME type, and the extension of `path`.
+fn content_types(cli: &Cli, data: &[u8], mime: Option<&str>, path: Option<&Path>) -> Vec<String> {
+    let handler = handlers::registry(cli)
+        .resolve(data, mime, path)
+        .map(|h| h.name().to_owned());
+    let mime = mime.map(str::to_owned).or_else(|| {
+        image::guess_format(data)
+            .ok()
+            .map(|f| f.to_mime_type().to_owned())
+    });
+    let ext = path
+        .and_then(|p| p.extension())
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    [handler, mime, ext].into_iter().flatten().collect()
+}
+
+fn with_format(
+    mut reader: ImageReader<Cursor<&[u8]>>,
+    format: ImageFormat,
+) -> ImageReader<Cursor<&[u8]>> {
+    reader.set_format(format);
+    reader
+}
+
+/// Guess the MIME type of data, that isn't an image, for error messages.
+fn sniff(data: &[u8]) -> &'static str {
+    const MAGIC: &[(&[u8], &str)] = &[
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"\x7fELF", "application/x-executable"),
+        (b"%!PS", "application/postscript"),
+        (b"ID3", "audio/mpeg"),
+        (b"OggS", "audio/ogg"),
+    ];
+    MAGIC
+        .iter()
+        .find(|(magic, _)| data.starts_with(magic))
+        .map_or("application/octet-stream", |(_, mime)| mime)
+}
+
+/// Convert a document into an image, the inverse of [`rasterize()`].
+fn unpack(doc: &Document) -> GrayImage {
+    let stride = doc.stride();
+    GrayImage::from_fn(doc.width() as u32, doc.height() as u32, |x, y| {
+        let byte = doc.pixels()[y as usize * stride + x as usize / 8];
+        let black = byte & (0x80 >> (x % 8)) != 0;
+        Luma([if black { 0x00 } else { 0xff }])
+    })
+}
+
+/// Put images below each other, left aligned, narrower ones are padded with white.
+fn stack(imgs: &[GrayImage]) -> GrayImage {
+    let width = imgs.iter().map(|img| img.width()).max().unwrap_or(0);
+    let height = imgs.iter().map(|img| img.height()).sum();
+    let mut out = GrayImage::from_pixel(width, height, Luma([0xff]));
+    let mut y = 0;
+    for img in imgs {
+        image::imageops::replace(&mut out, img, 0, y as i64);
+        y += img.height();
     }
+    out
+}
+
+/// Convert a rendered image into a document, `--invert` is applied by [`document()`].
+fn rasterize(cli: &Cli, img: &GrayImage) -> Result<Document> {
+    Ok(Document::new(
+        pack_bits(img, cli.threshold, false),
+        img.width() as u16,
+    )?)
+}
 
+/// Pack pixels darker than `threshold` as black, or as white if `invert`.
+fn pack_bits(img: &GrayImage, threshold: u8, invert: bool) -> Vec<u8> {
     log::trace!("mapping...");
-    let pixels = img
-        .par_pixels()
-        .map(|c| (c.0[0] < cli.threshold) ^ cli.invert)
+    img.par_pixels()
+        .map(|c| (c.0[0] < threshold) ^ invert)
         .chunks(8)
         .map(|chunk| {
             chunk.iter().enumerate().fold(0u8, |mut acc, (i, c)| {
@@ -255,10 +869,27 @@ fn main() -> Result<()> {
                 acc
             })
         })
-        .collect::<Vec<u8>>();
+        .collect()
+}
+
+/// How long to wait for a relay to accept the connection, see `--tcp`.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
 
-    let mut printer = if let Some(dev) = cli.device {
-        Printer::new(FileBackend::open(&dev)?)
+fn open_printer(cli: &Cli) -> Result<Printer> {
+    let mut printer = if let Some(emulator) = &cli.emulator {
+        log::trace!("using a virtual printer...");
+        Printer::new(emulator.clone())
+    } else if let Some(dev) = &cli.device {
+        Printer::new(FileBackend::open(dev)?)
+    } else if let Some(addr) = &cli.tcp {
+        log::trace!("connecting to {addr}...");
+        Printer::new(TcpBackend::connect_timeout(addr.as_str(), CONNECT_TIMEOUT)?)
+    } else if let Some(idle) = cli.idle {
+        log::trace!("searching for printer...");
+        Printer::find_idle(Duration::from_secs(idle))?
+    } else if let Some(wait) = cli.wait {
+        log::trace!("searching for printer, waiting up to {wait}s for other jobs...");
+        Printer::find_exclusive(Duration::from_secs(wait))?
     } else {
         log::trace!("searching for printer...");
         Printer::find()?
@@ -271,23 +902,540 @@ fn main() -> Result<()> {
     log::info!("Serial: {}", printer.get_serial()?);
     log::info!("Hardware: {}", printer.get_hardware_ver()?);
     log::info!("Name: {}", printer.get_name()?);
+    let model = printer.detect_model()?;
+    log::info!("Model: {model}");
+    log::debug!("Quirks: {:?}", printer.detect_quirks()?);
+    let width = printer.capabilities().width as u32;
+    if width != cli.width() {
+        log::warn!(
+            "the {model} is {width}px wide, but images are rendered {}px wide, see --model",
+            cli.width()
+        );
+    }
     log::info!("MAC: {:x?}", printer.get_mac()?);
-    log::info!("Battery: {}%", printer.get_battery()?);
+    log::info!("Battery: {}", printer.get_battery_status()?);
+
+    printer.set_end_of_image(match cli.end_of_image {
+        EndOfImageArg::Always => EndOfImage::Always,
+        EndOfImageArg::Once => EndOfImage::OncePerJob,
+        EndOfImageArg::Never => EndOfImage::Never,
+    });
+
+    printer.set_framing(match cli.framing {
+        FramingArg::Chunk => Framing::PerChunk,
+        FramingArg::Job => Framing::PerJob,
+    });
+
+    if cli.adaptive_delay {
+        printer.set_thermal_policy(Some(ThermalPolicy::default()));
+    }
+
+    if cli.check_status {
+        log::info!("Status: {:?}", printer.get_status()?);
+        printer.set_check_status(true);
+    }
 
     if let Some(c) = cli.concentration {
         log::trace!("setting printer concentration to {c}...");
         printer.set_concentration(c)?;
     }
 
-    for i in 0..cli.num {
-        log::trace!("printing chunk {i}...");
-        printer.print_image_chunked(&pixels, 384)?;
+    Ok(printer)
+}
+
+/// Turn a rendered document into the [`Document`] to be printed.
+fn document(cli: &Cli, doc: Document) -> Result<Document> {
+    let mut doc = if cli.invert {
+        Document::new(doc.pixels().iter().map(|b| !b).collect(), doc.width())?
+    } else {
+        doc
+    };
+    if !cli.no_trim {
+        doc.trim_whitespace(0, cli.margin);
+    }
+    Ok(cli.settings.chain().apply(doc)?)
+}
+
+/// Progress bar for printing `rows` rows, hidden with `-q` and if stderr isn't a terminal.
+/// The ETA is based on the rows printed so far.
+fn progress_bar(cli: &Cli, rows: usize) -> Result<ProgressBar> {
+    if cli.verbose.log_level().is_none() {
+        return Ok(ProgressBar::hidden());
+    }
+
+    let style = ProgressStyle::with_template(
+        "[{elapsed_precise}] {wide_bar} {pos}/{len} rows ({percent}%, ETA {eta})",
+    )?
+    .progress_chars("=> ");
+    Ok(ProgressBar::new(rows as u64).with_style(style))
+}
+
+/// Print a job, `job` is the correlation ID that prefixes every log message of the job.
+fn print(cli: &Cli, printer: &mut Printer, job: Uuid, doc: Document) -> Result<()> {
+    log::info!("[{job}] settings: {}", cli.effective.summary(cli));
+    let doc = document(cli, doc)?;
+    check_battery(cli, printer, job, &doc)?;
+
+    let start = Instant::now();
+    let bar = progress_bar(cli, doc.height() * cli.num)?;
+    let opts = PrintOptions {
+        copies: cli.num,
+        feed: if cli.feed { 0x60 } else { 0 },
+        // already done by open_printer()
+        reset: false,
+        resume_on_reconnect: cli.resume,
+        stamp: cli.stamp.then(|| stamp(job)),
+        ..PrintOptions::default()
+    };
+    printer
+        .print_ext(&doc, &opts, |p| bar.set_position(p.rows as u64))
+        .with_context(|| format!("job {job}"))?;
+    bar.finish_and_clear();
+
+    if let (Some(emulator), Some(dir)) = (&cli.emulator, &cli.dry_run) {
+        let path = dir.join(format!("{job}.png"));
+        unpack(&emulator.take_document()).save_with_format(&path, ImageFormat::Png)?;
+        log::info!("[{job}] dry run, written to {}", path.display());
+    }
+
+    log::info!("[{job}] done in {:.1?}", start.elapsed());
+    if let Some(stats) = printer.transport_stats() {
+        log::debug!("[{job}] transport: {stats:?}");
+    }
+    Ok(())
+}
+
+/// Text of `--stamp`: the first 8 digits of the job ID and today's date, e.g. `1a2b3c4d 2025-01-31`.
+fn stamp(job: Uuid) -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / 86400;
+
+    // civil date from days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let (era, doe) = (z / 146097, z % 146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + u64::from(m <= 2);
+
+    let id = job.simple().to_string();
+    format!("{} {y:04}-{m:02}-{d:02}", &id[..8])
+}
+
+/// Make sure the job doesn't drain the battery below `--min-battery`.
+fn check_battery(cli: &Cli, printer: &mut Printer, job: Uuid, doc: &Document) -> Result<()> {
+    let Some(min) = cli.min_battery else {
+        return Ok(());
+    };
+
+    let battery = printer.get_battery_status()?;
+    if battery.charging == Some(true) {
+        return Ok(());
+    }
+
+    let feed = if cli.feed { 0x60 } else { 0 };
+    let needed = estimate::battery(doc, feed) * cli.num as f64;
+    if battery.percent as f64 - needed >= min as f64 {
+        return Ok(());
+    }
+
+    let msg = format!(
+        "battery is at {}%, the job needs ~{needed:.1}%, which would leave less than {min}%",
+        battery.percent
+    );
+    if cli.low_battery_warn {
+        log::warn!("[{job}] {msg}");
+        Ok(())
+    } else {
+        bail!("job {job}: {msg}")
+    }
+}
+
+/// The printer of a long-running mode, like [`spool()`] or [`listen()`].
+struct Station<'a> {
+    cli: &'a Cli,
+    printer: Option<Printer>,
+    power: Option<Power>,
+}
+
+impl<'a> Station<'a> {
+    fn new(cli: &'a Cli) -> Result<Self> {
+        let power = cli.power.start();
+
+        // without a smart plug, the printer should already be there
+        let printer = match power {
+            Some(_) => None,
+            None => Some(open_printer(cli)?),
+        };
+
+        Ok(Self {
+            cli,
+            printer,
+            power,
+        })
+    }
+
+    /// Run a job, switching on and (re)connecting the printer, if needed.
+    fn job<T>(&mut self, f: impl FnOnce(&mut Printer) -> Result<T>) -> Result<T> {
+        if let Some(power) = &self.power {
+            if power.acquire()? {
+                self.printer = None;
+            }
+        }
+
+        let res = match &mut self.printer {
+            Some(printer) => f(printer),
+            None => open_printer(self.cli).and_then(|printer| f(self.printer.insert(printer))),
+        };
+
+        if let Some(power) = &self.power {
+            power.release();
+        }
+        res
+    }
+}
+
+/// A job of the spool, see [`spool()`].
+#[derive(Debug, PartialEq)]
+enum SpoolJob<'a> {
+    File(&'a Path),
+    Text(&'a str),
+}
+
+impl<'a> SpoolJob<'a> {
+    /// Files need an explicit `file:` prefix, so that text, which happens to be a path, isn't printed as a file.
+    fn parse(line: &'a str) -> Result<Self> {
+        if let Some(path) = line.strip_prefix("file:") {
+            let path = Path::new(path);
+            if !path.is_absolute() {
+                bail!("{}: the path of a file must be absolute", path.display());
+            }
+            return Ok(Self::File(path));
+        }
+        Ok(Self::Text(line.strip_prefix("text:").unwrap_or(line)))
+    }
+}
+
+/// Print jobs read line by line from `path`, which is usually a FIFO, see [`SpoolJob`].
+fn spool(cli: &Cli, path: &Path) -> Result<()> {
+    let mut station = Station::new(cli)?;
+
+    loop {
+        log::trace!("opening spool {}...", path.display());
+        let file = File::open(path)?;
+
+        for line in BufReader::new(&file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let job = Uuid::new_v4();
+            let mut job_cli = cli.clone();
+            let doc = SpoolJob::parse(&line).and_then(|spooled| match spooled {
+                SpoolJob::File(file) => {
+                    log::info!("[{job}] printing {}", file.display());
+                    let data = std::fs::read(file)?;
+                    let types = content_types(cli, &data, None, Some(file));
+                    settings::apply_type(&mut job_cli, &types)?;
+                    render(&job_cli, &data, None, Some(file))
+                }
+                SpoolJob::Text(line) => {
+                    log::info!("[{job}] printing text: {line:?}");
+                    rasterize(cli, &text(&cli.font, line.as_bytes())?)
+                }
+            });
+
+            // a single bad job must not bring down the spooler
+            let result = doc.and_then(|doc| {
+                job_cli.hooks.run(job, &line, job_cli.num, || {
+                    station.job(|printer| print(&job_cli, printer, job, doc))
+                })
+            });
+            if let Err(e) = result {
+                log::error!("[{job}] {line}: {e:#}");
+            }
+        }
+
+        // writers closing a FIFO is not the end of the spool, but EOF of a regular file is
+        #[cfg(unix)]
+        if file.metadata()?.file_type().is_fifo() {
+            continue;
+        }
+
+        return Ok(());
     }
+}
 
-    if cli.feed {
-        log::trace!("feeding...");
-        printer.push(0x60)?;
+/// Largest job accepted by [`listen()`].
+const MAX_JOB_SIZE: u64 = 64 << 20;
+
+/// How long [`listen()`] waits for a whole job.
+const LISTEN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Read a job of at most [`MAX_JOB_SIZE`] + 1 bytes, until `deadline`.
+/// The read timeout only limits a single read, so it is set to the time left before every read,
+/// otherwise a client sending a byte now and then could keep a job open for ever.
+fn read_job(stream: &mut TcpStream, deadline: Instant) -> std::io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    let mut buf = [0u8; 64 << 10];
+    while data.len() as u64 <= MAX_JOB_SIZE {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(std::io::ErrorKind::TimedOut.into());
+        }
+        stream.set_read_timeout(Some(left))?;
+        match stream.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => data.extend_from_slice(&buf[..n]),
+            // the read timed out, the deadline is checked on the next round
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::Interrupted | std::io::ErrorKind::WouldBlock
+                ) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(data)
+}
+
+/// Act like a raw (JetDirect) network printer, listening on `addr`.
+/// Every connection is one job, in any format of [`handlers::raw_registry()`],
+/// data in no known format is taken as packed rows as wide as `--model`, see [`Printer::print_image()`].
+/// Failed jobs are logged, and the listener goes on with the next connection.
+fn listen(cli: &Cli, addr: &str) -> Result<()> {
+    let mut station = Station::new(cli)?;
+    let listener = TcpListener::bind(addr)?;
+    let registry = handlers::raw_registry(cli);
+    log::info!("listening on {}", listener.local_addr()?);
+
+    for stream in listener.incoming() {
+        let (mut stream, peer) = match stream.and_then(|s| Ok((s.peer_addr()?, s))) {
+            Ok((peer, stream)) => (stream, peer),
+            Err(e) => {
+                log::error!("cannot accept connection: {e}");
+                // e.g. out of file descriptors, don't spin
+                std::thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+        };
+        let job = Uuid::new_v4();
+
+        // a client, that never finishes sending, mustn't block the listener or fill the memory
+        let data = match read_job(&mut stream, Instant::now() + LISTEN_TIMEOUT) {
+            Ok(data) => data,
+            Err(e) => {
+                log::error!("[{job}] {peer}: {e}");
+                continue;
+            }
+        };
+        if data.is_empty() {
+            log::debug!("[{job}] {peer}: empty job");
+            continue;
+        }
+        if data.len() as u64 > MAX_JOB_SIZE {
+            log::error!(
+                "[{job}] {peer}: job is larger than {} MiB",
+                MAX_JOB_SIZE >> 20
+            );
+            continue;
+        }
+
+        let doc = match registry.render(&data, None, None, cli.width() as u16) {
+            Ok(doc) => doc,
+            Err(e) => {
+                log::error!("[{job}] {peer}: {e}");
+                continue;
+            }
+        };
+        log::info!("[{job}] {peer}: printing {} rows", doc.height());
+        let result = cli.hooks.run(job, &peer.to_string(), cli.num, || {
+            station.job(|printer| print(cli, printer, job, doc))
+        });
+        if let Err(e) = result {
+            log::error!("[{job}] {peer}: {e:#}");
+        }
     }
 
     Ok(())
 }
+
+fn main() -> Result<()> {
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    env_logger::builder()
+        .filter_level(cli.verbose.log_level_filter())
+        .init();
+    cli.settings = config::load(cli.config.as_deref())?;
+    cli.effective = settings::resolve(&mut cli, &matches)?;
+
+    if let Some(dir) = &cli.dry_run {
+        std::fs::create_dir_all(dir).with_context(|| format!("cannot create {}", dir.display()))?;
+        cli.emulator = Some(EmulatorBackend::new(cli.model()));
+    }
+
+    if cli.explain && cli.file.is_none() {
+        cli.effective.explain(&cli);
+        return Ok(());
+    }
+
+    if let Some(path) = &cli.spool {
+        return spool(&cli, path);
+    }
+
+    if let Some(addr) = &cli.listen {
+        return listen(&cli, addr);
+    }
+
+    match &cli.command {
+        Some(Command::Estimate(args)) => return estimate::estimate(&cli, args),
+        Some(Command::BenchPrint(args)) => return bench::bench(&cli, args),
+        Some(Command::Setup(args)) => return setup::setup(&cli, args),
+        Some(Command::SelfUpdate(args)) => return update::update(args),
+        _ => {}
+    }
+
+    let (doc, name) = match &cli.command {
+        Some(Command::Qr(args)) => (rasterize(&cli, &qr::qr(&cli, args)?)?, "QR code".to_owned()),
+        Some(Command::Codes(args)) => (
+            rasterize(&cli, &codes::codes(&cli, args)?)?,
+            "backup codes".to_owned(),
+        ),
+        Some(Command::Receipt(args)) => (
+            rasterize(&cli, &receipt::receipt(&cli, args)?)?,
+            "receipt".to_owned(),
+        ),
+        Some(Command::Ruler(args)) => (
+            rasterize(&cli, &ruler::ruler(&cli, args)?)?,
+            "ruler".to_owned(),
+        ),
+        Some(Command::Web(args)) => (
+            rasterize(&cli, &web::web(&cli, args)?)?,
+            "web page".to_owned(),
+        ),
+        Some(
+            Command::Estimate(_)
+            | Command::BenchPrint(_)
+            | Command::Setup(_)
+            | Command::SelfUpdate(_),
+        ) => unreachable!(),
+        None => {
+            let file = cli.file.clone().unwrap();
+            let (data, mime) = read_input(&cli, &file)?;
+            let types = content_types(&cli, &data, mime.as_deref(), Some(&file));
+            settings::apply_type(&mut cli, &types)?;
+            if cli.explain {
+                cli.effective.explain(&cli);
+                return Ok(());
+            }
+            let doc = render(&cli, &data, mime.as_deref(), Some(&file))?;
+            (doc, file.display().to_string())
+        }
+    };
+
+    if cli.show {
+        // preview what would be printed, after trimming and the transforms of the config
+        let preview = unpack(&document(&cli, doc)?);
+        let temppath = Path::new("/tmp/ppa6-preview.png");
+        preview.save_with_format(temppath, ImageFormat::Png)?;
+        open::that(temppath)?;
+        return Ok(());
+    }
+
+    let job = Uuid::new_v4();
+    log::info!("[{job}] printing {name}");
+    cli.hooks.run(job, &name, cli.num, || {
+        cli.power.run(|| {
+            let mut printer = open_printer(&cli)?;
+            print(&cli, &mut printer, job, doc)?;
+
+            if cli.release {
+                log::trace!("releasing printer...");
+                printer.release()?;
+            }
+
+            Ok(())
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spool_jobs() {
+        let parse = |line| SpoolJob::parse(line).ok();
+        assert_eq!(
+            parse("file:/tmp/a.png"),
+            Some(SpoolJob::File(Path::new("/tmp/a.png")))
+        );
+        assert_eq!(parse("file:a.png"), None);
+        // paths without the prefix are text
+        assert_eq!(parse("/etc/passwd"), Some(SpoolJob::Text("/etc/passwd")));
+        assert_eq!(
+            parse("text:file:/etc/passwd"),
+            Some(SpoolJob::Text("file:/etc/passwd"))
+        );
+    }
+
+    #[test]
+    fn job_deadline() {
+        use std::io::Write;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = std::thread::spawn(move || {
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.write_all(b"hello").unwrap();
+            // a byte now and then, each before the read timeout
+            for _ in 0..10 {
+                std::thread::sleep(Duration::from_millis(50));
+                if s.write_all(b".").is_err() {
+                    break;
+                }
+            }
+        });
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let start = Instant::now();
+        let e = read_job(&mut stream, start + Duration::from_millis(200)).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_millis(400));
+        drop(stream);
+        client.join().unwrap();
+
+        let client = std::thread::spawn(move || {
+            TcpStream::connect(addr).unwrap().write_all(b"job").unwrap();
+        });
+        let (mut stream, _) = listener.accept().unwrap();
+        let data = read_job(&mut stream, Instant::now() + Duration::from_secs(5)).unwrap();
+        assert_eq!(data, b"job");
+        client.join().unwrap();
+    }
+
+    #[test]
+    fn data_uris() {
+        assert_eq!(percent_decode("a%20b%2Fc"), b"a b/c");
+        assert_eq!(percent_decode("100%"), b"100%");
+        assert_eq!(percent_decode("%zz%4"), b"%zz%4");
+        assert_eq!(percent_decode("%4%41"), b"%4A");
+        assert_eq!(percent_decode("%+1"), b"%+1");
+
+        let (data, mime) = parse_data_uri("data:,hello%20world").unwrap();
+        assert_eq!(data, b"hello world");
+        assert_eq!(mime.as_deref(), Some("text/plain"));
+        let (_, mime) = parse_data_uri("data:;charset=utf-8,x").unwrap();
+        assert_eq!(mime.as_deref(), Some("text/plain"));
+        let (data, mime) = parse_data_uri("data:Image/PNG;base64,aGk=").unwrap();
+        assert_eq!(data, b"hi");
+        assert_eq!(mime.as_deref(), Some("image/png"));
+        assert!(parse_data_uri("data:text/plain").is_err());
+    }
+}
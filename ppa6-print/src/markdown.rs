@@ -0,0 +1,332 @@
+use anyhow::{bail, Result};
+use image::{GrayImage, Luma};
+
+use crate::{
+    separator, stack,
+    text::{text, TextArgs, TextStyle},
+};
+
+/// A block of a Markdown document, with the inline markup removed.
+#[derive(Debug, PartialEq)]
+enum Block {
+    /// A heading of level 1 to 6.
+    Heading(usize, String),
+    Paragraph(String),
+    /// An item of a list, with its marker, e.g. `-` or `1.`.
+    Item(String, String),
+    Quote(String),
+    /// A fenced code block, as it is.
+    Code(String),
+    Rule,
+}
+
+/// The marker and the text of a list item, e.g. `- text` or `1. text`.
+fn list_item(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start();
+    if let Some(rest) = line.strip_prefix(['-', '*', '+']) {
+        return rest.strip_prefix(' ').map(|rest| (&line[..1], rest));
+    }
+    let digits = line.find(|c: char| !c.is_ascii_digit())?;
+    let rest = line[digits..].strip_prefix(['.', ')'])?.strip_prefix(' ')?;
+    (digits > 0).then(|| (&line[..digits + 1], rest))
+}
+
+/// Whether `line` is a thematic break, e.g. `---` or `* * *`.
+fn is_rule(line: &str) -> bool {
+    let chars: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    chars.len() >= 3
+        && ['-', '*', '_']
+            .iter()
+            .any(|&m| chars.iter().all(|&c| c == m))
+}
+
+/// Remove emphasis, code spans, and link targets, but keep their text.
+fn inline(s: &str) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let prev = i.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(i + 1).copied();
+        match chars[i] {
+            '\\' if next.is_some_and(|c| c.is_ascii_punctuation()) => {
+                out.extend(next);
+                i += 2;
+                continue;
+            }
+            '`' => {
+                let end = chars[i + 1..].iter().position(|&c| c == '`');
+                if let Some(end) = end {
+                    out.extend(&chars[i + 1..i + 1 + end]);
+                    i += end + 2;
+                    continue;
+                }
+            }
+            '!' if next == Some('[') => {
+                i += 1;
+                continue;
+            }
+            '[' => {
+                // `[text](url)` becomes `text`
+                let close = chars[i..].iter().position(|&c| c == ']').map(|n| i + n);
+                let target = close.filter(|&c| chars.get(c + 1) == Some(&'('));
+                let end = target.and_then(|c| chars[c..].iter().position(|&c| c == ')'));
+                if let (Some(close), Some(end)) = (target, end) {
+                    out += &inline(&chars[i + 1..close].iter().collect::<String>());
+                    i = close + end + 1;
+                    continue;
+                }
+            }
+            '*' | '_' => {
+                let word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric());
+                let space = |c: Option<char>| c.is_none_or(char::is_whitespace);
+                let mut n = 1;
+                while chars.get(i + n) == Some(&chars[i]) {
+                    n += 1;
+                }
+                let after = chars.get(i + n).copied();
+                // `snake_case` and `2 * 3` are no emphasis
+                let intraword = chars[i] == '_' && word(prev) && word(after);
+                if !(intraword || space(prev) && space(after)) {
+                    i += n;
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        out.push(chars[i]);
+        i += 1;
+    }
+    out
+}
+
+/// Split a Markdown document into blocks.
+fn parse(md: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut para = String::new();
+    let mut code: Option<(&str, String)> = None;
+
+    let flush = |para: &mut String, blocks: &mut Vec<Block>| {
+        if !para.is_empty() {
+            blocks.push(Block::Paragraph(inline(para.trim_end())));
+            para.clear();
+        }
+    };
+
+    for line in md.lines() {
+        if let Some((fence, body)) = &mut code {
+            if line.trim_start().starts_with(*fence) {
+                blocks.push(Block::Code(std::mem::take(body)));
+                code = None;
+            } else {
+                body.push_str(line);
+                body.push('\n');
+            }
+            continue;
+        }
+
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            flush(&mut para, &mut blocks);
+        } else if let Some(fence) = ["```", "~~~"].into_iter().find(|f| trimmed.starts_with(f)) {
+            flush(&mut para, &mut blocks);
+            code = Some((fence, String::new()));
+        } else if !para.is_empty() && trimmed.chars().all(|c| c == '=' || c == '-') {
+            // setext headings are underlined
+            let level = if trimmed.starts_with('=') { 1 } else { 2 };
+            blocks.push(Block::Heading(level, inline(para.trim_end())));
+            para.clear();
+        } else if is_rule(trimmed) {
+            flush(&mut para, &mut blocks);
+            blocks.push(Block::Rule);
+        } else if let Some(rest) = trimmed
+            .strip_prefix('#')
+            .map(|s| s.trim_start_matches('#'))
+            .filter(|s| (s.is_empty() || s.starts_with(' ')) && trimmed.len() - s.len() <= 6)
+        {
+            flush(&mut para, &mut blocks);
+            let level = trimmed.len() - rest.len();
+            let title = rest.trim().trim_end_matches('#').trim_end();
+            blocks.push(Block::Heading(level, inline(title)));
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            flush(&mut para, &mut blocks);
+            let quote = inline(quote.trim());
+            match blocks.last_mut() {
+                Some(Block::Quote(text)) => {
+                    text.push(' ');
+                    text.push_str(&quote);
+                }
+                _ => blocks.push(Block::Quote(quote)),
+            }
+        } else if let Some((marker, item)) = list_item(line) {
+            flush(&mut para, &mut blocks);
+            blocks.push(Block::Item(marker.to_owned(), inline(item.trim())));
+        } else if let (true, Some(Block::Item(_, text))) = (
+            para.is_empty() && line.starts_with([' ', '\t']),
+            blocks.last_mut(),
+        ) {
+            // continuation of a list item
+            text.push(' ');
+            text.push_str(&inline(trimmed));
+        } else {
+            // two trailing spaces are a line break
+            let sep = if line.ends_with("  ") { '\n' } else { ' ' };
+            para.push_str(trimmed);
+            para.push(sep);
+        }
+    }
+
+    flush(&mut para, &mut blocks);
+    if let Some((_, body)) = code {
+        blocks.push(Block::Code(body));
+    }
+    blocks
+}
+
+/// Render Markdown with the font set on the command line, headings are larger.
+/// `--fit` and `--columns` don't apply, as they would shrink long paragraphs.
+pub fn markdown(opts: &TextArgs, data: &[u8]) -> Result<GrayImage> {
+    let blocks = parse(std::str::from_utf8(data)?);
+
+    let mut body = opts.clone();
+    body.fit = false;
+    body.fit_lines = None;
+    body.columns = 1;
+
+    // paragraphs, lists and quotes are rendered together, to keep the line spacing of the font
+    let mut parts = Vec::new();
+    let mut pending = String::new();
+    let flush = |pending: &mut String, parts: &mut Vec<GrayImage>| -> Result<()> {
+        if !pending.trim().is_empty() {
+            let pending = pending.trim_start_matches('\n').trim_end();
+            parts.push(text(&body, pending.as_bytes())?);
+        }
+        pending.clear();
+        Ok(())
+    };
+
+    let mut list = false;
+    for block in blocks {
+        match block {
+            Block::Paragraph(s) | Block::Quote(s) | Block::Item(_, s) if s.is_empty() => {}
+            Block::Paragraph(s) => {
+                pending += &format!("\n{s}\n");
+                list = false;
+            }
+            Block::Quote(s) => {
+                pending += &format!("\n> {s}\n");
+                list = false;
+            }
+            Block::Item(marker, s) => {
+                if !list {
+                    pending.push('\n');
+                }
+                pending += &format!("{marker} {s}\n");
+                list = true;
+            }
+            Block::Heading(level, s) => {
+                flush(&mut pending, &mut parts)?;
+                let mut heading = body.clone();
+                heading.size *= [1.6, 1.3, 1.15].get(level - 1).copied().unwrap_or(1.0);
+                if level == 1 {
+                    heading.style.push(TextStyle::Underline);
+                }
+                parts.push(text(&heading, s.as_bytes())?);
+                list = false;
+            }
+            Block::Code(s) => {
+                flush(&mut pending, &mut parts)?;
+                let mut code = body.clone();
+                code.mono = true;
+                code.style.clear();
+                parts.push(text(&code, s.trim_end().as_bytes())?);
+                list = false;
+            }
+            Block::Rule => {
+                flush(&mut pending, &mut parts)?;
+                parts.push(separator(opts.width));
+                list = false;
+            }
+        }
+    }
+    flush(&mut pending, &mut parts)?;
+
+    if parts.is_empty() {
+        bail!("the document has no text");
+    }
+
+    // half a line between blocks
+    let gap = GrayImage::from_pixel(opts.width, (opts.size / 2.0) as u32, Luma([0xff]));
+    let mut out = Vec::new();
+    for (i, part) in parts.into_iter().enumerate() {
+        if i > 0 {
+            out.push(gap.clone());
+        }
+        out.push(part);
+    }
+    Ok(stack(&out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inline_markup() {
+        assert_eq!(inline("**bold** and *it* and _it_"), "bold and it and it");
+        assert_eq!(inline("`a * b` in `code`"), "a * b in code");
+        assert_eq!(
+            inline("2 * 3 and snake_case_name"),
+            "2 * 3 and snake_case_name"
+        );
+        assert_eq!(inline("see [the *docs*](https://x.y/(z)"), "see the docs");
+        assert_eq!(inline("![logo](a.png) [unclosed"), "logo [unclosed");
+        assert_eq!(inline(r"\*not emphasis\*"), "*not emphasis*");
+    }
+
+    #[test]
+    fn blocks() {
+        let md = "\
+# Title #
+Some *text*,
+continued.
+
+Setext
+---
+- one
+  continued
+2. two
+
+> quoted
+> twice
+
+```rust
+let x = **y;
+```
+***
+####### not a heading
+";
+        assert_eq!(
+            parse(md),
+            [
+                Block::Heading(1, "Title".into()),
+                Block::Paragraph("Some text, continued.".into()),
+                Block::Heading(2, "Setext".into()),
+                Block::Item("-".into(), "one continued".into()),
+                Block::Item("2.".into(), "two".into()),
+                Block::Quote("quoted twice".into()),
+                Block::Code("let x = **y;\n".into()),
+                Block::Rule,
+                Block::Paragraph("####### not a heading".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn unclosed_code_block() {
+        assert_eq!(
+            parse("```\ncode\n\nmore"),
+            [Block::Code("code\n\nmore\n".into())]
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use anyhow::{bail, Context, Result};
+use image::{DynamicImage, GrayAlphaImage, GrayImage, Luma, RgbImage, RgbaImage};
+use std::{
+    io::{Cursor, ErrorKind},
+    ops::RangeInclusive,
+    path::Path,
+    process::Command,
+};
+use tiff::{
+    decoder::{Decoder, DecodingResult},
+    ColorType,
+};
+use uuid::Uuid;
+
+/// Parse a range of pages, like `3` or `1-3`, counting from 1.
+pub fn parse_range(s: &str) -> Result<RangeInclusive<usize>> {
+    let (first, last) = s.split_once('-').unwrap_or((s, s));
+    let first: usize = first.trim().parse().context("invalid page number")?;
+    let last: usize = last.trim().parse().context("invalid page number")?;
+    if first == 0 || last < first {
+        bail!("invalid page range: {s}");
+    }
+    Ok(first..=last)
+}
+
+/// Whether page `n` (counting from 1) is selected, all pages are, if `ranges` is empty.
+fn selected(ranges: &[RangeInclusive<usize>], n: usize) -> bool {
+    ranges.is_empty() || ranges.iter().any(|r| r.contains(&n))
+}
+
+/// Decode the current page of a TIFF.
+fn page(decoder: &mut Decoder<Cursor<&[u8]>>) -> Result<DynamicImage> {
+    let (w, h) = decoder.dimensions()?;
+    let color = decoder.colortype()?;
+    let DecodingResult::U8(buf) = decoder.read_image()? else {
+        bail!("unsupported TIFF color type: {color:?}");
+    };
+
+    let img = match color {
+        ColorType::Gray(1) => {
+            let stride = w.div_ceil(8);
+            let img = GrayImage::from_fn(w, h, |x, y| {
+                let byte = buf[(y * stride + x / 8) as usize];
+                let white = byte & (0x80 >> (x % 8)) != 0;
+                Luma([if white { 0xff } else { 0x00 }])
+            });
+            Some(DynamicImage::ImageLuma8(img))
+        }
+        ColorType::Gray(8) => GrayImage::from_raw(w, h, buf).map(DynamicImage::ImageLuma8),
+        ColorType::GrayA(8) => GrayAlphaImage::from_raw(w, h, buf).map(DynamicImage::ImageLumaA8),
+        ColorType::RGB(8) => RgbImage::from_raw(w, h, buf).map(DynamicImage::ImageRgb8),
+        ColorType::RGBA(8) => RgbaImage::from_raw(w, h, buf).map(DynamicImage::ImageRgba8),
+        _ => None,
+    };
+    img.with_context(|| format!("unsupported TIFF color type: {color:?}"))
+}
+
+/// Decode the selected pages of a multi-page TIFF.
+pub fn tiff_pages(data: &[u8], ranges: &[RangeInclusive<usize>]) -> Result<Vec<DynamicImage>> {
+    let mut decoder = Decoder::new(Cursor::new(data)).context("cannot decode TIFF")?;
+    let mut pages = Vec::new();
+    let mut n = 1;
+    loop {
+        if selected(ranges, n) {
+            log::trace!("decoding page {n}...");
+            pages.push(page(&mut decoder).with_context(|| format!("page {n}"))?);
+        }
+        if !decoder.more_images() {
+            break;
+        }
+        decoder.next_image()?;
+        n += 1;
+    }
+    log::debug!("TIFF has {n} pages, {} selected", pages.len());
+
+    if pages.is_empty() {
+        bail!("no pages selected, the TIFF has {n} pages");
+    }
+    Ok(pages)
+}
+
+/// Rasterize the selected pages of a PDF, `width` pixels wide, with `pdftoppm` of poppler.
+pub fn pdf_pages(
+    data: &[u8],
+    ranges: &[RangeInclusive<usize>],
+    width: u32,
+) -> Result<Vec<DynamicImage>> {
+    let dir = std::env::temp_dir().join(format!("ppa6-pdf-{}", Uuid::new_v4()));
+    std::fs::create_dir(&dir).with_context(|| format!("cannot create {}", dir.display()))?;
+    let pages = pdftoppm(&dir, data, ranges, width);
+    let _ = std::fs::remove_dir_all(&dir);
+    pages
+}
+
+fn pdftoppm(
+    dir: &Path,
+    data: &[u8],
+    ranges: &[RangeInclusive<usize>],
+    width: u32,
+) -> Result<Vec<DynamicImage>> {
+    let input = dir.join("input.pdf");
+    std::fs::write(&input, data)?;
+
+    let mut cmd = Command::new("pdftoppm");
+    cmd.args(["-png", "-gray", "-scale-to-y", "-1", "-scale-to-x"])
+        .arg(width.to_string());
+    // only render the pages between the first and the last selected one
+    if let (Some(first), Some(last)) = (
+        ranges.iter().map(|r| *r.start()).min(),
+        ranges.iter().map(|r| *r.end()).max(),
+    ) {
+        cmd.args(["-f", &first.to_string(), "-l", &last.to_string()]);
+    }
+    let status = match cmd.arg(&input).arg(dir.join("page")).status() {
+        Ok(status) => status,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            bail!("printing PDFs requires pdftoppm, install poppler-utils")
+        }
+        Err(e) => return Err(e).context("cannot run pdftoppm"),
+    };
+    if !status.success() {
+        bail!("pdftoppm failed: {status}");
+    }
+
+    // page-1.png, or page-01.png, if the PDF has more than 9 pages
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let n = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.strip_prefix("page-"))
+            .and_then(|n| n.parse::<usize>().ok());
+        if let Some(n) = n {
+            files.push((n, path));
+        }
+    }
+    files.sort();
+
+    let mut pages = Vec::new();
+    for (n, path) in files.iter().filter(|(n, _)| selected(ranges, *n)) {
+        log::trace!("decoding page {n}...");
+        pages.push(image::open(path).with_context(|| format!("page {n}"))?);
+    }
+    log::debug!("rendered {} pages of the PDF", pages.len());
+
+    if pages.is_empty() {
+        bail!("no pages selected, the PDF has fewer pages");
+    }
+    Ok(pages)
+}
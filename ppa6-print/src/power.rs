@@ -0,0 +1,228 @@
+#[cfg(feature = "net")]
+use anyhow::Context;
+use anyhow::Result;
+use clap::Args;
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Power the printer from a smart plug, which is switched on for jobs and off when idle.
+#[derive(Args, Clone, Default)]
+pub struct PowerArgs {
+    /// Smart plug powering the printer, either `tasmota:HOST` or `shelly:HOST[/RELAY]`.
+    /// It is switched on before a job, and off again when idle (requires the `net` feature).
+    #[arg(long, value_name = "PLUG")]
+    pub plug: Option<Plug>,
+
+    /// Switch the plug off after this many seconds without a job.
+    /// A single job (without `--spool` or `--listen`) switches it off right away.
+    #[arg(long, value_name = "SECS", default_value_t = 300)]
+    pub plug_idle: u64,
+
+    /// Seconds to wait for the printer to boot, after switching the plug on.
+    #[arg(long, value_name = "SECS", default_value_t = 5)]
+    pub plug_delay: u64,
+}
+
+/// A smart plug with an HTTP API.
+#[derive(Clone, Debug)]
+pub enum Plug {
+    /// [Tasmota](https://tasmota.github.io/docs/Commands/#with-web-requests)
+    Tasmota { host: String },
+
+    /// [Shelly](https://shelly-api-docs.shelly.cloud/), Gen1 and Gen2+ devices.
+    Shelly { host: String, relay: u8 },
+}
+
+impl FromStr for Plug {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((kind, host)) = s.split_once(':') else {
+            return Err("expected tasmota:HOST or shelly:HOST[/RELAY]".into());
+        };
+        if host.is_empty() {
+            return Err("missing host".into());
+        }
+
+        match kind {
+            "tasmota" => Ok(Self::Tasmota { host: host.into() }),
+            "shelly" => {
+                let (host, relay) = match host.split_once('/') {
+                    Some((host, relay)) => (host, relay.parse().map_err(|e| format!("{e}"))?),
+                    None => (host, 0),
+                };
+                Ok(Self::Shelly {
+                    host: host.into(),
+                    relay,
+                })
+            }
+            _ => Err(format!("unknown smart plug type: {kind}")),
+        }
+    }
+}
+
+impl Display for Plug {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tasmota { host } => write!(f, "tasmota:{host}"),
+            Self::Shelly { host, relay } => write!(f, "shelly:{host}/{relay}"),
+        }
+    }
+}
+
+impl Plug {
+    /// Switch the plug on or off.
+    pub fn set(&self, on: bool) -> Result<()> {
+        let state = if on { "on" } else { "off" };
+        log::info!("switching {self} {state}...");
+
+        #[cfg(feature = "net")]
+        return self
+            .request(on)
+            .with_context(|| format!("cannot switch {self} {state}"));
+
+        #[cfg(not(feature = "net"))]
+        anyhow::bail!(
+            "cannot switch {self} {state}: ppa6-print was built without the `net` feature"
+        );
+    }
+
+    #[cfg(feature = "net")]
+    fn request(&self, on: bool) -> Result<()> {
+        let state = if on { "on" } else { "off" };
+        match self {
+            Self::Tasmota { host } => {
+                ureq::get(&format!("http://{host}/cm?cmnd=Power%20{state}")).call()?;
+            }
+            Self::Shelly { host, relay } => {
+                match ureq::get(&format!("http://{host}/relay/{relay}?turn={state}")).call() {
+                    // Gen2+ devices only have the RPC API
+                    Err(ureq::Error::Status(404, _)) => {
+                        ureq::get(&format!("http://{host}/rpc/Switch.Set?id={relay}&on={on}"))
+                            .call()?;
+                    }
+                    r => {
+                        r?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct State {
+    on: bool,
+    busy: bool,
+    last_used: Instant,
+}
+
+/// Switches the [`Plug`] on for jobs, and off again after it was idle for a while.
+pub struct Power {
+    plug: Plug,
+    delay: Duration,
+    state: Arc<(Mutex<State>, Condvar)>,
+}
+
+impl PowerArgs {
+    /// Start managing the plug, if there is one.
+    /// The plug is assumed to be off, until the first job.
+    pub fn start(&self) -> Option<Power> {
+        let plug = self.plug.clone()?;
+        let idle = Duration::from_secs(self.plug_idle);
+        let state = Arc::new((
+            Mutex::new(State {
+                on: false,
+                busy: false,
+                last_used: Instant::now(),
+            }),
+            Condvar::new(),
+        ));
+
+        let watchdog = Arc::clone(&state);
+        let watchdog_plug = plug.clone();
+        thread::spawn(move || {
+            let (lock, cvar) = &*watchdog;
+            let mut state = lock.lock().unwrap();
+            loop {
+                if !state.on || state.busy {
+                    state = cvar.wait(state).unwrap();
+                    continue;
+                }
+
+                let left = idle.saturating_sub(state.last_used.elapsed());
+                if !left.is_zero() {
+                    state = cvar.wait_timeout(state, left).unwrap().0;
+                    continue;
+                }
+
+                match watchdog_plug.set(false) {
+                    Ok(()) => state.on = false,
+                    Err(e) => {
+                        // try again after another idle period
+                        log::error!("{e:#}");
+                        state.last_used = Instant::now();
+                    }
+                }
+            }
+        });
+
+        Some(Power {
+            plug,
+            delay: Duration::from_secs(self.plug_delay),
+            state,
+        })
+    }
+
+    /// Power the printer for a single job, switching the plug off again afterwards.
+    pub fn run<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let Some(plug) = &self.plug else {
+            return f();
+        };
+
+        plug.set(true)?;
+        log::trace!("waiting {}s for the printer to boot...", self.plug_delay);
+        thread::sleep(Duration::from_secs(self.plug_delay));
+        let res = f();
+        if let Err(e) = plug.set(false) {
+            log::error!("{e:#}");
+        }
+        res
+    }
+}
+
+impl Power {
+    /// Make sure the printer is powered, before starting a job.
+    /// Returns `true`, if the printer was just switched on, which means it has to be reconnected.
+    pub fn acquire(&self) -> Result<bool> {
+        let (lock, _) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        state.busy = true;
+        if state.on {
+            return Ok(false);
+        }
+
+        if let Err(e) = self.plug.set(true) {
+            state.busy = false;
+            return Err(e);
+        }
+        state.on = true;
+        log::trace!("waiting {:?} for the printer to boot...", self.delay);
+        thread::sleep(self.delay);
+        Ok(true)
+    }
+
+    /// The job is done, start the idle timer.
+    pub fn release(&self) {
+        let (lock, cvar) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        state.busy = false;
+        state.last_used = Instant::now();
+        cvar.notify_one();
+    }
+}
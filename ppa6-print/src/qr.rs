@@ -0,0 +1,184 @@
+use anyhow::Result;
+use clap::{Args, Subcommand, ValueEnum};
+use image::GrayImage;
+use ppa6::qr::{Contact, QrPayload, Wifi, WifiSecurity};
+
+use crate::{stack, text, unpack, Cli};
+
+#[derive(Args, Clone)]
+pub struct QrArgs {
+    #[command(subcommand)]
+    payload: Payload,
+
+    /// Text printed below the code, instead of the default caption.
+    #[arg(long, global = true)]
+    caption: Option<String>,
+
+    /// Don't print a caption below the code.
+    #[arg(long, global = true, conflicts_with = "caption")]
+    no_caption: bool,
+}
+
+#[derive(Subcommand, Clone)]
+enum Payload {
+    /// Encode arbitrary text.
+    Text {
+        /// Text to be encoded.
+        data: String,
+    },
+
+    /// A URL, opened in the browser when scanned.
+    Url {
+        /// URL to be encoded.
+        url: String,
+    },
+
+    /// A geographic location, opened in a maps app when scanned.
+    Geo {
+        /// Latitude in degrees.
+        #[arg(allow_negative_numbers = true)]
+        lat: f64,
+
+        /// Longitude in degrees.
+        #[arg(allow_negative_numbers = true)]
+        lon: f64,
+    },
+
+    /// Wi-Fi network credentials, phones join the network when scanning the code.
+    Wifi {
+        /// Name of the network.
+        #[arg(long)]
+        ssid: String,
+
+        /// Password of the network.
+        #[arg(long)]
+        pass: Option<String>,
+
+        /// Security of the network, defaults to WPA if a password is given.
+        #[arg(long, value_enum)]
+        security: Option<Security>,
+
+        /// The network doesn't broadcast its SSID.
+        #[arg(long)]
+        hidden: bool,
+    },
+
+    /// Contact card (vCard 3.0).
+    Vcard(ContactArgs),
+
+    /// Contact card in the more compact MECARD format, which results in smaller codes.
+    Mecard(ContactArgs),
+}
+
+#[derive(Args, Clone)]
+struct ContactArgs {
+    /// Full name.
+    #[arg(long)]
+    name: String,
+
+    /// Phone numbers.
+    #[arg(long)]
+    tel: Vec<String>,
+
+    /// Email addresses.
+    #[arg(long)]
+    email: Vec<String>,
+
+    /// Organization.
+    #[arg(long)]
+    org: Option<String>,
+
+    /// Website.
+    #[arg(long)]
+    url: Option<String>,
+
+    /// Postal address.
+    #[arg(long)]
+    address: Option<String>,
+
+    /// Free-form note.
+    #[arg(long)]
+    note: Option<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Security {
+    Wpa,
+    Wep,
+    None,
+}
+
+impl ContactArgs {
+    fn contact(&self) -> Contact {
+        Contact {
+            name: self.name.clone(),
+            tel: self.tel.clone(),
+            email: self.email.clone(),
+            org: self.org.clone(),
+            url: self.url.clone(),
+            address: self.address.clone(),
+            note: self.note.clone(),
+        }
+    }
+}
+
+impl Payload {
+    /// Build the payload and the default caption.
+    fn build(&self) -> (QrPayload, Option<String>) {
+        match self {
+            Self::Text { data } => (QrPayload::Text(data.clone()), None),
+            Self::Url { url } => (QrPayload::Url(url.clone()), Some(url.clone())),
+            Self::Geo { lat, lon } => (
+                QrPayload::Geo {
+                    lat: *lat,
+                    lon: *lon,
+                },
+                Some(format!("{lat}, {lon}")),
+            ),
+            Self::Wifi {
+                ssid,
+                pass,
+                security,
+                hidden,
+            } => {
+                let security = match (security, pass) {
+                    (Some(Security::Wpa), _) | (None, Some(_)) => WifiSecurity::Wpa,
+                    (Some(Security::Wep), _) => WifiSecurity::Wep,
+                    (Some(Security::None), _) | (None, None) => WifiSecurity::None,
+                };
+                let wifi = Wifi {
+                    ssid: ssid.clone(),
+                    password: pass.clone(),
+                    security,
+                    hidden: *hidden,
+                };
+
+                let mut caption = format!("Wi-Fi: {ssid}");
+                if let Some(pass) = pass {
+                    caption += &format!("\nPassword: {pass}");
+                }
+                (QrPayload::Wifi(wifi), Some(caption))
+            }
+            Self::Vcard(c) => (QrPayload::VCard(c.contact()), Some(c.name.clone())),
+            Self::Mecard(c) => (QrPayload::MeCard(c.contact()), Some(c.name.clone())),
+        }
+    }
+}
+
+/// Render the QR code, with its caption below.
+pub fn qr(cli: &Cli, args: &QrArgs) -> Result<GrayImage> {
+    let (payload, caption) = args.payload.build();
+    log::debug!("QR payload: {:?}", payload.encode());
+
+    let code = unpack(&ppa6::qr::render_payload(&payload, cli.width() as u16)?);
+    let caption = match (&args.caption, args.no_caption) {
+        (_, true) => None,
+        (Some(caption), false) => Some(caption.clone()),
+        (None, false) => caption,
+    };
+
+    match caption {
+        Some(caption) => Ok(stack(&[code, text(&cli.font, caption.as_bytes())?])),
+        None => Ok(code),
+    }
+}
@@ -0,0 +1,242 @@
+use anyhow::{bail, Result};
+use clap::Args;
+use image::GrayImage;
+use serde::Deserialize;
+use std::{collections::BTreeMap, fmt::Write, path::PathBuf};
+
+use crate::{
+    locale::{self, Locale},
+    stack,
+    text::{chars_per_line, text, TextStyle},
+    Cli,
+};
+
+#[derive(Args, Clone)]
+pub struct ReceiptArgs {
+    /// Path to the receipt, in JSON format, `-` for stdin.
+    file: PathBuf,
+
+    /// Locale for formatting numbers and dates, e.g. `de_AT`.
+    /// Defaults to the receipt's `locale`, or `LC_NUMERIC`/`LANG`.
+    #[arg(long)]
+    locale: Option<String>,
+}
+
+/// A receipt, as produced by a webshop or POS system.
+#[derive(Deserialize)]
+struct Receipt {
+    /// Name of the merchant, printed as the header.
+    merchant: String,
+
+    /// Additional header lines, e.g. the address.
+    #[serde(default)]
+    header: Vec<String>,
+
+    /// Date of the purchase, in ISO 8601 format.
+    date: Option<String>,
+
+    /// Locale the receipt should be printed in, e.g. `en_US`.
+    locale: Option<String>,
+
+    /// ISO 4217 currency code or symbol, printed next to the total.
+    #[serde(default)]
+    currency: String,
+
+    /// Whether the prices include taxes (gross), or taxes come on top (net).
+    #[serde(default = "default_true")]
+    tax_included: bool,
+
+    lines: Vec<Line>,
+
+    /// Total, checked against the sum of the lines, if given.
+    total: Option<f64>,
+
+    /// Footer lines, e.g. "Thank you!".
+    #[serde(default)]
+    footer: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Line {
+    name: String,
+
+    #[serde(default = "default_qty")]
+    qty: f64,
+
+    /// Price of a single unit.
+    price: f64,
+
+    /// Tax rate in percent.
+    #[serde(default)]
+    tax: f64,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_qty() -> f64 {
+    1.0
+}
+
+/// Put `left` and `right` on one line of `width` characters, with `right` right-aligned.
+/// `left` is cut off, if both don't fit.
+fn columns(left: &str, right: &str, width: usize) -> String {
+    let room = width.saturating_sub(right.chars().count() + 1);
+    let left: String = left.chars().take(room).collect();
+    let pad = width.saturating_sub(left.chars().count() + right.chars().count());
+    format!("{left}{:pad$}{right}", "")
+}
+
+/// Digits needed for a tax rate in hundredths of a percent, e.g. 1 for 7.5% and 2 for 7.25%.
+fn rate_decimals(hundredths: i64) -> usize {
+    match hundredths {
+        r if r % 100 == 0 => 0,
+        r if r % 10 == 0 => 1,
+        _ => 2,
+    }
+}
+
+/// Render a receipt, read from a JSON file.
+pub fn receipt(cli: &Cli, args: &ReceiptArgs) -> Result<GrayImage> {
+    let data = if args.file.as_os_str() == "-" {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        std::fs::read_to_string(&args.file)?
+    };
+    render(cli, data.as_bytes(), args.locale.as_deref())
+}
+
+/// Whether `data` is a receipt in the JSON format of [`receipt()`].
+pub fn is_receipt(data: &[u8]) -> bool {
+    serde_json::from_slice::<Receipt>(data).is_ok()
+}
+
+/// Render a receipt in JSON format, in `locale` or the receipt's own.
+pub fn render(cli: &Cli, data: &[u8], locale: Option<&str>) -> Result<GrayImage> {
+    let receipt: Receipt = serde_json::from_slice(data)?;
+    let locale = match locale.or(receipt.locale.as_deref()) {
+        Some(tag) => Locale::from_tag(tag),
+        None => Locale::from_env(),
+    };
+    let decimals = locale::decimals(&receipt.currency);
+    let money = |x| locale.number(x, decimals);
+
+    let mut body_opts = cli.font.clone();
+    body_opts.mono = true;
+    body_opts.columns = 1;
+    body_opts.fit = false;
+    body_opts.fit_lines = None;
+    body_opts.style.clear();
+    let width = chars_per_line(&body_opts);
+    let rule = "-".repeat(width);
+
+    let mut body = String::new();
+    for line in &receipt.header {
+        writeln!(body, "{line}")?;
+    }
+    if let Some(date) = &receipt.date {
+        writeln!(body, "{}", locale.date(date))?;
+    }
+    writeln!(body, "{rule}")?;
+
+    // tax rate (in hundredths of a percent, to be usable as a key) -> gross amount
+    let mut rates = BTreeMap::<i64, f64>::new();
+    let mut sum = 0.0;
+    for line in &receipt.lines {
+        let amount = line.qty * line.price;
+        sum += amount;
+        *rates.entry((line.tax * 100.0).round() as i64).or_default() += amount;
+
+        let name = if line.qty == 1.0 {
+            line.name.clone()
+        } else {
+            format!(
+                "{} x {}",
+                locale.number(line.qty, if line.qty.fract() == 0.0 { 0 } else { 3 }),
+                line.name
+            )
+        };
+        writeln!(body, "{}", columns(&name, &money(amount), width))?;
+    }
+    writeln!(body, "{rule}")?;
+
+    let mut tax_total = 0.0;
+    let mut taxes = String::new();
+    for (&hundredths, &amount) in rates.iter().filter(|(&rate, _)| rate != 0) {
+        let rate = hundredths as f64 / 100.0;
+        let tax = if receipt.tax_included {
+            amount * rate / (100.0 + rate)
+        } else {
+            amount * rate / 100.0
+        };
+        tax_total += tax;
+        writeln!(
+            taxes,
+            "{}",
+            columns(
+                &format!("Tax {}%", locale.number(rate, rate_decimals(hundredths))),
+                &money(tax),
+                width
+            )
+        )?;
+    }
+
+    let total = if receipt.tax_included {
+        sum
+    } else {
+        sum + tax_total
+    };
+    if let Some(expected) = receipt.total {
+        // half of the smallest unit of the currency
+        if (expected - total).abs() >= 0.5 / 10f64.powi(decimals as i32) {
+            bail!(
+                "total is {}, but the lines add up to {}",
+                money(expected),
+                money(total)
+            );
+        }
+    }
+
+    if !receipt.tax_included {
+        writeln!(body, "{}", columns("Net", &money(sum), width))?;
+        body += &taxes;
+    }
+    writeln!(
+        body,
+        "{}",
+        columns("TOTAL", &locale.money(total, &receipt.currency), width)
+    )?;
+    if receipt.tax_included {
+        body += &taxes;
+    }
+
+    if !receipt.footer.is_empty() {
+        writeln!(body, "{rule}")?;
+        for line in &receipt.footer {
+            writeln!(body, "{line}")?;
+        }
+    }
+
+    let mut header_opts = body_opts.clone();
+    header_opts.mono = false;
+    header_opts.size *= 1.5;
+    header_opts.style = vec![TextStyle::Inverted];
+
+    Ok(stack(&[
+        text(&header_opts, format!(" {} ", receipt.merchant).as_bytes())?,
+        text(&body_opts, body.as_bytes())?,
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tax_rates() {
+        assert_eq!(rate_decimals(2000), 0);
+        assert_eq!(rate_decimals(750), 1);
+        assert_eq!(rate_decimals(725), 2);
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::{bail, Result};
+use clap::{Args, ValueEnum};
+use image::{GrayImage, Luma};
+
+use crate::{text::text, Cli};
+
+/// Thickness of the ticks and edges, in dots.
+const LINE: u32 = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Scale {
+    /// Millimetres on the left edge.
+    Metric,
+    /// Inches on the right edge.
+    Imperial,
+    /// Both.
+    Both,
+}
+
+#[derive(Args, Clone)]
+pub struct RulerArgs {
+    /// Length of the ruler in millimetres.
+    #[arg(long, default_value_t = 150.0)]
+    length: f64,
+
+    /// Which scales to print.
+    #[arg(long, default_value = "both")]
+    scale: Scale,
+}
+
+/// Row of the paper, that is `inches` from the start, at `dpi` rows per inch.
+fn row(inches: f64, dpi: f64) -> u32 {
+    (inches * dpi).round() as u32
+}
+
+/// Draw a tick from the left or right edge at `y`.
+fn tick(img: &mut GrayImage, y: u32, len: u32, right: bool) {
+    let width = img.width();
+    let xs = if right { width - len..width } else { 0..len };
+    for y in y.saturating_sub(LINE / 2)..(y + LINE / 2).min(img.height()) {
+        for x in xs.clone() {
+            img.put_pixel(x, y, Luma([0x00]));
+        }
+    }
+}
+
+/// Draw the black pixels of `label` with their top left corner at `(x, y)`.
+fn overlay(img: &mut GrayImage, label: &GrayImage, x: u32, y: u32) {
+    for (lx, ly, px) in label.enumerate_pixels() {
+        let (tx, ty) = (x + lx, y + ly);
+        if px.0[0] < 0x80 && tx < img.width() && ty < img.height() {
+            img.put_pixel(tx, ty, Luma([0x00]));
+        }
+    }
+}
+
+/// Width of the black part of `label`.
+fn label_width(label: &GrayImage) -> u32 {
+    label
+        .enumerate_pixels()
+        .filter(|(_, _, px)| px.0[0] < 0x80)
+        .map(|(x, _, _)| x + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Render a ruler along the paper, with ticks at the exact dot pitch of the printer.
+/// Comparing it to a real ruler shows, whether the paper feed is accurate.
+pub fn ruler(cli: &Cli, args: &RulerArgs) -> Result<GrayImage> {
+    if !(1.0..=10_000.0).contains(&args.length) {
+        bail!("invalid length: {}mm", args.length);
+    }
+
+    // the paper feed has the resolution of the print head
+    let dpi = cli.model().capabilities().dpi as f64;
+    let width = cli.width();
+    let inches = args.length / 25.4;
+    let mut img = GrayImage::from_pixel(width, row(inches, dpi) + 24, Luma([0xff]));
+
+    let mut font = cli.font.clone();
+    font.size = 16.0;
+    font.style.clear();
+    font.columns = 1;
+    font.fit = false;
+    font.fit_lines = None;
+    let label = |s: &str| text(&font, s.as_bytes());
+
+    if args.scale != Scale::Imperial {
+        for mm in 0..=args.length as u32 {
+            let y = row(mm as f64 / 25.4, dpi);
+            let len = match mm {
+                mm if mm % 10 == 0 => 64,
+                mm if mm % 5 == 0 => 40,
+                _ => 24,
+            };
+            tick(&mut img, y, len, false);
+            if mm % 10 == 0 && mm > 0 {
+                overlay(
+                    &mut img,
+                    &label(&format!("{}", mm / 10))?,
+                    70,
+                    y.saturating_sub(10),
+                );
+            }
+        }
+        overlay(&mut img, &label("cm")?, 70, 0);
+    }
+
+    if args.scale != Scale::Metric {
+        for sixteenth in 0..=(inches * 16.0) as u32 {
+            let y = row(sixteenth as f64 / 16.0, dpi);
+            let len = match sixteenth {
+                n if n % 16 == 0 => 64,
+                n if n % 8 == 0 => 48,
+                n if n % 4 == 0 => 32,
+                n if n % 2 == 0 => 24,
+                _ => 16,
+            };
+            tick(&mut img, y, len, true);
+            if sixteenth % 16 == 0 && sixteenth > 0 {
+                let l = label(&format!("{}", sixteenth / 16))?;
+                let x = width - 70 - label_width(&l);
+                overlay(&mut img, &l, x, y.saturating_sub(10));
+            }
+        }
+        let l = label("in")?;
+        let x = width - 70 - label_width(&l);
+        overlay(&mut img, &l, x, 0);
+    }
+
+    log::info!(
+        "{:.1}mm are {} rows at {dpi}dpi, measure them to check the paper feed",
+        args.length,
+        row(inches, dpi)
+    );
+    Ok(img)
+}
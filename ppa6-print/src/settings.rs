@@ -0,0 +1,585 @@
+use anyhow::{bail, Context, Result};
+use clap::{error::ErrorKind, parser::ValueSource, ArgMatches, CommandFactory, ValueEnum};
+use ppa6::Concentration;
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use crate::{config::Config, Channel, Cli, Dither, EndOfImageArg, FramingArg, ModelArg};
+
+/// Where the effective value of a setting came from, see `--explain`.
+#[derive(Clone)]
+pub enum Source {
+    Default,
+    CommandLine,
+    Env(String),
+    Config(PathBuf),
+    Profile(String),
+    Type(String),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => f.write_str("default"),
+            Self::CommandLine => f.write_str("command line"),
+            Self::Env(var) => write!(f, "environment {var}"),
+            Self::Config(path) => write!(f, "config file {}", path.display()),
+            Self::Profile(name) => write!(f, "profile {name}"),
+            Self::Type(name) => write!(f, "type {name}"),
+        }
+    }
+}
+
+/// A value of a setting, as written in the config file.
+trait Value: Sized {
+    fn parse(v: &toml::Value) -> Result<Self>;
+    fn show(&self) -> String;
+}
+
+/// Parse numbers and strings with [`FromStr`], e.g. `concentration = 2` or `concentration = "dark"`.
+macro_rules! from_str {
+    ($($ty:ty),*) => {$(
+        impl Value for $ty {
+            fn parse(v: &toml::Value) -> Result<Self> {
+                let s = match v {
+                    toml::Value::String(s) => s.clone(),
+                    v => v.to_string(),
+                };
+                <$ty>::from_str(&s).map_err(|e| anyhow::anyhow!("{s:?}: {e}"))
+            }
+
+            fn show(&self) -> String {
+                self.to_string()
+            }
+        }
+    )*};
+}
+
+from_str!(u8, usize, i32, f32, Concentration);
+
+macro_rules! value_enum {
+    ($($ty:ty),*) => {$(
+        impl Value for $ty {
+            fn parse(v: &toml::Value) -> Result<Self> {
+                let s = v.as_str().context("expected a string")?;
+                <$ty>::from_str(s, true).map_err(|e| anyhow::anyhow!(e))
+            }
+
+            fn show(&self) -> String {
+                self.to_possible_value()
+                    .map_or_else(String::new, |v| v.get_name().to_owned())
+            }
+        }
+    )*};
+}
+
+value_enum!(Channel, Dither, EndOfImageArg, FramingArg, ModelArg);
+
+impl Value for PathBuf {
+    fn parse(v: &toml::Value) -> Result<Self> {
+        Ok(v.as_str().context("expected a path")?.into())
+    }
+
+    fn show(&self) -> String {
+        self.display().to_string()
+    }
+}
+
+impl Value for String {
+    fn parse(v: &toml::Value) -> Result<Self> {
+        Ok(v.as_str().context("expected a string")?.to_owned())
+    }
+
+    fn show(&self) -> String {
+        self.clone()
+    }
+}
+
+impl Value for bool {
+    fn parse(v: &toml::Value) -> Result<Self> {
+        v.as_bool().context("expected true or false")
+    }
+
+    fn show(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// A field of [`Cli`], that holds a `T`, either always or optionally.
+trait Field<T> {
+    fn set(&mut self, v: T);
+    fn show(&self) -> String;
+}
+
+impl<T: Value> Field<T> for T {
+    fn set(&mut self, v: T) {
+        *self = v;
+    }
+
+    fn show(&self) -> String {
+        Value::show(self)
+    }
+}
+
+impl<T: Value> Field<T> for Option<T> {
+    fn set(&mut self, v: T) {
+        *self = Some(v);
+    }
+
+    fn show(&self) -> String {
+        self.as_ref().map_or_else(|| "none".into(), Value::show)
+    }
+}
+
+/// Settings, that can be set in the config file, named like the fields of [`Cli`],
+/// or of a group of options flattened into it, e.g. `pre_job in hooks`.
+macro_rules! settings {
+    ($($field:ident $(in $group:ident)?: $ty:ty),* $(,)?) => {
+        const NAMES: &[&str] = &[$(stringify!($field)),*];
+
+        fn set(cli: &mut Cli, name: &str, v: &toml::Value) -> Result<()> {
+            match name {
+                $(stringify!($field) => Field::<$ty>::set(&mut cli$(.$group)?.$field, <$ty as Value>::parse(v)?),)*
+                _ => unreachable!("unknown setting {name}"),
+            }
+            Ok(())
+        }
+
+        fn show(cli: &Cli, name: &str) -> String {
+            match name {
+                $(stringify!($field) => Field::<$ty>::show(&cli$(.$group)?.$field),)*
+                _ => unreachable!("unknown setting {name}"),
+            }
+        }
+    };
+}
+
+settings! {
+    num: usize,
+    feed: bool,
+    invert: bool,
+    rotate: usize,
+    threshold: u8,
+    dither: Dither,
+    channel: Channel,
+    brighten: i32,
+    contrast: f32,
+    scan_cleanup: bool,
+    no_trim: bool,
+    margin: usize,
+    concentration: Concentration,
+    model: ModelArg,
+    check_status: bool,
+    min_battery: u8,
+    low_battery_warn: bool,
+    end_of_image: EndOfImageArg,
+    adaptive_delay: bool,
+    resume: bool,
+    stamp: bool,
+    framing: FramingArg,
+    dry_run: PathBuf,
+    pre_job in hooks: String,
+    post_job in hooks: String,
+    on_error in hooks: String,
+}
+
+/// The source of every setting, see [`resolve()`].
+#[derive(Default, Clone)]
+pub struct Effective {
+    sources: Vec<(&'static str, Source)>,
+}
+
+impl Effective {
+    /// The settings as `name=value` pairs, for the log of a job.
+    pub fn summary(&self, cli: &Cli) -> String {
+        self.sources
+            .iter()
+            .map(|(name, _)| format!("{}={}", name.replace('_', "-"), show(cli, name)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Print every setting, its value, and where it came from.
+    pub fn explain(&self, cli: &Cli) {
+        let width = NAMES.iter().map(|n| n.len()).max().unwrap_or(0);
+        for (name, source) in &self.sources {
+            let value = show(cli, name);
+            println!("{:width$}  {value:8}  ({source})", name.replace('_', "-"));
+        }
+    }
+
+    fn source(&self, name: &str) -> &Source {
+        self.sources
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map_or(&Source::Default, |(_, source)| source)
+    }
+
+    /// Apply the settings of `table`, except those given on the command line.
+    fn apply(
+        &mut self,
+        cli: &mut Cli,
+        section: &str,
+        table: &toml::Table,
+        to: Source,
+    ) -> Result<()> {
+        for (key, value) in table {
+            let name = key.replace('-', "_");
+            let Some((_, source)) = self.sources.iter_mut().find(|(n, _)| *n == name) else {
+                bail!("unknown setting {key:?} in {section}");
+            };
+            if matches!(source, Source::CommandLine | Source::Env(_)) {
+                continue;
+            }
+            set(cli, &name, value)
+                .with_context(|| format!("invalid setting {key:?} in {section}"))?;
+            *source = to.clone();
+        }
+        Ok(())
+    }
+}
+
+/// Options, that choose how to connect to the printer, and can't be combined.
+const EXCLUSIVE: &[(&str, &str)] = &[
+    ("tcp", "device"),
+    ("wait", "device"),
+    ("wait", "tcp"),
+    ("idle", "device"),
+    ("idle", "tcp"),
+    ("idle", "wait"),
+];
+
+fn is_set(cli: &Cli, id: &str) -> bool {
+    match id {
+        "device" => cli.device.is_some(),
+        "tcp" => cli.tcp.is_some(),
+        "wait" => cli.wait.is_some(),
+        "idle" => cli.idle.is_some(),
+        _ => unreachable!("{id} is not a connection option"),
+    }
+}
+
+fn unset(cli: &mut Cli, id: &str) {
+    match id {
+        "device" => cli.device = None,
+        "tcp" => cli.tcp = None,
+        "wait" => cli.wait = None,
+        "idle" => cli.idle = None,
+        _ => unreachable!("{id} is not a connection option"),
+    }
+}
+
+/// Drop connection options from `PPA6_*` variables, that conflict with ones from the command line,
+/// e.g. `--tcp` overrides `PPA6_DEVICE`.
+fn connection(cli: &mut Cli, matches: &ArgMatches) {
+    for &(a, b) in EXCLUSIVE {
+        if !is_set(cli, a) || !is_set(cli, b) {
+            continue;
+        }
+        match (matches.value_source(a), matches.value_source(b)) {
+            (Some(ValueSource::CommandLine), Some(ValueSource::EnvVariable)) => unset(cli, b),
+            (Some(ValueSource::EnvVariable), Some(ValueSource::CommandLine)) => unset(cli, a),
+            _ => Cli::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!("--{a} cannot be used with --{b}"),
+                )
+                .exit(),
+        }
+    }
+}
+
+/// The selected profile and the profiles it extends, outermost first, without their `extends` keys.
+fn profiles(config: &Config, selected: Option<&str>) -> Result<Vec<(String, toml::Table)>> {
+    let mut name = match selected {
+        Some(name) => name.to_owned(),
+        None if config.profile.contains_key("default") => "default".to_owned(),
+        None => return Ok(Vec::new()),
+    };
+
+    let mut chain: Vec<(String, toml::Table)> = Vec::new();
+    loop {
+        let Some(table) = config.profile.get(&name) else {
+            bail!("no profile {name:?} in the config file");
+        };
+        if chain.iter().any(|(n, _)| *n == name) {
+            bail!("profile {name:?} extends itself");
+        }
+        let mut table = table.clone();
+        let parent = table.remove("extends");
+        chain.push((name, table));
+        match parent {
+            Some(toml::Value::String(parent)) => name = parent,
+            Some(_) => bail!(
+                "`extends` of profile {:?} must be a name",
+                chain[chain.len() - 1].0
+            ),
+            None => break,
+        }
+    }
+    chain.reverse();
+    Ok(chain)
+}
+
+/// Apply the config file to the settings, that weren't given on the command line or in `PPA6_*` variables:
+/// first the `[print]` table, then the profiles from the outermost one to the one selected with `--profile`.
+pub fn resolve(cli: &mut Cli, matches: &ArgMatches) -> Result<Effective> {
+    connection(cli, matches);
+
+    let mut effective = Effective {
+        sources: NAMES
+            .iter()
+            .map(|&name| match matches.value_source(name) {
+                Some(ValueSource::CommandLine) => (name, Source::CommandLine),
+                Some(ValueSource::EnvVariable) => (
+                    name,
+                    Source::Env(format!("PPA6_{}", name.to_ascii_uppercase())),
+                ),
+                _ => (name, Source::Default),
+            })
+            .collect(),
+    };
+
+    // check every section now, not only when a job of that type comes along
+    let config = cli.settings.clone();
+    for (name, table) in &config.types {
+        if let Some(key) = table
+            .keys()
+            .find(|k| !NAMES.contains(&k.replace('-', "_").as_str()))
+        {
+            bail!("unknown setting {key:?} in [type.{name}]");
+        }
+    }
+
+    let source = config.path.clone().map_or(Source::Default, Source::Config);
+    effective.apply(cli, "[print]", &config.print, source)?;
+    for (name, table) in profiles(&config, cli.profile.as_deref())? {
+        effective.apply(
+            cli,
+            &format!("[profile.{name}]"),
+            &table,
+            Source::Profile(name),
+        )?;
+    }
+
+    resume(cli, &effective);
+    cli.font.width = cli.width();
+    Ok(effective)
+}
+
+/// `--resume` needs a way to find the printer again, which only the bus search has.
+fn resume(cli: &Cli, effective: &Effective) {
+    let Some(option) = [
+        ("device", cli.device.is_some()),
+        ("tcp", cli.tcp.is_some()),
+        ("idle", cli.idle.is_some()),
+    ]
+    .into_iter()
+    .find_map(|(name, set)| set.then_some(name)) else {
+        return;
+    };
+    if !cli.resume {
+        return;
+    }
+
+    match effective.source("resume") {
+        Source::CommandLine => Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!(
+                    "--resume cannot be used with --{option}, the printer can't be found again"
+                ),
+            )
+            .exit(),
+        source => log::warn!(
+            "resume ({source}) is ignored with --{option}, the printer can't be found again"
+        ),
+    }
+}
+
+/// Whether `[type.NAME]` applies to `kind`, `NAME` may be a MIME type ending in `/*`.
+fn type_matches(name: &str, kind: &str) -> bool {
+    match name.strip_suffix("/*") {
+        Some(prefix) => kind
+            .split_once('/')
+            .is_some_and(|(k, _)| k.eq_ignore_ascii_case(prefix)),
+        None => name.eq_ignore_ascii_case(kind),
+    }
+}
+
+/// Apply the `[type.NAME]` sections for the `kinds` of the input, e.g. `["image", "image/png", "png"]`,
+/// from the least to the most specific one.
+pub fn apply_type(cli: &mut Cli, kinds: &[String]) -> Result<()> {
+    let mut effective = std::mem::take(&mut cli.effective);
+    let types = cli.settings.types.clone();
+    for kind in kinds {
+        for (name, table) in types.iter().filter(|(name, _)| type_matches(name, kind)) {
+            log::debug!("applying [type.{name}]");
+            effective.apply(
+                cli,
+                &format!("[type.{name}]"),
+                table,
+                Source::Type(name.clone()),
+            )?;
+        }
+    }
+    cli.effective = effective;
+    cli.font.width = cli.width();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::FromArgMatches;
+
+    const CONFIG: &str = r#"
+        [print]
+        threshold = 100
+        margin = 4
+        concentration = "light"
+
+        [profile.default]
+        margin = 8
+
+        [profile.base]
+        dither = "threshold"
+        margin = 10
+
+        [profile.labels]
+        extends = "base"
+        concentration = 2
+        margin = 12
+
+        [type.text]
+        concentration = "dark"
+
+        [type."image/*"]
+        threshold = 150
+        dither = "threshold"
+
+        [type.png]
+        threshold = 200
+    "#;
+
+    fn cli(args: &[&str], config: &str) -> Result<Cli> {
+        let args = ["ppa6-print"].iter().chain(args).chain(&["file"]);
+        let matches = Cli::command().try_get_matches_from(args)?;
+        let mut cli = Cli::from_arg_matches(&matches)?;
+        cli.settings = Config::parse(config)?;
+        cli.effective = resolve(&mut cli, &matches)?;
+        Ok(cli)
+    }
+
+    fn source(cli: &Cli, name: &str) -> String {
+        cli.effective.source(name).to_string()
+    }
+
+    #[test]
+    fn defaults() {
+        let cli = cli(&[], "").unwrap();
+        assert_eq!(cli.margin, 16);
+        assert_eq!(cli.concentration, None);
+        assert_eq!(source(&cli, "margin"), "default");
+    }
+
+    #[test]
+    fn layering() {
+        // [print], then the default profile
+        let cli = cli(&[], CONFIG).unwrap();
+        assert_eq!((cli.threshold, cli.margin), (100, 8));
+        assert_eq!(cli.concentration, Some(Concentration::Light));
+        assert_eq!(source(&cli, "threshold"), "default");
+        assert_eq!(source(&cli, "margin"), "profile default");
+
+        // a profile and the one it extends, the command line wins over both
+        let cli = super::tests::cli(&["--profile", "labels", "-C", "dark"], CONFIG).unwrap();
+        assert_eq!(cli.margin, 12);
+        assert_eq!(show(&cli, "dither"), "threshold");
+        assert_eq!(cli.concentration, Some(Concentration::Dark));
+        assert_eq!(source(&cli, "dither"), "profile base");
+        assert_eq!(source(&cli, "concentration"), "command line");
+    }
+
+    #[test]
+    fn types() {
+        let mut cli = cli(&["--threshold", "50"], CONFIG).unwrap();
+        let kinds = ["image", "image/png", "png"].map(String::from);
+        apply_type(&mut cli, &kinds).unwrap();
+        // the command line beats every type
+        assert_eq!(cli.threshold, 50);
+        assert_eq!(show(&cli, "dither"), "threshold");
+        assert_eq!(source(&cli, "dither"), "type image/*");
+
+        let mut cli = super::tests::cli(&[], CONFIG).unwrap();
+        apply_type(&mut cli, &kinds).unwrap();
+        // the most specific type wins, types beat the profile
+        assert_eq!(cli.threshold, 200);
+        assert_eq!(source(&cli, "threshold"), "type png");
+        assert_eq!(cli.concentration, Some(Concentration::Light));
+
+        apply_type(&mut cli, &["text".into()]).unwrap();
+        assert_eq!(cli.concentration, Some(Concentration::Dark));
+    }
+
+    #[test]
+    fn model_sets_the_width() {
+        let cli = cli(&[], "").unwrap();
+        assert_eq!((cli.width(), cli.font.width), (384, 384));
+
+        let mut cli = super::tests::cli(&[], "[type.text]\nmodel = \"a9-max\"\n").unwrap();
+        apply_type(&mut cli, &["text".into()]).unwrap();
+        assert_eq!(cli.model(), ppa6::Model::A9Max);
+        assert_eq!(cli.font.width, 832);
+    }
+
+    #[test]
+    fn environment() {
+        std::env::set_var("PPA6_CONTRAST", "0.5");
+        let cli = cli(&[], "[print]\ncontrast = 0.25\n");
+        std::env::remove_var("PPA6_CONTRAST");
+        let cli = cli.unwrap();
+        assert_eq!(cli.contrast, 0.5);
+        assert_eq!(source(&cli, "contrast"), "environment PPA6_CONTRAST");
+    }
+
+    #[test]
+    fn hooks() {
+        let config = "[print]\npre-job = \"plug on\"\n[profile.default]\non-error = \"notify\"\n";
+        let cli = cli(&["--post-job", "plug off"], config).unwrap();
+        assert_eq!(cli.hooks.pre_job.as_deref(), Some("plug on"));
+        assert_eq!(cli.hooks.post_job.as_deref(), Some("plug off"));
+        assert_eq!(cli.hooks.on_error.as_deref(), Some("notify"));
+        assert_eq!(source(&cli, "post_job"), "command line");
+        assert_eq!(source(&cli, "on_error"), "profile default");
+        assert!(super::tests::cli(&[], "[print]\npre-job = 1\n").is_err());
+    }
+
+    #[test]
+    fn invalid_configs() {
+        let invalid = [
+            "[print]\nspeed = 3\n",
+            "[print]\nthreshold = 300\n",
+            "[type.png]\nspeed = 3\n",
+            "[profile.default]\nextends = \"a\"\n[profile.a]\nextends = \"default\"\n",
+            "[profile.default]\nextends = 1\n",
+            "[profile.default]\nextends = \"missing\"\n",
+            "[[transform]]\nkind = \"thermal-limit\"\nmax-coverage = 0.0\n",
+            "[[transform]]\nkind = \"thermal-limit\"\nmax-coverage = 1.5\n",
+            "[[transform]]\nkind = \"thermal-limit\"\nmax-coverage = nan\n",
+            "[[transform]]\nkind = \"thermal-limit\"\nmax-coverage = 0.5\nband-height = 0\n",
+        ];
+        for config in invalid {
+            assert!(cli(&[], config).is_err(), "{config}");
+        }
+        assert!(cli(&["--profile", "missing"], "").is_err());
+    }
+
+    #[test]
+    fn type_names() {
+        assert!(type_matches("png", "PNG"));
+        assert!(type_matches("image/*", "image/png"));
+        assert!(!type_matches("image/*", "image"));
+        assert!(!type_matches("image/*", "text/plain"));
+        assert!(!type_matches("png", "image/png"));
+    }
+}
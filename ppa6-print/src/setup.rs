@@ -0,0 +1,283 @@
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use image::{GrayImage, Luma};
+use ppa6::{Concentration, Document, PrintOptions, Printer};
+#[cfg(target_os = "linux")]
+use std::process::{Command, Stdio};
+use std::{
+    io::{BufRead, Write},
+    path::Path,
+};
+use toml_edit::{DocumentMut, Item};
+
+use crate::{config, open_printer, pack_bits, stack, text::text, Cli};
+
+/// The udev rules, that give every user access to the printer.
+#[cfg(target_os = "linux")]
+const UDEV_RULES: &str = include_str!("../../etc/80-ppa6.rules");
+
+#[cfg(target_os = "linux")]
+const UDEV_PATH: &str = "/etc/udev/rules.d/80-ppa6.rules";
+
+/// Concentrations of the calibration print, in order.
+const LEVELS: [Concentration; 3] = [
+    Concentration::Light,
+    Concentration::Medium,
+    Concentration::Dark,
+];
+
+#[derive(Args, Clone)]
+pub struct SetupArgs {
+    /// Don't print anything, only connect to the printer and write the config file.
+    #[arg(long)]
+    no_print: bool,
+}
+
+/// Read a line from stdin, after showing `prompt`.
+fn prompt(prompt: &str) -> Result<String> {
+    print!("{prompt} ");
+    std::io::stdout().flush()?;
+    let mut line = String::new();
+    if std::io::stdin().lock().read_line(&mut line)? == 0 {
+        bail!("setup aborted");
+    }
+    Ok(line.trim().to_owned())
+}
+
+/// Ask a yes/no question, an empty answer means `default`.
+fn confirm(question: &str, default: bool) -> Result<bool> {
+    let hint = if default { "[Y/n]" } else { "[y/N]" };
+    loop {
+        match prompt(&format!("{question} {hint}"))?
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => println!("Please answer y or n."),
+        }
+    }
+}
+
+/// Install the udev rules with sudo, if the user agrees.
+#[cfg(target_os = "linux")]
+fn install_udev_rules() -> Result<()> {
+    println!("Your user isn't allowed to access the printer. These udev rules allow it:\n");
+    println!("{}", UDEV_RULES.trim_end());
+    println!();
+    if !confirm(&format!("Install them to {UDEV_PATH} with sudo?"), true)? {
+        println!("Install them yourself, then run `ppa6-print setup` again.");
+        bail!("no access to the printer");
+    }
+
+    let mut tee = Command::new("sudo")
+        .args(["tee", UDEV_PATH])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .context("cannot run sudo")?;
+    tee.stdin.take().unwrap().write_all(UDEV_RULES.as_bytes())?;
+    if !tee.wait()?.success() {
+        bail!("cannot write {UDEV_PATH}");
+    }
+    for args in [&["control", "--reload-rules"][..], &["trigger"]] {
+        let status = Command::new("sudo").arg("udevadm").args(args).status()?;
+        if !status.success() {
+            bail!("udevadm {} failed: {status}", args.join(" "));
+        }
+    }
+    prompt("Rules installed, unplug the printer and plug it back in, then press Enter.")?;
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn install_driver() -> Result<()> {
+    println!("Windows needs the WinUSB driver for the printer:");
+    println!("1. Download Zadig from https://zadig.akeo.ie and run it.");
+    println!(
+        "2. Select the PeriPage printer (enable Options > List All Devices, if it's missing)."
+    );
+    println!("3. Choose WinUSB as the driver and click Replace Driver.");
+    prompt("Press Enter, when the driver is installed.")?;
+    Ok(())
+}
+
+/// Connect to the printer, helping with the usual problems on the way.
+fn connect(cli: &Cli) -> Result<Printer> {
+    loop {
+        let e = match open_printer(cli) {
+            Ok(printer) => return Ok(printer),
+            Err(e) => e,
+        };
+        match e.downcast_ref::<ppa6::Error>() {
+            Some(ppa6::Error::NotFound | ppa6::Error::Disconnected) => {
+                #[cfg(target_os = "windows")]
+                if confirm("No printer found. Is the WinUSB driver installed?", false)? {
+                    prompt("Connect the printer and switch it on, then press Enter.")?;
+                } else {
+                    install_driver()?;
+                }
+                #[cfg(not(target_os = "windows"))]
+                prompt("No printer found. Connect it and switch it on, then press Enter.")?;
+            }
+            #[cfg(target_os = "linux")]
+            Some(ppa6::Error::PermissionDenied) => install_udev_rules()?,
+            Some(ppa6::Error::DeviceBusy { .. }) => {
+                println!("{e}");
+                prompt("Close the other program using the printer, then press Enter.")?;
+            }
+            _ => return Err(e),
+        }
+    }
+}
+
+/// A label, a black bar, and a fine checkerboard, to judge a concentration by.
+fn swatch(cli: &Cli, n: usize, level: Concentration) -> Result<GrayImage> {
+    let label = text(&cli.font, format!("{n}: {level}").as_bytes())?;
+    let pattern = GrayImage::from_fn(cli.width(), 48, |x, y| {
+        let black = y < 24 || (x + y) % 2 == 0;
+        Luma([if black { 0x00 } else { 0xff }])
+    });
+    let gap = GrayImage::from_pixel(cli.width(), 16, Luma([0xff]));
+    Ok(stack(&[label, pattern, gap]))
+}
+
+/// Print `img`, with `concentration`, if given, which the job sets after its reset.
+fn print_image(
+    printer: &mut Printer,
+    img: &GrayImage,
+    concentration: Option<Concentration>,
+) -> Result<()> {
+    let doc = Document::new(pack_bits(img, 0x80, false), img.width() as u16)?;
+    let opts = PrintOptions {
+        concentration,
+        ..PrintOptions::default()
+    };
+    printer.print(&doc, &opts)?;
+    Ok(())
+}
+
+/// Print every concentration, and let the user pick the best one.
+fn calibrate(cli: &Cli, printer: &mut Printer) -> Result<Concentration> {
+    println!(
+        "Printing a calibration strip with {} levels...",
+        LEVELS.len()
+    );
+    for (i, level) in LEVELS.into_iter().enumerate() {
+        print_image(printer, &swatch(cli, i + 1, level)?, Some(level))?;
+    }
+    printer.feed_to_tear_off()?;
+
+    loop {
+        let answer = prompt(&format!(
+            "Which one looks best, with a solid black bar and an even gray? [1-{}, default 2]",
+            LEVELS.len()
+        ))?;
+        if answer.is_empty() {
+            return Ok(Concentration::Medium);
+        }
+        match answer.parse::<usize>() {
+            Ok(n) if (1..=LEVELS.len()).contains(&n) => return Ok(LEVELS[n - 1]),
+            _ => println!("Please enter a number between 1 and {}.", LEVELS.len()),
+        }
+    }
+}
+
+/// Set `concentration` in the `[print]` table of the config file, keeping everything else,
+/// including comments and formatting.
+fn write_config(path: &Path, concentration: Concentration) -> Result<()> {
+    let mut doc: DocumentMut = match std::fs::read_to_string(path) {
+        Ok(data) => data
+            .parse()
+            .with_context(|| format!("invalid config {}", path.display()))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => DocumentMut::new(),
+        Err(e) => return Err(e).with_context(|| format!("cannot read {}", path.display())),
+    };
+    let print = doc
+        .entry("print")
+        .or_insert_with(toml_edit::table)
+        .as_table_like_mut()
+        .context("[print] of the config is not a table")?;
+    // replace only the value, so comments on the line stay
+    match print.get_mut("concentration").and_then(Item::as_value_mut) {
+        Some(value) => {
+            let decor = value.decor().clone();
+            *value = concentration.to_string().into();
+            *value.decor_mut() = decor;
+        }
+        None => {
+            print.insert("concentration", toml_edit::value(concentration.to_string()));
+        }
+    }
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, doc.to_string())
+        .with_context(|| format!("cannot write {}", path.display()))
+}
+
+/// Walk through connecting the printer, a test print, and choosing the concentration,
+/// and write the result into the config file.
+pub fn setup(cli: &Cli, args: &SetupArgs) -> Result<()> {
+    println!("Looking for the printer...");
+    let mut printer = connect(cli)?;
+    println!(
+        "Found a {} ({}), battery: {}.",
+        printer.detect_model()?,
+        printer.get_name()?,
+        printer.get_battery_status()?
+    );
+
+    let mut concentration = Concentration::Medium;
+    if !args.no_print {
+        print_image(&mut printer, &text(&cli.font, b"Hello from ppa6!")?, None)?;
+        if !confirm("Did the printer print \"Hello from ppa6!\"?", true)? {
+            bail!("test print failed, is there paper in the printer, with the shiny side up?");
+        }
+        concentration = calibrate(cli, &mut printer)?;
+    }
+
+    let path = match &cli.config {
+        Some(path) => path.clone(),
+        None => config::default_path().context("cannot find the config directory, use --config")?,
+    };
+    if path.exists() && !confirm(&format!("Update {}?", path.display()), true)? {
+        println!("Add `concentration = \"{concentration}\"` to [print] yourself.");
+        return Ok(());
+    }
+    write_config(&path, concentration)?;
+    println!("Wrote {}, you're ready to print!", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_keeps_comments() {
+        let path = std::env::temp_dir().join(format!("ppa6-setup-{}.toml", std::process::id()));
+        let config = "\
+# my printer
+[print]
+# darker is better
+concentration = \"light\" # was medium
+feed = 30 # rows
+
+[text]
+size = 20
+";
+        std::fs::write(&path, config).unwrap();
+        write_config(&path, Concentration::Dark).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written, config.replace("\"light\"", "\"dark\""));
+
+        std::fs::remove_file(&path).unwrap();
+        write_config(&path, Concentration::Light).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written, "[print]\nconcentration = \"light\"\n");
+        let _ = std::fs::remove_file(&path);
+    }
+}
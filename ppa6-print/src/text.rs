@@ -0,0 +1,360 @@
+use anyhow::Result;
+use clap::{Args, ValueEnum};
+use cosmic_text::{
+    Attrs, Buffer, Color, Family, FontSystem, LayoutRun, Metrics, Shaping, SwashCache,
+};
+use image::{DynamicImage, GrayImage, Luma, RgbImage};
+use std::ops::Range;
+
+/// Options for rendering text.
+#[derive(Args, Clone)]
+pub struct TextArgs {
+    /// Font size for `--text`. Anything below 12 starts to be difficult to read.
+    #[arg(short = 'S', long, default_value_t = 18.0)]
+    pub size: f32,
+
+    /// Font weight for `--text`. Good numbers are 600 and 800.
+    #[arg(short, long, default_value_t = 800)]
+    pub weight: u16,
+
+    /// Choose the largest font size, at which no line of `--text` has to be wrapped.
+    #[arg(long)]
+    pub fit: bool,
+
+    /// Choose the largest font size, at which `--text` fits into this many lines.
+    #[arg(long, value_name = "N")]
+    pub fit_lines: Option<usize>,
+
+    /// Text styles for `--text`, can be given multiple times or comma-separated.
+    /// Inverted and underlined text make good receipt headers.
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub style: Vec<TextStyle>,
+
+    /// Extra space between letters for `--text`, in pixels.
+    /// A pixel or two make small text more legible on paper.
+    #[arg(long, default_value_t = 0.0)]
+    pub letter_spacing: f32,
+
+    /// Emulate bold text for `--text`, by printing every glyph twice, 1px apart.
+    #[arg(long)]
+    pub double_strike: bool,
+
+    /// Number of columns for `--text`, short lines of text can be put side by side to save paper.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=4))]
+    pub columns: u8,
+
+    /// Line Height Factor. This gets multiplied with the font size to get the line height.
+    #[arg(short, long, default_value_t = 1.0)]
+    pub line_height: f32,
+
+    /// Use a monospace font for `--text`.
+    #[arg(long)]
+    pub mono: bool,
+
+    /// Width of the rendered image in pixels, set from `--model`.
+    #[arg(skip = 384u32)]
+    pub width: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TextStyle {
+    /// White text on a black background.
+    Inverted,
+    /// Only the outlines of the glyphs.
+    Outline,
+    /// Underline every line.
+    Underline,
+}
+
+fn layout_text(
+    opts: &TextArgs,
+    font_system: &mut FontSystem,
+    text: &str,
+    width: f32,
+    size: f32,
+) -> Buffer {
+    let metrics = Metrics::new(size, size * opts.line_height);
+    let mut buffer = Buffer::new(font_system, metrics);
+    let mut buffer_ref = buffer.borrow_with(font_system);
+    buffer_ref.set_size(Some(width), None);
+    let mut attrs = Attrs::new();
+    attrs.weight.0 = opts.weight;
+    if opts.mono {
+        attrs = attrs.family(Family::Monospace);
+    }
+
+    buffer_ref.set_text(text, attrs, Shaping::Advanced);
+    buffer_ref.shape_until_scroll(true);
+    buffer
+}
+
+/// Find the largest font size, at which `text` satisfies `--fit` and `--fit-lines`.
+fn fit_size(opts: &TextArgs, font_system: &mut FontSystem, text: &str, width: f32) -> f32 {
+    let paragraphs = text.lines().count().max(1);
+    let mut fits = |size: f32| {
+        let buffer = layout_text(opts, font_system, text, width, size);
+        let mut lines = 0;
+        let mut line_w = 0.0f32;
+        for run in buffer.layout_runs() {
+            lines += 1;
+            line_w = line_w.max(run.line_w);
+        }
+        let fit_width = !opts.fit || (lines <= paragraphs && line_w <= width);
+        let fit_lines = opts.fit_lines.is_none_or(|n| lines <= n);
+        fit_width && fit_lines
+    };
+
+    let (mut lo, mut hi) = (4.0f32, 256.0f32);
+    if !fits(lo) {
+        log::warn!("text doesn't fit, even at font size {lo}");
+        return lo;
+    }
+    while hi - lo > 0.25 {
+        let mid = (lo + hi) / 2.0;
+        if fits(mid) {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    log::debug!("fitted font size: {lo}");
+    lo
+}
+
+/// Rasterize `text`, wrapped at `width` pixels, into an image `opts.width` pixels wide.
+/// Also returns the vertical pixel range of each laid out line.
+fn render_text(
+    opts: &TextArgs,
+    font_system: &mut FontSystem,
+    text: &str,
+    width: f32,
+    size: f32,
+) -> (GrayImage, Vec<Range<u32>>) {
+    let mut cache = SwashCache::new();
+    let spacing = opts.letter_spacing;
+    let run_width =
+        |run: &LayoutRun| run.line_w + spacing * run.glyphs.len().saturating_sub(1) as f32;
+
+    // letter spacing is added after layout, so wrap narrower until the spaced lines fit
+    let mut wrap = width;
+    let buffer = loop {
+        let buffer = layout_text(opts, font_system, text, wrap, size);
+        let widest = buffer
+            .layout_runs()
+            .map(|run| run_width(&run))
+            .fold(0.0, f32::max);
+        if widest <= width + 0.5 || wrap <= size {
+            break buffer;
+        }
+        wrap -= widest - width;
+    };
+
+    let lines = buffer
+        .layout_runs()
+        .map(|run| {
+            let top = run.line_top.max(0.0) as u32;
+            top..(run.line_top + run.line_height).ceil() as u32
+        })
+        .collect::<Vec<_>>();
+
+    // (baseline, width) of every line
+    let underlines = buffer
+        .layout_runs()
+        .map(|run| (run.line_y, run_width(&run)))
+        .collect::<Vec<_>>();
+
+    let mut pixels = Vec::new();
+    let mut height = 0;
+    let img_width = opts.width as usize;
+
+    let mut draw = |x: i32, y: i32, color: Color| {
+        let a = color.a();
+        if x < 0 || y < 0 || x >= img_width as i32 || a == 0 {
+            return;
+        }
+
+        let x = x as usize;
+        let y = y as usize;
+
+        if y >= height {
+            height = y + 1;
+            pixels.resize(3 * img_width * height, 0xff);
+        }
+
+        let scale = |c: u8| {
+            let c = c as f32 / 255.0;
+            let a = a as f32 / 255.0;
+            let c = (c * a) + (1.0 * (1.0 - a));
+            (c * 255.0).clamp(0.0, 255.0) as u8
+        };
+
+        // overlapping pixels (double-strike, tight spacing) keep the darker value
+        let px = &mut pixels[(y * img_width + x) * 3..][..3];
+        px[0] = px[0].min(scale(color.r()));
+        px[1] = px[1].min(scale(color.g()));
+        px[2] = px[2].min(scale(color.b()));
+    };
+
+    // like Buffer::draw(), but with letter spacing and double-strike
+    let strikes: &[i32] = if opts.double_strike { &[0, 1] } else { &[0] };
+    for run in buffer.layout_runs() {
+        for (i, glyph) in run.glyphs.iter().enumerate() {
+            let physical = glyph.physical((i as f32 * spacing, 0.0), 1.0);
+            let color = glyph.color_opt.unwrap_or(Color::rgb(0xff, 0, 0));
+            cache.with_pixels(font_system, physical.cache_key, color, |x, y, color| {
+                for dx in strikes {
+                    draw(
+                        physical.x + x + dx,
+                        run.line_y as i32 + physical.y + y,
+                        color,
+                    );
+                }
+            });
+        }
+    }
+
+    let img =
+        DynamicImage::ImageRgb8(RgbImage::from_vec(opts.width, height as u32, pixels).unwrap());
+    let mut img = img.into_luma8();
+
+    if opts.style.contains(&TextStyle::Underline) {
+        let offset = (size / 8.0).ceil().max(2.0);
+        let thickness = (size / 12.0).round().max(1.0) as u32;
+        for (baseline, w) in underlines {
+            let y = (baseline + offset) as u32;
+            if y + thickness > img.height() {
+                let mut taller = GrayImage::from_pixel(opts.width, y + thickness, Luma([0xff]));
+                image::imageops::replace(&mut taller, &img, 0, 0);
+                img = taller;
+            }
+            for y in y..y + thickness {
+                for x in 0..(w.ceil() as u32).min(opts.width) {
+                    img.put_pixel(x, y, Luma([0x00]));
+                }
+            }
+        }
+    }
+
+    (img, lines)
+}
+
+/// Replace glyphs by their outlines, 1px around the original shape.
+fn outline(img: &GrayImage) -> GrayImage {
+    let (w, h) = img.dimensions();
+    let dark = |x: i64, y: i64| {
+        x >= 0
+            && y >= 0
+            && x < w as i64
+            && y < h as i64
+            && img.get_pixel(x as u32, y as u32).0[0] < 0x80
+    };
+
+    GrayImage::from_fn(w, h, |x, y| {
+        let (x, y) = (x as i64, y as i64);
+        let edge = !dark(x, y)
+            && [
+                (-1, 0),
+                (1, 0),
+                (0, -1),
+                (0, 1),
+                (-1, -1),
+                (1, 1),
+                (-1, 1),
+                (1, -1),
+            ]
+            .iter()
+            .any(|(dx, dy)| dark(x + dx, y + dy));
+        Luma([if edge { 0x00 } else { 0xff }])
+    })
+}
+
+/// Space between two columns of text.
+const COLUMN_GAP: u32 = 16;
+
+/// Width available for text in one column.
+fn column_width(opts: &TextArgs) -> u32 {
+    match opts.columns as u32 {
+        1 => opts.width - 44,
+        n => (opts.width - (n - 1) * COLUMN_GAP) / n,
+    }
+}
+
+/// Lay out text in `opts.columns` columns side by side, separated by a divider.
+/// The lines are distributed evenly, so that all columns end at about the same height.
+fn text_columns(opts: &TextArgs, font_system: &mut FontSystem, text: &str, size: f32) -> GrayImage {
+    let n = opts.columns as u32;
+    let col_width = column_width(opts);
+    let (column, lines) = render_text(opts, font_system, text, col_width as f32, size);
+
+    let per_col = lines.len().div_ceil(n as usize).max(1);
+    let parts = lines
+        .chunks(per_col)
+        .map(|lines| lines[0].start..lines.last().unwrap().end.min(column.height()))
+        .collect::<Vec<_>>();
+    let height = parts.iter().map(|r| r.len() as u32).max().unwrap_or(0);
+
+    let mut img = GrayImage::from_pixel(opts.width, height, Luma([0xff]));
+    for (i, rows) in parts.into_iter().enumerate() {
+        let x0 = i as u32 * (col_width + COLUMN_GAP);
+        for (y, sy) in rows.enumerate() {
+            for x in 0..col_width {
+                img.put_pixel(x0 + x, y as u32, *column.get_pixel(x, sy));
+            }
+        }
+
+        if i > 0 {
+            for y in 0..height {
+                img.put_pixel(x0 - COLUMN_GAP / 2, y, Luma([0x00]));
+            }
+        }
+    }
+    img
+}
+
+/// Number of characters, that fit into one line of monospaced text.
+pub fn chars_per_line(opts: &TextArgs) -> usize {
+    let mut opts = opts.clone();
+    opts.mono = true;
+
+    const N: usize = 64;
+    let mut font_system = FontSystem::new();
+    let buffer = layout_text(&opts, &mut font_system, &"0".repeat(N), f32::MAX, opts.size);
+    let advance = buffer.layout_runs().map(|run| run.line_w).sum::<f32>() / N as f32;
+    (column_width(&opts) as f32 / (advance + opts.letter_spacing)) as usize
+}
+
+// TODO: parse ANSI escape sequences
+pub fn text(opts: &TextArgs, data: &[u8]) -> Result<GrayImage> {
+    let text = String::from_utf8(data.to_vec())?;
+
+    let mut font_system = FontSystem::new();
+
+    let size = if opts.fit || opts.fit_lines.is_some() {
+        fit_size(opts, &mut font_system, &text, column_width(opts) as f32)
+    } else {
+        opts.size
+    };
+
+    let mut img = if opts.columns > 1 {
+        text_columns(opts, &mut font_system, &text, size)
+    } else {
+        render_text(
+            opts,
+            &mut font_system,
+            &text,
+            column_width(opts) as f32,
+            size,
+        )
+        .0
+    };
+
+    if opts.style.contains(&TextStyle::Outline) {
+        img = outline(&img);
+    }
+
+    if opts.style.contains(&TextStyle::Inverted) {
+        image::imageops::invert(&mut img);
+    }
+
+    Ok(img)
+}
@@ -0,0 +1,179 @@
+use clap::Args;
+
+/// Where releases publish their manifest, and its signature at the same URL with `.sig` appended.
+const MANIFEST_URL: &str =
+    "https://github.com/realchonk/ppa6/releases/latest/download/manifest.json";
+
+#[derive(Args, Clone)]
+pub struct UpdateArgs {
+    /// Only check for a new version, don't install it.
+    #[arg(long)]
+    check: bool,
+
+    /// Install without asking.
+    #[arg(short, long)]
+    yes: bool,
+
+    /// URL of the release manifest.
+    #[arg(long, env = "PPA6_UPDATE_MANIFEST", default_value = MANIFEST_URL)]
+    manifest: String,
+}
+
+#[cfg(not(feature = "self-update"))]
+pub fn update(_args: &UpdateArgs) -> anyhow::Result<()> {
+    anyhow::bail!(
+        "ppa6-print was built without the `self-update` feature, update it the way you installed it"
+    );
+}
+
+#[cfg(feature = "self-update")]
+pub use imp::update;
+
+#[cfg(feature = "self-update")]
+mod imp {
+    use anyhow::{bail, Context, Result};
+    use base64::prelude::*;
+    use ring::{
+        digest::{digest, SHA256},
+        signature::{UnparsedPublicKey, ED25519},
+    };
+    use serde::Deserialize;
+    use std::{
+        collections::HashMap,
+        io::{BufRead, Read, Write},
+        path::Path,
+    };
+
+    use super::UpdateArgs;
+
+    /// The ed25519 public key of the releases, base64 encoded, set when building a release.
+    const PUBLIC_KEY: Option<&str> = option_env!("PPA6_UPDATE_KEY");
+
+    /// `manifest.json` of a release.
+    #[derive(Deserialize)]
+    struct Manifest {
+        version: String,
+        #[serde(default)]
+        notes: Option<String>,
+        /// The binaries, by platform, e.g. `linux-x86_64`.
+        assets: HashMap<String, Asset>,
+    }
+
+    #[derive(Deserialize)]
+    struct Asset {
+        url: String,
+        /// SHA-256 of the binary, hex encoded.
+        sha256: String,
+    }
+
+    fn get(url: &str) -> Result<Vec<u8>> {
+        log::debug!("fetching {url}...");
+        let mut data = Vec::new();
+        ureq::get(url)
+            .call()
+            .with_context(|| format!("cannot fetch {url}"))?
+            .into_reader()
+            .read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// `"0.2.10"` -> `[0, 2, 10]`, ignoring a leading `v` and a `-pre` suffix.
+    fn parse_version(v: &str) -> Vec<u64> {
+        let v = v.trim().trim_start_matches('v');
+        let v = v.split(['-', '+']).next().unwrap_or(v);
+        v.split('.').map(|n| n.parse().unwrap_or(0)).collect()
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    /// Fetch the manifest and check its signature against [`PUBLIC_KEY`].
+    fn manifest(url: &str) -> Result<Manifest> {
+        let Some(key) = PUBLIC_KEY else {
+            bail!(
+                "this build has no key to verify updates with, update it the way you installed it"
+            );
+        };
+        let key = BASE64_STANDARD
+            .decode(key.trim())
+            .context("invalid PPA6_UPDATE_KEY")?;
+
+        let data = get(url)?;
+        let sig = get(&format!("{url}.sig"))?;
+        let sig = BASE64_STANDARD
+            .decode(sig.trim_ascii())
+            .context("invalid signature of the manifest")?;
+        UnparsedPublicKey::new(&ED25519, &key)
+            .verify(&data, &sig)
+            .map_err(|_| anyhow::anyhow!("the signature of {url} is invalid, not updating"))?;
+
+        serde_json::from_slice(&data).context("invalid manifest")
+    }
+
+    /// Replace the running binary with `new`, by renaming a file next to it over it.
+    fn replace_exe(exe: &Path, new: &[u8]) -> Result<()> {
+        let name = exe.file_name().context("invalid path of the binary")?;
+        let tmp = exe.with_file_name(format!(".{}.new", name.to_string_lossy()));
+        std::fs::write(&tmp, new).with_context(|| format!("cannot write {}", tmp.display()))?;
+
+        #[cfg(unix)]
+        std::fs::set_permissions(&tmp, std::fs::metadata(exe)?.permissions())?;
+
+        // Windows can't replace a running binary, but rename it
+        #[cfg(windows)]
+        {
+            let old = exe.with_extension("old");
+            let _ = std::fs::remove_file(&old);
+            std::fs::rename(exe, &old)?;
+        }
+
+        std::fs::rename(&tmp, exe).with_context(|| format!("cannot replace {}", exe.display()))
+    }
+
+    pub fn update(args: &UpdateArgs) -> Result<()> {
+        let current = env!("CARGO_PKG_VERSION");
+        let manifest = manifest(&args.manifest)?;
+        if parse_version(&manifest.version) <= parse_version(current) {
+            println!("ppa6-print {current} is up to date.");
+            return Ok(());
+        }
+
+        println!(
+            "ppa6-print {} is available, you have {current}.",
+            manifest.version
+        );
+        if let Some(notes) = &manifest.notes {
+            println!("\n{}\n", notes.trim_end());
+        }
+        if args.check {
+            return Ok(());
+        }
+
+        let platform = format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH);
+        let Some(asset) = manifest.assets.get(&platform) else {
+            bail!("the release has no binary for {platform}");
+        };
+
+        let exe = std::env::current_exe()?.canonicalize()?;
+        if !args.yes {
+            print!("Replace {}? [y/N] ", exe.display());
+            std::io::stdout().flush()?;
+            let mut answer = String::new();
+            std::io::stdin().lock().read_line(&mut answer)?;
+            if !matches!(answer.trim(), "y" | "Y" | "yes") {
+                return Ok(());
+            }
+        }
+
+        let bin = get(&asset.url)?;
+        let sum = hex(digest(&SHA256, &bin).as_ref());
+        if !sum.eq_ignore_ascii_case(asset.sha256.trim()) {
+            bail!("checksum of {} doesn't match the manifest", asset.url);
+        }
+
+        replace_exe(&exe, &bin)?;
+        println!("Updated to ppa6-print {}.", manifest.version);
+        Ok(())
+    }
+}
@@ -0,0 +1,575 @@
+use anyhow::{bail, Result};
+use clap::Args;
+use image::GrayImage;
+use std::{collections::HashMap, path::PathBuf};
+
+use crate::{read_input, text::text, Cli};
+
+#[derive(Args, Clone)]
+pub struct WebArgs {
+    /// URL of the page (requires the `net` feature), a saved HTML file, or `-` for stdin.
+    page: PathBuf,
+
+    /// Print only the article of the page, without navigation, ads, and comments.
+    #[arg(long)]
+    readability: bool,
+}
+
+/// Elements, whose content is never printed.
+const SKIP: &[&str] = &[
+    "script", "style", "noscript", "template", "svg", "iframe", "select",
+];
+
+/// Elements, that are usually not part of the article.
+const BOILERPLATE: &[&str] = &["nav", "header", "footer", "aside", "form", "button", "menu"];
+
+/// Substrings of classes and IDs, that are usually not part of the article.
+const BOILERPLATE_NAMES: &[&str] = &[
+    "comment",
+    "sidebar",
+    "footer",
+    "navbar",
+    "menu",
+    "share",
+    "social",
+    "promo",
+    "related",
+    "cookie",
+    "banner",
+    "newsletter",
+    "advert",
+    "sponsor",
+];
+
+/// Elements, that start a new block of text.
+const BLOCKS: &[&str] = &[
+    "p",
+    "div",
+    "br",
+    "li",
+    "tr",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "blockquote",
+    "pre",
+    "article",
+    "main",
+    "section",
+    "ul",
+    "ol",
+    "table",
+    "dd",
+    "dt",
+    "figcaption",
+    "hr",
+];
+
+/// Elements without a closing tag.
+const VOID: &[&str] = &[
+    "br", "hr", "img", "input", "meta", "link", "area", "base", "col", "embed", "source", "track",
+    "wbr",
+];
+
+/// Elements, that stop the search for an element closed by [`implied_end()`].
+const SCOPES: &[&str] = &[
+    "ul",
+    "ol",
+    "dl",
+    "table",
+    "tr",
+    "td",
+    "th",
+    "li",
+    "dd",
+    "dt",
+    "div",
+    "article",
+    "main",
+    "section",
+    "blockquote",
+];
+
+/// Open elements, that are closed by opening `name`, like browsers do for `<p>one<p>two`.
+fn implied_end(name: &str) -> &'static [&'static str] {
+    match name {
+        "li" => &["li"],
+        "dt" | "dd" => &["dt", "dd"],
+        "tr" => &["tr", "td", "th"],
+        "td" | "th" => &["td", "th"],
+        "p" | "div" | "ul" | "ol" | "dl" | "table" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6"
+        | "blockquote" | "pre" | "section" | "article" | "main" | "hr" | "header" | "footer"
+        | "nav" | "aside" | "form" | "figure" => &["p"],
+        _ => &[],
+    }
+}
+
+/// Find the `>`, that ends the tag at the start of `s`, skipping quoted attribute values.
+fn tag_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut after_eq = false;
+    for (i, ch) in s.char_indices() {
+        match (quote, ch) {
+            (Some(q), ch) if ch == q => quote = None,
+            (Some(_), _) => continue,
+            (None, '"' | '\'') if after_eq => quote = Some(ch),
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+        if !ch.is_ascii_whitespace() {
+            after_eq = quote.is_none() && ch == '=';
+        }
+    }
+    None
+}
+
+/// A paragraph, heading, or list item of the page.
+struct Block {
+    tag: String,
+    text: String,
+    /// IDs of the elements, the block is in, outermost first.
+    ancestors: Vec<usize>,
+    /// ID of the element, that started the block, e.g. the `<p>`, if it's still open.
+    element: Option<usize>,
+    boilerplate: bool,
+}
+
+/// An open element.
+struct Element {
+    id: usize,
+    name: String,
+    boilerplate: bool,
+}
+
+#[derive(Default)]
+struct Page {
+    title: String,
+    blocks: Vec<Block>,
+    /// IDs of `<article>` and `<main>` elements.
+    articles: Vec<usize>,
+}
+
+/// Split a tag like `div class="x"` into its lowercase name and attributes.
+fn parse_tag(tag: &str) -> (String, HashMap<String, String>) {
+    let tag = tag.trim_end_matches('/');
+    let (name, mut rest) = tag
+        .split_once(|ch: char| ch.is_ascii_whitespace())
+        .unwrap_or((tag, ""));
+
+    let mut attrs = HashMap::new();
+    loop {
+        rest = rest.trim_start();
+        let Some(end) = rest.find(|ch: char| ch == '=' || ch.is_ascii_whitespace()) else {
+            if !rest.is_empty() {
+                attrs.insert(rest.to_ascii_lowercase(), String::new());
+            }
+            break;
+        };
+        let key = rest[..end].to_ascii_lowercase();
+        rest = rest[end..].trim_start();
+        let Some(value) = rest.strip_prefix('=') else {
+            attrs.insert(key, String::new());
+            continue;
+        };
+        let value = value.trim_start();
+        let (value, next) = match value.chars().next() {
+            Some(q @ ('"' | '\'')) => value[1..].split_once(q).unwrap_or((&value[1..], "")),
+            _ => value
+                .split_once(|ch: char| ch.is_ascii_whitespace())
+                .unwrap_or((value, "")),
+        };
+        attrs.insert(key, value.to_owned());
+        rest = next;
+    }
+    (name.to_ascii_lowercase(), attrs)
+}
+
+/// Decode HTML character references, like `&amp;` or `&#8217;`.
+fn decode_entities(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| Some((entity(&rest[1..end + 1])?, end + 2)));
+        match decoded {
+            Some((ch, len)) => {
+                out.push(ch);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn entity(name: &str) -> Option<char> {
+    if let Some(num) = name.strip_prefix('#') {
+        let code = match num.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => num.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        "ndash" => '–',
+        "mdash" => '—',
+        "hellip" => '…',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        "copy" => '©',
+        "euro" => '€',
+        _ => return None,
+    })
+}
+
+fn is_boilerplate(name: &str, attrs: &HashMap<String, String>) -> bool {
+    if BOILERPLATE.contains(&name) {
+        return true;
+    }
+    if attrs.get("role").is_some_and(|r| r == "navigation") || attrs.contains_key("hidden") {
+        return true;
+    }
+    ["class", "id"]
+        .iter()
+        .filter_map(|a| attrs.get(*a))
+        .map(|v| v.to_ascii_lowercase())
+        .any(|v| BOILERPLATE_NAMES.iter().any(|n| v.contains(n)))
+}
+
+impl Page {
+    fn parse(html: &str) -> Self {
+        let mut page = Self::default();
+        let mut stack: Vec<Element> = Vec::new();
+        let mut next_id = 0;
+        let mut text = String::new();
+        let mut tag = "body".to_owned();
+        let mut in_title = false;
+        let mut rest = html;
+        // same byte offsets as `html`, to find closing tags regardless of case
+        let lower = html.to_ascii_lowercase();
+
+        while !rest.is_empty() {
+            let Some(start) = rest.find('<') else {
+                text.push_str(rest);
+                break;
+            };
+            if in_title {
+                page.title.push_str(&rest[..start]);
+            } else {
+                text.push_str(&rest[..start]);
+            }
+            rest = &rest[start..];
+
+            if let Some(comment) = rest.strip_prefix("<!--") {
+                rest = comment.split_once("-->").map_or("", |(_, r)| r);
+                continue;
+            }
+            // a `<`, that doesn't start a tag, is text, e.g. `1 < 2`
+            let starts_tag = rest[1..]
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || matches!(c, '/' | '!' | '?'));
+            if !starts_tag {
+                if in_title {
+                    page.title.push('<');
+                } else {
+                    text.push('<');
+                }
+                rest = &rest[1..];
+                continue;
+            }
+            // an unclosed tag at the end is dropped, like browsers do
+            let Some(end) = tag_end(rest) else {
+                break;
+            };
+            let raw = &rest[1..end];
+            rest = &rest[end + 1..];
+            if raw.starts_with(['!', '?']) {
+                continue;
+            }
+
+            let closing = raw.starts_with('/');
+            let (name, attrs) = parse_tag(raw.trim_start_matches('/'));
+
+            if name == "title" {
+                in_title = !closing;
+                continue;
+            }
+            if !closing && SKIP.contains(&name.as_str()) {
+                let close = format!("</{name}");
+                let offset = html.len() - rest.len();
+                rest = match lower[offset..].find(&close) {
+                    Some(i) => rest[i..].split_once('>').map_or("", |(_, r)| r),
+                    None => "",
+                };
+                continue;
+            }
+
+            if BLOCKS.contains(&name.as_str()) {
+                page.push_block(&tag, &mut text, &stack);
+                tag = if closing { "div" } else { &name }.to_owned();
+            }
+
+            if !closing {
+                let closes = implied_end(&name);
+                let open = stack.iter().rposition(|e| {
+                    closes.contains(&e.name.as_str()) || SCOPES.contains(&e.name.as_str())
+                });
+                if let Some(i) = open.filter(|&i| closes.contains(&stack[i].name.as_str())) {
+                    stack.truncate(i);
+                }
+            }
+
+            if closing {
+                if let Some(i) = stack.iter().rposition(|e| e.name == name) {
+                    stack.truncate(i);
+                }
+            } else if !VOID.contains(&name.as_str()) && !raw.ends_with('/') {
+                let boilerplate = is_boilerplate(&name, &attrs);
+                if matches!(name.as_str(), "article" | "main")
+                    || attrs.get("role").is_some_and(|r| r == "main")
+                {
+                    page.articles.push(next_id);
+                }
+                stack.push(Element {
+                    id: next_id,
+                    name,
+                    boilerplate,
+                });
+                next_id += 1;
+            }
+        }
+        page.push_block(&tag, &mut text, &stack);
+
+        page.title = decode_entities(&page.title)
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        page
+    }
+
+    fn push_block(&mut self, tag: &str, text: &mut String, stack: &[Element]) {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        if !words.is_empty() {
+            self.blocks.push(Block {
+                tag: tag.to_owned(),
+                text: decode_entities(&words.join(" ")),
+                ancestors: stack.iter().map(|e| e.id).collect(),
+                element: stack.iter().rev().find(|e| e.name == tag).map(|e| e.id),
+                boilerplate: stack.iter().any(|e| e.boilerplate),
+            });
+        }
+        text.clear();
+    }
+
+    /// The ID of the element, that most likely contains the article.
+    fn article(&self) -> Option<usize> {
+        let text_len = |id: usize| -> usize {
+            self.blocks
+                .iter()
+                .filter(|b| !b.boilerplate && b.ancestors.contains(&id))
+                .map(|b| b.text.len())
+                .sum()
+        };
+        if let Some(id) = self.articles.iter().copied().max_by_key(|&id| text_len(id)) {
+            if text_len(id) > 0 {
+                return Some(id);
+            }
+        }
+
+        // Like Readability: paragraphs score for their parent, and half for their grandparent.
+        let mut scores: HashMap<usize, usize> = HashMap::new();
+        for block in self
+            .blocks
+            .iter()
+            .filter(|b| b.tag == "p" && !b.boilerplate && b.text.len() >= 25)
+        {
+            let score = block.text.len() + 10 * block.text.matches(',').count();
+            // the ancestors of the paragraph, not the `<p>` itself
+            let end = block
+                .element
+                .and_then(|id| block.ancestors.iter().position(|&a| a == id))
+                .unwrap_or(block.ancestors.len());
+            let mut ancestors = block.ancestors[..end].iter().rev();
+            if let Some(&parent) = ancestors.next() {
+                *scores.entry(parent).or_default() += score;
+            }
+            if let Some(&grandparent) = ancestors.next() {
+                *scores.entry(grandparent).or_default() += score / 2;
+            }
+        }
+        scores
+            .into_iter()
+            .max_by_key(|&(id, score)| (score, usize::MAX - id))
+            .map(|(id, _)| id)
+    }
+
+    /// Format the blocks as plain text, with empty lines between paragraphs.
+    fn format<'a>(&self, blocks: impl Iterator<Item = &'a Block>, title: bool) -> String {
+        let mut out = String::new();
+        let mut blocks = blocks.peekable();
+        if title && !self.title.is_empty() {
+            out.push_str(&self.title);
+            out.push_str("\n\n");
+            if blocks.peek().is_some_and(|b| b.text == self.title) {
+                blocks.next();
+            }
+        }
+        for block in blocks {
+            match block.tag.as_str() {
+                "li" | "dd" => {
+                    out.push_str("- ");
+                    out.push_str(&block.text);
+                    out.push('\n');
+                }
+                _ => {
+                    if out.ends_with("\n") && !out.ends_with("\n\n") {
+                        out.push('\n');
+                    }
+                    out.push_str(&block.text);
+                    out.push_str("\n\n");
+                }
+            }
+        }
+        out.trim_end().to_owned()
+    }
+}
+
+/// Print a web page, or with `--readability` only its article, as text.
+pub fn web(cli: &Cli, args: &WebArgs) -> Result<GrayImage> {
+    let (data, mime) = read_input(cli, &args.page)?;
+    if let Some(mime) = mime.filter(|m| m != "text/html" && m != "application/xhtml+xml") {
+        log::warn!("{}: expected HTML, but got {mime}", args.page.display());
+    }
+    let page = Page::parse(&String::from_utf8_lossy(&data));
+
+    let content = if args.readability {
+        let Some(article) = page.article() else {
+            bail!("{}: no article found", args.page.display());
+        };
+        log::debug!("article is element #{article}");
+        page.format(
+            page.blocks
+                .iter()
+                .filter(|b| !b.boilerplate && b.ancestors.contains(&article)),
+            true,
+        )
+    } else {
+        page.format(page.blocks.iter(), false)
+    };
+    if content.is_empty() {
+        bail!("{}: the page has no text", args.page.display());
+    }
+    text(&cli.font, content.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(page: &Page) -> Vec<&str> {
+        page.blocks.iter().map(|b| b.text.as_str()).collect()
+    }
+
+    #[test]
+    fn tags() {
+        let (name, attrs) = parse_tag(r#"A href="/x?a=1&b=2" title='1 > 0' data-x = y hidden"#);
+        assert_eq!(name, "a");
+        assert_eq!(attrs["href"], "/x?a=1&b=2");
+        assert_eq!(attrs["title"], "1 > 0");
+        assert_eq!(attrs["data-x"], "y");
+        assert_eq!(attrs["hidden"], "");
+
+        assert_eq!(tag_end(r#"<p title="a > b" class='c>d'>x"#), Some(28));
+        assert_eq!(tag_end("<p title=a>b>"), Some(10));
+        assert_eq!(tag_end(r#"<p it's>"#), Some(7));
+        assert_eq!(tag_end(r#"<p title="unclosed>"#), None);
+    }
+
+    #[test]
+    fn entities() {
+        assert_eq!(
+            decode_entities("&amp;&lt;b&gt; &#8217;&#x41;&euro;"),
+            "&<b> ’A€"
+        );
+        assert_eq!(decode_entities("AT&T &unknown; &;"), "AT&T &unknown; &;");
+        assert_eq!(decode_entities("&#xffffffff; &"), "&#xffffffff; &");
+    }
+
+    #[test]
+    fn text_and_markup() {
+        let page = Page::parse(
+            r#"<html><head><title>A &amp; B</title><style>p > b { x: "</p>" }</style></head>
+<body><p title="a > b">1 < 2 and 3 > 2</p><!-- <p>comment</p> -->
+<SCRIPT>if (a < b) document.write("<p>x</p>")</script>
+<ul><li>one<li>two</ul><p>end <b unclosed"#,
+        );
+        assert_eq!(page.title, "A & B");
+        assert_eq!(texts(&page), ["1 < 2 and 3 > 2", "one", "two", "end"]);
+    }
+
+    #[test]
+    fn unclosed_elements() {
+        let page = Page::parse("<div id=a><p>one<p>two<ul><li>three<li>four</ul></div>");
+        let ancestors: Vec<_> = page.blocks.iter().map(|b| b.ancestors.len()).collect();
+        // the paragraphs and list items close each other, and aren't nested
+        assert_eq!(ancestors, [2, 2, 3, 3]);
+    }
+
+    #[test]
+    fn readability() {
+        let page = Page::parse(
+            r#"<body>
+<nav><p>Home, News, Sports, Weather, and everything else</p></nav>
+<div class="content">
+  <h1>Title</h1>
+  <p>The first paragraph of the story, which is long enough to count.
+  <p>The second paragraph, with a comma, another comma, and more text.
+  <div class="comments"><p>I disagree with this, completely, and totally.</p></div>
+</div>
+<footer><p>Copyright, all rights reserved, and so on and so forth.</p></footer>"#,
+        );
+        let article = page.article().unwrap();
+        let text = page.format(
+            page.blocks
+                .iter()
+                .filter(|b| !b.boilerplate && b.ancestors.contains(&article)),
+            false,
+        );
+        assert_eq!(
+            text,
+            "Title\n\nThe first paragraph of the story, which is long enough to count.\n\n\
+             The second paragraph, with a comma, another comma, and more text."
+        );
+    }
+
+    #[test]
+    fn article_element() {
+        let page = Page::parse(
+            "<main><p>short</p></main><div><p>A long paragraph outside of the main element.</p></div>",
+        );
+        let article = page.article().unwrap();
+        assert_eq!(page.articles, [article]);
+    }
+}
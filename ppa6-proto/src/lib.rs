@@ -0,0 +1,354 @@
+//! Raw commands of the protocol of the Peripage A6 thermal printers.
+//!
+//! Most of them were found by sniffing the official Windows driver,
+//! the names describe what they seem to do.
+//! A [`CommandBatch`] combines several [`Command`]s into one transfer, like the Windows driver does.
+//!
+//! This crate is `no_std` and only needs `alloc`, so firmware and embedded gateways can build packets
+//! without the USB and Bluetooth stacks of the `ppa6` crate, which re-exports it as `ppa6::proto`.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Reset the printer, see `Printer::reset()`.
+pub const RESET: [u8; 16] = [
+    0x10, 0xff, 0xfe, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// ESC/POS initialize printer (`ESC @`), the Windows driver sends it after [`RESET`].
+pub const INIT: [u8; 2] = [0x1b, 0x40];
+
+/// Sent by the Windows driver after every image, nobody knows what it does.
+pub const END_OF_IMAGE: [u8; 4] = [0x10, 0xff, 0xfe, 0x45];
+
+/// Followed by one byte, see `Printer::set_concentration()`.
+pub const SET_CONCENTRATION: [u8; 4] = [0x10, 0xff, 0x10, 0x00];
+
+/// See `Printer::get_ip()`.
+pub const GET_IP: [u8; 4] = [0x10, 0xff, 0x20, 0xf0];
+
+/// See `Printer::get_firmware_ver()`.
+pub const GET_FIRMWARE_VER: [u8; 4] = [0x10, 0xff, 0x20, 0xf1];
+
+/// See `Printer::get_serial()`.
+pub const GET_SERIAL: [u8; 4] = [0x10, 0xff, 0x20, 0xf2];
+
+/// See `Printer::get_hardware_ver()`.
+pub const GET_HARDWARE_VER: [u8; 4] = [0x10, 0xff, 0x30, 0x10];
+
+/// See `Printer::get_name()`.
+pub const GET_NAME: [u8; 4] = [0x10, 0xff, 0x30, 0x11];
+
+/// See `Printer::get_mac()`.
+pub const GET_MAC: [u8; 4] = [0x10, 0xff, 0x30, 0x12];
+
+/// See `Printer::get_battery_status()`.
+pub const GET_BATTERY: [u8; 4] = [0x10, 0xff, 0x50, 0xf1];
+
+/// ESC/POS real-time status request (`DLE EOT`), followed by one byte,
+/// see `Printer::get_status()`.
+/// This is standard ESC/POS, it is not verified, that PeriPage printers know it.
+pub const GET_STATUS: [u8; 2] = [0x10, 0x04];
+
+/// ESC/POS raster image (`GS v 0`), followed by the mode (0) and the width in bytes and the height (16-bit each),
+/// then the pixels.
+/// See `Printer::print_image()`.
+pub const PRINT_IMAGE: [u8; 3] = [0x1d, 0x76, 0x30];
+
+/// ESC/POS feed paper (`ESC J`), followed by the number of rows,
+/// see `Printer::push()`.
+pub const PUSH: [u8; 2] = [0x1b, 0x4a];
+
+/// Result type of this crate.
+pub type Result<T, E = EncodeError> = core::result::Result<T, E>;
+
+/// Why a [`Command`] can't be encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EncodeError {
+    /// The width of an image is zero or not divisible by 8.
+    InvalidWidth(u16),
+
+    /// The pixels of an image aren't a whole number of rows, this is their length.
+    InvalidLength(usize),
+
+    /// An image has more rows, than fit into one command.
+    TooManyRows {
+        /// Number of rows in the image.
+        rows: usize,
+
+        /// Maximum number of rows.
+        max: usize,
+    },
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidWidth(_) => write!(f, "width must be non-zero and divisible by 8"),
+            Self::InvalidLength(len) => write!(f, "invalid length of pixels: {len}"),
+            Self::TooManyRows { rows, max } => {
+                write!(
+                    f,
+                    "document too long: {rows} rows, at most {max} are possible"
+                )
+            }
+        }
+    }
+}
+
+impl core::error::Error for EncodeError {}
+
+/// A single command, see [`CommandBatch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Command<'a> {
+    /// See [`RESET`].
+    Reset,
+
+    /// See [`INIT`].
+    Init,
+
+    /// See [`SET_CONCENTRATION`].
+    SetConcentration(u8),
+
+    /// Feed this many rows, see [`PUSH`].
+    Push(u8),
+
+    /// Print packed pixels, see `Printer::print_image()`.
+    Image {
+        /// Width in pixels.
+        width: u16,
+
+        /// Packed pixels, at most 65535 rows.
+        pixels: &'a [u8],
+    },
+
+    /// Only the header of [`Command::Image`], the pixels have to be sent after it.
+    ImageHeader {
+        /// Width in pixels.
+        width: u16,
+
+        /// Number of rows, that will follow.
+        height: u16,
+    },
+
+    /// See [`END_OF_IMAGE`].
+    EndOfImage,
+
+    /// ASCII text, see `Printer::print_text()`.
+    Text(&'a str),
+
+    /// Anything else.
+    Raw(&'a [u8]),
+}
+
+impl Command<'_> {
+    /// Append the encoded command to `out`.
+    pub fn encode(&self, out: &mut Vec<u8>) -> Result<()> {
+        match *self {
+            Self::Reset => out.extend_from_slice(&RESET),
+            Self::Init => out.extend_from_slice(&INIT),
+            Self::SetConcentration(c) => {
+                out.extend_from_slice(&SET_CONCENTRATION);
+                out.push(c);
+            }
+            Self::Push(n) => {
+                out.extend_from_slice(&PUSH);
+                out.push(n);
+            }
+            Self::Image { width, pixels } => {
+                check_width(width)?;
+                let rs = width as usize / 8;
+                let h = pixels.len() / rs;
+                if h > 0xffff {
+                    return Err(EncodeError::TooManyRows {
+                        rows: h,
+                        max: 0xffff,
+                    });
+                }
+                if pixels.len() != rs * h {
+                    return Err(EncodeError::InvalidLength(pixels.len()));
+                }
+
+                Self::ImageHeader {
+                    width,
+                    height: h as u16,
+                }
+                .encode(out)?;
+                out.extend_from_slice(pixels);
+            }
+            Self::ImageHeader { width, height } => {
+                check_width(width)?;
+                let [xl, xh] = (width / 8).to_le_bytes();
+                let [yl, yh] = height.to_le_bytes();
+                out.extend_from_slice(&PRINT_IMAGE);
+                out.extend_from_slice(&[0x00, xl, xh, yl, yh]);
+            }
+            Self::EndOfImage => out.extend_from_slice(&END_OF_IMAGE),
+            Self::Text(text) => out.extend(
+                text.chars()
+                    .filter(|ch| matches!(ch, '\n' | '\x20'..='\x7f'))
+                    .map(|ch| ch as u8),
+            ),
+            Self::Raw(buf) => out.extend_from_slice(buf),
+        }
+        Ok(())
+    }
+}
+
+fn check_width(width: u16) -> Result<()> {
+    if width == 0 || !width.is_multiple_of(8) {
+        return Err(EncodeError::InvalidWidth(width));
+    }
+    Ok(())
+}
+
+/// Several [`Command`]s, which are sent in a single transfer,
+/// see `Printer::send_batch()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandBatch {
+    buf: Vec<u8>,
+}
+
+impl CommandBatch {
+    /// Create an empty batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append `cmd` to the batch.
+    pub fn push(&mut self, cmd: Command) -> Result<&mut Self> {
+        cmd.encode(&mut self.buf)?;
+        Ok(self)
+    }
+
+    /// The encoded commands.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Length of the encoded commands, in bytes.
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Whether the batch has no commands.
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn encode(cmd: Command) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        cmd.encode(&mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn simple_commands() {
+        assert_eq!(encode(Command::Reset).unwrap(), RESET);
+        assert_eq!(encode(Command::Init).unwrap(), INIT);
+        assert_eq!(encode(Command::EndOfImage).unwrap(), END_OF_IMAGE);
+        assert_eq!(
+            encode(Command::SetConcentration(2)).unwrap(),
+            [0x10, 0xff, 0x10, 0x00, 0x02]
+        );
+        assert_eq!(encode(Command::Push(0x60)).unwrap(), [0x1b, 0x4a, 0x60]);
+        assert_eq!(encode(Command::Raw(&[1, 2, 3])).unwrap(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn text_drops_unprintable_characters() {
+        assert_eq!(
+            encode(Command::Text("a\tb\nä€c")).unwrap(),
+            b"ab\nc".to_vec()
+        );
+    }
+
+    #[test]
+    fn image() {
+        let pixels = [0xaa; 48 * 3];
+        let out = encode(Command::Image {
+            width: 384,
+            pixels: &pixels,
+        })
+        .unwrap();
+        // GS v 0, mode 0, 48 bytes per row, 3 rows, both little endian
+        assert_eq!(out[..8], [0x1d, 0x76, 0x30, 0x00, 48, 0, 3, 0]);
+        assert_eq!(out[8..], pixels);
+
+        let header = encode(Command::ImageHeader {
+            width: 576,
+            height: 0x0102,
+        })
+        .unwrap();
+        assert_eq!(header, [0x1d, 0x76, 0x30, 0x00, 72, 0, 0x02, 0x01]);
+    }
+
+    #[test]
+    fn invalid_images() {
+        for width in [0, 383] {
+            assert_eq!(
+                encode(Command::Image {
+                    width,
+                    pixels: &[0; 48],
+                }),
+                Err(EncodeError::InvalidWidth(width))
+            );
+        }
+        assert_eq!(
+            encode(Command::Image {
+                width: 384,
+                pixels: &[0; 50],
+            }),
+            Err(EncodeError::InvalidLength(50))
+        );
+        assert_eq!(
+            encode(Command::Image {
+                width: 8,
+                pixels: &vec![0; 0x10000],
+            }),
+            Err(EncodeError::TooManyRows {
+                rows: 0x10000,
+                max: 0xffff
+            })
+        );
+    }
+
+    #[test]
+    fn batch() {
+        let mut batch = CommandBatch::new();
+        assert!(batch.is_empty());
+        batch
+            .push(Command::Reset)
+            .unwrap()
+            .push(Command::Init)
+            .unwrap()
+            .push(Command::Push(1))
+            .unwrap();
+        assert_eq!(
+            batch.as_bytes(),
+            [&RESET[..], &INIT, &[0x1b, 0x4a, 1]].concat()
+        );
+        assert_eq!(batch.len(), RESET.len() + 5);
+
+        // a failed command leaves the batch as it was
+        assert!(batch
+            .push(Command::ImageHeader {
+                width: 3,
+                height: 1
+            })
+            .is_err());
+        assert_eq!(batch.len(), RESET.len() + 5);
+    }
+}
@@ -0,0 +1,20 @@
+use ppa6::AsyncPrinter;
+
+fn main() {
+    let rt = tokio::runtime::Runtime::new().expect("cannot start runtime");
+    rt.block_on(async {
+        // the printer can be moved to another task, while the bulk transfer doesn't block the runtime
+        let task = tokio::spawn(async {
+            let mut printer = AsyncPrinter::find().await.expect("no printer found");
+            printer.reset().await.expect("failed to reset printer");
+            let pixels = vec![0xffu8; 384 * 384 / 8];
+            printer
+                .print_image_chunked(&pixels, 384)
+                .await
+                .expect("failed to print black image");
+            printer.push(0x60).await.expect("failed to push paper");
+        });
+
+        task.await.expect("printing task panicked");
+    });
+}
@@ -0,0 +1,29 @@
+#[cfg(target_os = "linux")]
+fn main() {
+    use ppa6::{BtBackend, MacAddr, Printer};
+
+    // the address of a paired printer, e.g. from `bluetoothctl devices Paired`,
+    // otherwise look it up, which requires root
+    let addr: MacAddr = match std::env::args().nth(1) {
+        Some(addr) => addr.parse().expect("invalid MAC address"),
+        None => {
+            let devices = BtBackend::list()
+                .expect("failed to list paired printers, pass the address instead");
+            devices.first().expect("no paired printer found").addr
+        }
+    };
+
+    let backend = BtBackend::open(addr).expect("failed to connect to printer");
+    let mut printer = Printer::new(backend);
+    printer.reset().expect("failed to reset printer");
+    let name = printer.get_name().expect("failed to get name");
+    printer
+        .print_text(&format!("Hello from {name} over Bluetooth\n"))
+        .expect("failed to print text");
+    printer.push(0x40).expect("failed to push paper");
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {
+    eprintln!("the bluetooth backend is only available on Linux");
+}
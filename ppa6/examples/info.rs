@@ -8,6 +8,6 @@ fn main() {
     println!("Serial:        {}", printer.get_serial().unwrap());
     println!("Firmware Ver.: {}", printer.get_firmware_ver().unwrap());
     println!("Hardware Ver.: {}", printer.get_hardware_ver().unwrap());
-    println!("Battery Level: {}", printer.get_battery().unwrap());
+    println!("Battery:       {}", printer.get_battery_status().unwrap());
     println!("MAC address:   {}", printer.get_mac().unwrap());
 }
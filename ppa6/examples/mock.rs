@@ -0,0 +1,39 @@
+use std::time::Duration;
+
+use ppa6::{proto, Document, MockBackend, PrintOptions, PrinterBuilder};
+
+fn main() {
+    // script the conversation, the mock fails on anything else
+    let mock = MockBackend::new();
+    mock.expect_send(proto::GET_NAME).respond(*b"PeriPage_A6");
+    mock.expect_send(proto::RESET)
+        .expect_send_any()
+        .expect_send(proto::END_OF_IMAGE)
+        .expect_send([0x1b, 0x4a, 30]);
+
+    // clones share their state, so keep one to check what was sent
+    let mut printer = PrinterBuilder::new()
+        .chunk_delay(Duration::ZERO)
+        .build(mock.clone());
+
+    let name = printer.get_name().expect("failed to get name");
+    println!("talking to {name}");
+
+    let doc = Document::new(vec![0xff; 10 * 48], 384).expect("invalid document");
+    let opts = PrintOptions {
+        feed: 30,
+        ..PrintOptions::default()
+    };
+    printer
+        .print(&doc, &opts)
+        .expect("failed to print document");
+
+    mock.assert_done();
+    for packet in mock.sent() {
+        println!(
+            "sent {} bytes: {:02x?}",
+            packet.len(),
+            &packet[..packet.len().min(8)]
+        );
+    }
+}
@@ -8,7 +8,7 @@ fn main() {
     pixels
         .iter_mut()
         .enumerate()
-        .filter(|(i, _)| (i % 2 == 0))
+        .filter(|(i, _)| i % 2 == 0)
         .for_each(|(_, p)| *p = 0xff);
 
     printer
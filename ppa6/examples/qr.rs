@@ -0,0 +1,20 @@
+use ppa6::{
+    qr::{self, QrPayload, Wifi},
+    Printer,
+};
+
+fn main() {
+    let payload = QrPayload::Wifi(Wifi {
+        ssid: "Guests".into(),
+        password: Some("correct horse battery staple".into()),
+        ..Default::default()
+    });
+    let doc = qr::render_payload(&payload, 384).expect("failed to render QR code");
+
+    let mut printer = Printer::find().expect("no printer found");
+    printer.reset().expect("failed to reset printer");
+    printer
+        .print_document(&doc)
+        .expect("failed to print QR code");
+    printer.push(0x60).expect("failed to push paper");
+}
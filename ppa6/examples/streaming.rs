@@ -0,0 +1,22 @@
+use ppa6::{PrintOptions, Printer};
+
+/// Rows of a sine wave, generated one at a time, so the image is never in memory as a whole.
+fn wave(rows: usize) -> impl Iterator<Item = [u8; 48]> + Send {
+    (0..rows).map(|y| {
+        let x = 192.0 + 160.0 * (y as f32 / 40.0).sin();
+        let mut row = [0u8; 48];
+        for x in x as usize - 2..x as usize + 2 {
+            row[x / 8] |= 0x80 >> (x % 8);
+        }
+        row
+    })
+}
+
+fn main() {
+    let mut printer = Printer::find().expect("no printer found");
+    printer.reset().expect("failed to reset printer");
+    printer
+        .print_rows(wave(2000), &PrintOptions::default())
+        .expect("failed to print rows");
+    printer.push(0x60).expect("failed to push paper");
+}
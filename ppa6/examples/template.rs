@@ -0,0 +1,37 @@
+use ppa6::{qr, Document, Printer};
+
+const WIDTH: u16 = 384;
+
+/// A shipping label: a framed header with an address line, followed by a QR code of the tracking URL.
+fn label(tracking: &str) -> Document {
+    let mut header = Document::blank(WIDTH, 120).expect("invalid document");
+    header.draw(0.5, |c| {
+        let (w, h) = (WIDTH as f32, 120.0);
+        c.stroke_polygon(
+            &[
+                (4.0, 4.0),
+                (w - 4.0, 4.0),
+                (w - 4.0, h - 4.0),
+                (4.0, h - 4.0),
+            ],
+            3.0,
+        );
+        c.fill_rect((20.0, 40.0), (w - 20.0, 52.0));
+        c.line((20.0, 80.0), (w / 2.0, 80.0), 2.0);
+        c.line((20.0, 96.0), (w / 3.0, 96.0), 2.0);
+    });
+    header.stamp(tracking);
+
+    let code = qr::render(format!("https://example.com/track/{tracking}"), WIDTH)
+        .expect("failed to render QR code");
+    Document::new([header.pixels(), code.pixels()].concat(), WIDTH).expect("invalid document")
+}
+
+fn main() {
+    let mut printer = Printer::find().expect("no printer found");
+    printer.reset().expect("failed to reset printer");
+    printer
+        .print_document(&label("PP6-000042"))
+        .expect("failed to print label");
+    printer.push(0x60).expect("failed to push paper");
+}
@@ -0,0 +1,1058 @@
+use std::{
+    future::Future,
+    pin::{pin, Pin},
+    sync::Arc,
+    task::{Context as TaskContext, Poll, Wake, Waker},
+    thread::{self, Thread},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    builder::{Config, EndOfImage, Framing, ThermalPolicy},
+    dump,
+    proto::{self, Command, CommandBatch},
+    Backend, Backoff, Band, BatteryStatus, Capabilities, Concentration, Document, Error,
+    FirmwareVersion, MacAddr, Model, PrintOptions, PrinterStatus, Progress, Quirks, Result,
+    TransportStats,
+};
+
+/// Printers, that don't know the status requests, don't answer at all.
+const STATUS_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// How long to wait for the rest of a late reply, see [`AsyncPrinter::discard_stale()`].
+const STALE_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// How long to look for a printer, that was disconnected during a job,
+/// see [`PrintOptions::resume_on_reconnect`].
+const RECONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Distance between the print head and the tear bar.
+const TEAR_OFF_MM: f32 = 12.0;
+
+/// Opens the printer again, see [`AsyncPrinter::set_reconnect()`].
+type Reconnect = Box<dyn FnMut() -> BoxFuture<'static, Result<Box<dyn AsyncBackend>>> + Send>;
+
+/// A boxed future, as returned by [`AsyncBackend`] methods.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Async variant of [`Backend`], see [`AsyncPrinter`].
+pub trait AsyncBackend: Send {
+    /// Send data to the printer.
+    fn send<'a>(&'a mut self, buf: &'a [u8], timeout: Duration) -> BoxFuture<'a, Result<()>>;
+
+    /// Receive at most `buf.len()` bytes of data from the printer.
+    ///
+    /// # Return value
+    /// This functions the number of bytes received from the printer.
+    fn recv<'a>(&'a mut self, buf: &'a mut [u8], timeout: Duration)
+        -> BoxFuture<'a, Result<usize>>;
+
+    /// Wait for `duration` without talking to the printer, e.g. using `tokio::time::sleep()`.
+    fn sleep(&mut self, duration: Duration) -> BoxFuture<'_, ()>;
+
+    /// Hand the device back to the operating system, after which the backend can't be used anymore.
+    fn release(&mut self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Statistics about the transfers done by this backend, if it keeps any.
+    fn stats(&self) -> Option<TransportStats> {
+        None
+    }
+
+    /// The printer model, if the backend can tell it from the device, e.g. by USB descriptors.
+    fn model(&self) -> Option<Model> {
+        None
+    }
+}
+
+/// Runs a [`Backend`] on the current thread, this is what [`Printer`](crate::Printer) uses.
+pub(crate) struct Blocking<B>(pub B);
+
+impl<B: Backend + Send> AsyncBackend for Blocking<B> {
+    fn send<'a>(&'a mut self, buf: &'a [u8], timeout: Duration) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move { self.0.send(buf, timeout) })
+    }
+
+    fn recv<'a>(
+        &'a mut self,
+        buf: &'a mut [u8],
+        timeout: Duration,
+    ) -> BoxFuture<'a, Result<usize>> {
+        Box::pin(async move { self.0.recv(buf, timeout) })
+    }
+
+    fn sleep(&mut self, duration: Duration) -> BoxFuture<'_, ()> {
+        Box::pin(async move { thread::sleep(duration) })
+    }
+
+    fn release(&mut self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move { self.0.release() })
+    }
+
+    fn stats(&self) -> Option<TransportStats> {
+        self.0.stats()
+    }
+
+    fn model(&self) -> Option<Model> {
+        self.0.model()
+    }
+}
+
+/// Stands in for a backend, that was dropped, e.g. while reconnecting, every transfer fails.
+struct Dropped;
+
+impl AsyncBackend for Dropped {
+    fn send<'a>(&'a mut self, _buf: &'a [u8], _timeout: Duration) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async { Err(Error::Disconnected) })
+    }
+
+    fn recv<'a>(
+        &'a mut self,
+        _buf: &'a mut [u8],
+        _timeout: Duration,
+    ) -> BoxFuture<'a, Result<usize>> {
+        Box::pin(async { Err(Error::Disconnected) })
+    }
+
+    fn sleep(&mut self, duration: Duration) -> BoxFuture<'_, ()> {
+        #[cfg(feature = "tokio")]
+        if tokio::runtime::Handle::try_current().is_ok() {
+            return Box::pin(tokio::time::sleep(duration));
+        }
+        Box::pin(async move { thread::sleep(duration) })
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Drive `fut` to completion on the current thread.
+pub(crate) fn block_on<F: Future>(fut: F) -> F::Output {
+    let mut fut = pin!(fut);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = TaskContext::from_waker(&waker);
+    loop {
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(x) => return x,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+/// Runs a [`Backend`] on tokio's blocking thread pool.
+///
+/// If a transfer gets cancelled, e.g. by a timeout around it, the backend is lost
+/// and every further call fails.
+#[cfg(feature = "tokio")]
+struct Threaded<B>(Option<B>);
+
+#[cfg(feature = "tokio")]
+impl<B: Backend + Send + 'static> Threaded<B> {
+    async fn run<T: Send + 'static>(
+        &mut self,
+        f: impl FnOnce(&mut B) -> T + Send + 'static,
+    ) -> Result<T> {
+        let Some(mut backend) = self.0.take() else {
+            return Err(Error::BackendLost);
+        };
+        let (backend, x) = tokio::task::spawn_blocking(move || {
+            let x = f(&mut backend);
+            (backend, x)
+        })
+        .await?;
+        self.0 = Some(backend);
+        Ok(x)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<B: Backend + Send + 'static> AsyncBackend for Threaded<B> {
+    fn send<'a>(&'a mut self, buf: &'a [u8], timeout: Duration) -> BoxFuture<'a, Result<()>> {
+        let buf = buf.to_vec();
+        Box::pin(async move { self.run(move |b| b.send(&buf, timeout)).await? })
+    }
+
+    fn recv<'a>(
+        &'a mut self,
+        buf: &'a mut [u8],
+        timeout: Duration,
+    ) -> BoxFuture<'a, Result<usize>> {
+        let mut tmp = vec![0u8; buf.len()];
+        Box::pin(async move {
+            let (tmp, n) = self
+                .run(move |b| b.recv(&mut tmp, timeout).map(|n| (tmp, n)))
+                .await??;
+            buf[..n].copy_from_slice(&tmp[..n]);
+            Ok(n)
+        })
+    }
+
+    fn sleep(&mut self, duration: Duration) -> BoxFuture<'_, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+
+    fn release(&mut self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move { self.run(|b| b.release()).await? })
+    }
+
+    fn stats(&self) -> Option<TransportStats> {
+        self.0.as_ref().and_then(|b| b.stats())
+    }
+
+    fn model(&self) -> Option<Model> {
+        self.0.as_ref().and_then(|b| b.model())
+    }
+}
+
+/// Async variant of [`Printer`](crate::Printer), which implements the printer's protocol.
+/// [`Printer`](crate::Printer) is a blocking wrapper around this.
+pub struct AsyncPrinter {
+    backend: Box<dyn AsyncBackend>,
+    config: Config,
+    check_status: bool,
+    model: Option<Model>,
+    quirks: Option<Quirks>,
+    concentration: Option<Concentration>,
+    reconnect: Option<Reconnect>,
+    /// The query, whose reply timed out, and may still arrive, see [`AsyncPrinter::discard_stale()`].
+    stale: Option<&'static [u8]>,
+}
+
+impl AsyncPrinter {
+    /// Construct a new printer using `backend` as it's printing [`AsyncBackend`].
+    /// Use [`PrinterBuilder`](crate::PrinterBuilder) for non-default timeouts.
+    pub fn new(backend: impl AsyncBackend + 'static) -> Self {
+        Self::with_config(backend, Config::default())
+    }
+
+    pub(crate) fn with_config(backend: impl AsyncBackend + 'static, config: Config) -> Self {
+        Self {
+            backend: Box::new(backend),
+            config,
+            check_status: false,
+            model: None,
+            quirks: None,
+            concentration: None,
+            reconnect: None,
+            stale: None,
+        }
+    }
+
+    /// Construct a new printer using a blocking `backend`, which is run on tokio's blocking thread pool.
+    #[cfg(feature = "tokio")]
+    pub fn from_blocking(backend: impl Backend + Send + 'static) -> Self {
+        Self::new(Threaded(Some(backend)))
+    }
+
+    /// See [`Printer::find()`](crate::Printer::find()).
+    #[cfg(feature = "tokio")]
+    pub async fn find() -> Result<Self> {
+        let backend = tokio::task::spawn_blocking(|| crate::backend::find_backend(None)).await??;
+        let mut printer = Self::from_blocking(backend);
+        printer.set_reconnect(|| {
+            Box::pin(async {
+                let backend =
+                    tokio::task::spawn_blocking(|| crate::backend::find_backend(None)).await??;
+                Ok(Box::new(Threaded(Some(backend))) as Box<dyn AsyncBackend>)
+            })
+        });
+        Ok(printer)
+    }
+
+    /// Use `reconnect` to open the printer again, if it was disconnected during a job,
+    /// see [`PrintOptions::resume_on_reconnect`].
+    /// `AsyncPrinter::find()` sets this to search for any printer again.
+    pub fn set_reconnect(
+        &mut self,
+        reconnect: impl FnMut() -> BoxFuture<'static, Result<Box<dyn AsyncBackend>>> + Send + 'static,
+    ) {
+        self.reconnect = Some(Box::new(reconnect));
+    }
+
+    /// Open the printer again, reset it, and restore the concentration,
+    /// polling for at most [`RECONNECT_TIMEOUT`].
+    async fn reconnect(&mut self) -> Result<()> {
+        let Some(mut reconnect) = self.reconnect.take() else {
+            return Err(Error::Disconnected);
+        };
+
+        // the old backend may still hold the DeviceLock, which the new one has to take
+        self.backend = Box::new(Dropped);
+
+        let start = Instant::now();
+        let mut result = Err(Error::Disconnected);
+        for delay in Backoff::default().delays() {
+            match reconnect().await {
+                Ok(backend) => {
+                    self.backend = backend;
+                    result = self.reset().await;
+                    if let (Ok(()), Some(c)) = (&result, self.concentration) {
+                        result = self.set_concentration(c).await;
+                    }
+                    break;
+                }
+                Err(e) if start.elapsed() + delay > RECONNECT_TIMEOUT => {
+                    result = Err(e);
+                    break;
+                }
+                Err(e) => log::debug!("{e}, retrying in {delay:.2?}"),
+            }
+            self.backend.sleep(delay).await;
+        }
+        self.reconnect = Some(reconnect);
+        result
+    }
+
+    /// See [`Printer::find_exclusive()`](crate::Printer::find_exclusive()).
+    #[cfg(feature = "tokio")]
+    pub async fn find_exclusive(wait: Duration) -> Result<Self> {
+        let backend =
+            tokio::task::spawn_blocking(move || crate::backend::find_backend(Some(wait))).await??;
+        let mut printer = Self::from_blocking(backend);
+        printer.set_reconnect(move || {
+            Box::pin(async move {
+                let backend =
+                    tokio::task::spawn_blocking(move || crate::backend::find_backend(Some(wait)))
+                        .await??;
+                Ok(Box::new(Threaded(Some(backend))) as Box<dyn AsyncBackend>)
+            })
+        });
+        Ok(printer)
+    }
+
+    /// Send a short command, that may be repeated, e.g. a query or [`proto::RESET`].
+    /// It is retried after transient errors.
+    async fn send(&mut self, buf: &[u8], timeout: Duration) -> Result<()> {
+        dump::sent(buf, timeout);
+        self.transfer(buf, timeout, true).await
+    }
+    /// Send a command, that must not be repeated, e.g. text, images, or feeding paper.
+    /// A failed send may have been written in part, sending it again would print the rest as garbage,
+    /// so it isn't retried.
+    async fn send_once(&mut self, buf: &[u8], timeout: Duration) -> Result<()> {
+        dump::sent(buf, timeout);
+        self.transfer(buf, timeout, false).await
+    }
+    /// Send pixels following a [`Command::ImageHeader`], like [`AsyncPrinter::send_once()`].
+    async fn send_pixels(&mut self, buf: &[u8], timeout: Duration) -> Result<()> {
+        dump::pixels(buf, timeout);
+        self.transfer(buf, timeout, false).await
+    }
+    async fn transfer(&mut self, buf: &[u8], timeout: Duration, retry: bool) -> Result<()> {
+        let mut attempt = 0;
+        loop {
+            match self.backend.send(buf, timeout).await {
+                Err(e) if retry && e.is_transient() && attempt < self.config.retries => {
+                    let left = self.config.retries - attempt - 1;
+                    log::warn!("send failed: {e}, retrying ({left} retries left)");
+                    self.backend
+                        .sleep(self.config.retry_backoff.delay(attempt))
+                        .await;
+                    attempt += 1;
+                }
+                r => return r,
+            }
+        }
+    }
+    /// Receive the reply to `query`.
+    async fn recv(&mut self, query: &[u8], buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        let n = self.backend.recv(buf, timeout).await?;
+        dump::received(query, &buf[0..n], timeout);
+        Ok(n)
+    }
+    /// Read and drop a reply, that arrived after its request timed out,
+    /// so it isn't taken as the answer to the next request.
+    async fn discard_stale(&mut self) {
+        let Some(query) = self.stale.take() else {
+            return;
+        };
+        let mut buf = [0u8; 128];
+        // bounded, in case a backend keeps returning data
+        for _ in 0..16 {
+            match self.backend.recv(&mut buf, STALE_TIMEOUT).await {
+                Ok(n) if n > 0 => {
+                    log::debug!("discarding {n} bytes of a late reply");
+                    dump::received(query, &buf[..n], STALE_TIMEOUT);
+                }
+                _ => break,
+            }
+        }
+    }
+    async fn query(&mut self, cmd: &'static [u8]) -> Result<Vec<u8>> {
+        let timeout = self.config.timeouts.query;
+        let mut attempt = 0;
+        loop {
+            self.discard_stale().await;
+            self.send(cmd, timeout).await?;
+            let mut buf = vec![0u8; 1024];
+            match self.recv(cmd, &mut buf, timeout).await {
+                Ok(n) => {
+                    buf.truncate(n);
+                    return Ok(buf);
+                }
+                Err(e) if e.is_transient() && attempt < self.config.retries => {
+                    self.stale = Some(cmd);
+                    let left = self.config.retries - attempt - 1;
+                    log::warn!("query failed: {e}, retrying ({left} retries left)");
+                    self.backend
+                        .sleep(self.config.retry_backoff.delay(attempt))
+                        .await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    self.stale = e.is_transient().then_some(cmd);
+                    return Err(e);
+                }
+            }
+        }
+    }
+    async fn query_string(&mut self, cmd: &'static [u8]) -> Result<String> {
+        let buf = self.query(cmd).await?;
+        let s = String::from_utf8_lossy(&buf);
+        Ok(s.into_owned())
+    }
+
+    /// See [`Printer::release()`](crate::Printer::release()).
+    pub async fn release(mut self) -> Result<()> {
+        self.backend.release().await
+    }
+
+    /// See [`Printer::transport_stats()`](crate::Printer::transport_stats()).
+    pub fn transport_stats(&self) -> Option<TransportStats> {
+        self.backend.stats()
+    }
+
+    /// See [`Printer::detect_model()`](crate::Printer::detect_model()).
+    pub async fn detect_model(&mut self) -> Result<Model> {
+        if let Some(model) = self.model {
+            return Ok(model);
+        }
+
+        let model = match self.backend.model() {
+            Some(model) => model,
+            None => {
+                let name = self.get_name().await?;
+                Model::from_name(&name).unwrap_or_else(|| {
+                    log::warn!("unknown printer {name:?}, assuming it's a {}", Model::A6);
+                    Model::A6
+                })
+            }
+        };
+        log::debug!("model: {model}");
+        self.model = Some(model);
+        Ok(model)
+    }
+
+    /// See [`Printer::model()`](crate::Printer::model()).
+    pub fn model(&self) -> Option<Model> {
+        self.model
+    }
+
+    /// See [`Printer::capabilities()`](crate::Printer::capabilities()).
+    pub fn capabilities(&self) -> Capabilities {
+        self.model.unwrap_or_default().capabilities()
+    }
+
+    fn check_width(&self, width: u16) -> Result<()> {
+        let max = self.capabilities().width;
+        if width > max {
+            return Err(Error::InvalidArgument(format!(
+                "image is {width}px wide, but the {} prints at most {max}px",
+                self.model.unwrap_or_default()
+            )));
+        }
+        Ok(())
+    }
+
+    /// See [`Printer::get_ip()`](crate::Printer::get_ip()).
+    pub async fn get_ip(&mut self) -> Result<String> {
+        self.query_string(&proto::GET_IP).await
+    }
+
+    /// See [`Printer::get_firmware_ver()`](crate::Printer::get_firmware_ver()).
+    pub async fn get_firmware_ver(&mut self) -> Result<String> {
+        self.query_string(&proto::GET_FIRMWARE_VER).await
+    }
+
+    /// See [`Printer::get_firmware_version()`](crate::Printer::get_firmware_version()).
+    pub async fn get_firmware_version(&mut self) -> Result<FirmwareVersion> {
+        self.get_firmware_ver()
+            .await?
+            .trim_end_matches('\0')
+            .parse()
+    }
+
+    /// See [`Printer::detect_quirks()`](crate::Printer::detect_quirks()).
+    pub async fn detect_quirks(&mut self) -> Result<Quirks> {
+        if let Some(quirks) = self.quirks {
+            return Ok(quirks);
+        }
+
+        let model = self.detect_model().await?;
+        let quirks = match self.get_firmware_version().await {
+            Ok(version) => Quirks::for_firmware(model, version),
+            Err(e @ Error::InvalidArgument(_)) => {
+                log::warn!("{e}, assuming no quirks");
+                Quirks::default()
+            }
+            Err(e) => return Err(e),
+        };
+        log::debug!("quirks: {quirks:?}");
+        self.quirks = Some(quirks);
+        Ok(quirks)
+    }
+
+    /// See [`Printer::set_quirks()`](crate::Printer::set_quirks()).
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = Some(quirks);
+    }
+
+    /// See [`Printer::quirks()`](crate::Printer::quirks()).
+    pub fn quirks(&self) -> Quirks {
+        self.quirks.unwrap_or_default()
+    }
+
+    /// See [`Printer::get_serial()`](crate::Printer::get_serial()).
+    pub async fn get_serial(&mut self) -> Result<String> {
+        self.query_string(&proto::GET_SERIAL).await
+    }
+
+    /// See [`Printer::get_hardware_ver()`](crate::Printer::get_hardware_ver()).
+    pub async fn get_hardware_ver(&mut self) -> Result<String> {
+        self.query_string(&proto::GET_HARDWARE_VER).await
+    }
+
+    /// See [`Printer::get_name()`](crate::Printer::get_name()).
+    pub async fn get_name(&mut self) -> Result<String> {
+        self.query_string(&proto::GET_NAME).await
+    }
+
+    /// See [`Printer::get_mac()`](crate::Printer::get_mac()).
+    pub async fn get_mac(&mut self) -> Result<MacAddr> {
+        let buf = self.query(&proto::GET_MAC).await?;
+        // for some reason the printer sends the MAC address twice
+        if buf.len() < 6 {
+            return Err(Error::InvalidResponse {
+                what: "MAC address",
+                data: buf,
+            });
+        }
+        let mut mac = [0u8; 6];
+        mac.copy_from_slice(&buf[0..6]);
+        Ok(MacAddr(mac))
+    }
+
+    /// See [`Printer::get_battery()`](crate::Printer::get_battery()).
+    pub async fn get_battery(&mut self) -> Result<u8> {
+        Ok(self.get_battery_status().await?.percent)
+    }
+
+    /// See [`Printer::get_battery_status()`](crate::Printer::get_battery_status()).
+    pub async fn get_battery_status(&mut self) -> Result<BatteryStatus> {
+        let buf = self.query(&proto::GET_BATTERY).await?;
+        let [state, percent] = buf[..] else {
+            return Err(Error::InvalidResponse {
+                what: "battery",
+                data: buf,
+            });
+        };
+        let charging = match state {
+            0 => Some(false),
+            1 => Some(true),
+            _ => {
+                log::debug!("unknown battery state: {state:#x}");
+                None
+            }
+        };
+        Ok(BatteryStatus { percent, charging })
+    }
+
+    /// Ask for one ESC/POS real-time status byte, `None` if the printer doesn't answer it.
+    async fn realtime_status(&mut self, n: u8) -> Result<Option<u8>> {
+        self.discard_stale().await;
+        self.send(
+            &[proto::GET_STATUS[0], proto::GET_STATUS[1], n],
+            self.config.timeouts.command,
+        )
+        .await?;
+        let mut buf = [0u8; 16];
+        let len = match self.backend.recv(&mut buf, STATUS_TIMEOUT).await {
+            Ok(len) => len,
+            Err(Error::Timeout) => 0,
+            Err(e) => return Err(e),
+        };
+        dump::received(&proto::GET_STATUS, &buf[..len], STATUS_TIMEOUT);
+        self.stale = (len == 0).then_some(&proto::GET_STATUS);
+
+        // bits 1 and 4 are always set, bits 0 and 7 always clear
+        Ok(match buf[..len] {
+            [b] if b & 0x93 == 0x12 => Some(b),
+            _ => None,
+        })
+    }
+
+    /// See [`Printer::get_status()`](crate::Printer::get_status()).
+    pub async fn get_status(&mut self) -> Result<PrinterStatus> {
+        let offline = self.realtime_status(2).await?;
+        let error = self.realtime_status(3).await?;
+        let paper = self.realtime_status(4).await?;
+        Ok(PrinterStatus {
+            paper: paper.map(|b| b & 0x60 == 0),
+            cover_open: offline.map(|b| b & 0x04 != 0),
+            overheated: error.map(|b| b & 0x40 != 0),
+        })
+    }
+
+    /// See [`Printer::set_check_status()`](crate::Printer::set_check_status()).
+    pub fn set_check_status(&mut self, check: bool) {
+        self.check_status = check;
+    }
+
+    /// See [`Printer::set_end_of_image()`](crate::Printer::set_end_of_image()).
+    pub fn set_end_of_image(&mut self, mode: EndOfImage) {
+        self.config.end_of_image = mode;
+    }
+
+    /// See [`Printer::set_framing()`](crate::Printer::set_framing()).
+    pub fn set_framing(&mut self, framing: Framing) {
+        self.config.framing = framing;
+    }
+
+    /// See [`Printer::set_thermal_policy()`](crate::Printer::set_thermal_policy()).
+    pub fn set_thermal_policy(&mut self, policy: Option<ThermalPolicy>) {
+        self.config.thermal = policy;
+    }
+
+    /// See [`Printer::set_concentration()`](crate::Printer::set_concentration()).
+    pub async fn set_concentration(&mut self, c: impl Into<Concentration>) -> Result<()> {
+        let c = c.into();
+        let level = c.level(self.model.unwrap_or_default());
+        if level > self.capabilities().max_concentration {
+            return Err(Error::InvalidArgument(format!(
+                "invalid concentration: {c}"
+            )));
+        }
+
+        let mut buf = Vec::new();
+        Command::SetConcentration(level).encode(&mut buf)?;
+        self.send(&buf, self.config.timeouts.command).await?;
+        self.concentration = Some(c);
+        Ok(())
+    }
+
+    /// See [`Printer::concentration()`](crate::Printer::concentration()).
+    pub fn concentration(&self) -> Option<Concentration> {
+        self.concentration
+    }
+
+    /// See [`Printer::reset()`](crate::Printer::reset()).
+    pub async fn reset(&mut self) -> Result<()> {
+        self.send(&proto::RESET, self.config.timeouts.query).await?;
+        let mut buf = [0u8; 128];
+        let _ = self
+            .backend
+            .recv(&mut buf, self.config.timeouts.command)
+            .await;
+
+        if self.quirks().init_after_reset {
+            self.send(&proto::INIT, self.config.timeouts.command)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// See [`Printer::print_text()`](crate::Printer::print_text()).
+    pub async fn print_text(&mut self, text: &str) -> Result<()> {
+        let mut buf = Vec::new();
+        Command::Text(text).encode(&mut buf)?;
+        self.send_once(&buf, self.config.timeouts.text).await?;
+        Ok(())
+    }
+
+    /// See [`Printer::print_image()`](crate::Printer::print_image()).
+    pub async fn print_image(&mut self, pixels: &[u8], width: u16) -> Result<()> {
+        self.send_image(pixels, width).await?;
+        self.end_job().await
+    }
+
+    /// Send one image, followed by [`proto::END_OF_IMAGE`] for [`EndOfImage::Always`].
+    async fn send_image(&mut self, pixels: &[u8], width: u16) -> Result<()> {
+        let mut packet = Vec::with_capacity(pixels.len() + 8);
+        Command::Image { width, pixels }.encode(&mut packet)?;
+        self.check_width(width)?;
+        let rows = pixels.len() / (width as usize / 8);
+        let max = self.quirks().max_chunk_rows as usize;
+        if rows > max {
+            return Err(Error::DocumentTooLong { rows, max });
+        }
+        self.send_once(&packet, self.config.timeouts.image).await?;
+
+        // no idea what this does, but the Windows driver sends this after every print.
+        if self.config.end_of_image == EndOfImage::Always {
+            self.send(&proto::END_OF_IMAGE, self.config.timeouts.command)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Send [`proto::END_OF_IMAGE`] for [`EndOfImage::OncePerJob`].
+    async fn end_job(&mut self) -> Result<()> {
+        if self.config.end_of_image == EndOfImage::OncePerJob {
+            self.send(&proto::END_OF_IMAGE, self.config.timeouts.command)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// See [`Printer::print_image_chunked_ext()`](crate::Printer::print_image_chunked_ext()).
+    pub async fn print_image_chunked_ext(
+        &mut self,
+        pixels: &[u8],
+        width: u16,
+        chunk_height: u16,
+        delay: Duration,
+        progress: impl FnMut(Progress) + Send,
+    ) -> Result<()> {
+        self.send_chunks(pixels, width, chunk_height, Some(delay), progress)
+            .await?;
+        self.end_job().await
+    }
+
+    async fn send_chunks(
+        &mut self,
+        pixels: &[u8],
+        width: u16,
+        chunk_height: u16,
+        delay: Option<Duration>,
+        mut progress: impl FnMut(Progress) + Send,
+    ) -> Result<()> {
+        let chunk_height = chunk_height.clamp(1, self.quirks().max_chunk_rows);
+        let stride = (width as usize / 8).max(1);
+        let total = pixels.len() / stride;
+        let start = Instant::now();
+        let mut rows = 0;
+        if self.config.framing == Framing::PerJob {
+            if !pixels.len().is_multiple_of(stride) {
+                return Err(Error::InvalidArgument(format!(
+                    "invalid length of pixels: {}",
+                    pixels.len()
+                )));
+            }
+
+            for frame in pixels.chunks(stride * 0xffff) {
+                self.poll_status().await?;
+                let mut header = Vec::new();
+                Command::ImageHeader {
+                    width,
+                    height: (frame.len() / stride) as u16,
+                }
+                .encode(&mut header)?;
+                self.check_width(width)?;
+                self.send_once(&header, self.config.timeouts.command)
+                    .await?;
+
+                for chunk in frame.chunks(stride * chunk_height as usize) {
+                    self.send_pixels(chunk, self.config.timeouts.image).await?;
+                    rows += chunk.len() / stride;
+                    progress(Progress {
+                        rows,
+                        total,
+                        elapsed: start.elapsed(),
+                    });
+                    self.backend.sleep(self.chunk_delay(delay, chunk)).await;
+                }
+
+                if self.config.end_of_image == EndOfImage::Always {
+                    self.send(&proto::END_OF_IMAGE, self.config.timeouts.command)
+                        .await?;
+                }
+            }
+            return Ok(());
+        }
+
+        for chunk in pixels.chunks(width as usize * chunk_height as usize / 8) {
+            self.poll_status().await?;
+            self.send_image(chunk, width).await?;
+            rows += chunk.len() / stride;
+            progress(Progress {
+                rows,
+                total,
+                elapsed: start.elapsed(),
+            });
+            self.backend.sleep(self.chunk_delay(delay, chunk)).await;
+        }
+        Ok(())
+    }
+
+    /// Delay after `chunk`, `delay` if given, otherwise from the [`ThermalPolicy`] or the fixed delay.
+    fn chunk_delay(&self, delay: Option<Duration>, chunk: &[u8]) -> Duration {
+        match (delay, self.config.thermal) {
+            (Some(delay), _) => delay,
+            (None, Some(policy)) => {
+                let black: u64 = chunk.iter().map(|b| b.count_ones() as u64).sum();
+                let coverage = black as f64 / (chunk.len() * 8).max(1) as f64;
+                policy.delay(coverage)
+            }
+            (None, None) => self.config.chunk_delay,
+        }
+    }
+
+    /// Fail, if the printer reports a problem, see [`AsyncPrinter::set_check_status()`].
+    async fn poll_status(&mut self) -> Result<()> {
+        if !self.check_status {
+            return Ok(());
+        }
+
+        let status = self.get_status().await?;
+        if status == PrinterStatus::default() {
+            log::warn!("printer doesn't report its status, not checking it anymore");
+            self.check_status = false;
+        }
+        status.check()
+    }
+
+    /// See [`Printer::print_image_chunked()`](crate::Printer::print_image_chunked()).
+    pub async fn print_image_chunked(&mut self, pixels: &[u8], width: u16) -> Result<()> {
+        self.send_chunks(pixels, width, 24, None, |_| {}).await?;
+        self.end_job().await
+    }
+
+    /// See [`Printer::print_document()`](crate::Printer::print_document()).
+    pub async fn print_document(&mut self, doc: &Document) -> Result<()> {
+        self.print_document_ext(doc, |_| {}).await
+    }
+
+    /// See [`Printer::print_document_ext()`](crate::Printer::print_document_ext()).
+    pub async fn print_document_ext(
+        &mut self,
+        doc: &Document,
+        mut progress: impl FnMut(Progress) + Send,
+    ) -> Result<()> {
+        let total = doc.height();
+        let start = Instant::now();
+        self.send_document(doc, 0, 24, None, |rows| {
+            progress(Progress {
+                rows,
+                total,
+                elapsed: start.elapsed(),
+            })
+        })
+        .await?;
+        self.end_job().await
+    }
+
+    /// See [`Printer::print()`](crate::Printer::print()).
+    pub async fn print(&mut self, doc: &Document, opts: &PrintOptions) -> Result<()> {
+        self.print_ext(doc, opts, |_| {}).await
+    }
+
+    /// See [`Printer::print_ext()`](crate::Printer::print_ext()).
+    pub async fn print_ext(
+        &mut self,
+        doc: &Document,
+        opts: &PrintOptions,
+        progress: impl FnMut(Progress) + Send,
+    ) -> Result<()> {
+        let end_of_image = self.config.end_of_image;
+        if let Some(mode) = opts.end_of_image {
+            self.config.end_of_image = mode;
+        }
+        let result = self.print_job(doc, opts, progress).await;
+        self.config.end_of_image = end_of_image;
+        result
+    }
+
+    async fn print_job(
+        &mut self,
+        doc: &Document,
+        opts: &PrintOptions,
+        mut progress: impl FnMut(Progress) + Send,
+    ) -> Result<()> {
+        if opts.reset {
+            self.reset().await?;
+        }
+        if let Some(c) = opts.concentration {
+            self.set_concentration(c).await?;
+        }
+
+        let total = doc.height() * opts.copies;
+        let start = Instant::now();
+        for copy in 0..opts.copies {
+            log::trace!("printing copy {copy}...");
+            let offset = copy * doc.height();
+            let stamped = opts.stamp.as_ref().map(|stamp| {
+                let mut doc = doc.clone();
+                match opts.copies {
+                    1 => doc.stamp(stamp),
+                    n => doc.stamp(&format!("{stamp} {}/{n}", copy + 1)),
+                }
+                doc
+            });
+            let doc = stamped.as_ref().unwrap_or(doc);
+            let mut done = 0;
+            loop {
+                let result = self
+                    .send_document(doc, done, opts.chunk_height, opts.delay, |rows| {
+                        done = rows;
+                        progress(Progress {
+                            rows: offset + rows,
+                            total,
+                            elapsed: start.elapsed(),
+                        })
+                    })
+                    .await;
+                match result {
+                    Err(Error::Disconnected)
+                        if opts.resume_on_reconnect && self.reconnect.is_some() =>
+                    {
+                        log::warn!("printer disconnected after {done} rows, reconnecting...");
+                        self.reconnect().await?;
+                    }
+                    result => break result?,
+                }
+            }
+        }
+        self.end_job().await?;
+
+        if opts.feed > 0 {
+            self.push(opts.feed).await?;
+        }
+        Ok(())
+    }
+
+    /// See [`Printer::print_rows()`](crate::Printer::print_rows()).
+    pub async fn print_rows(
+        &mut self,
+        rows: impl Iterator<Item = [u8; 48]> + Send,
+        opts: &PrintOptions,
+    ) -> Result<()> {
+        if opts.copies != 1 {
+            return Err(Error::InvalidArgument(
+                "rows can only be printed once".into(),
+            ));
+        }
+        if opts.reset {
+            self.reset().await?;
+        }
+        if let Some(c) = opts.concentration {
+            self.set_concentration(c).await?;
+        }
+
+        let chunk_height = opts.chunk_height.clamp(1, self.quirks().max_chunk_rows) as usize;
+        let mut rows = rows.peekable();
+        let mut chunk = Vec::with_capacity(chunk_height * 48);
+        while rows.peek().is_some() {
+            chunk.clear();
+            rows.by_ref()
+                .take(chunk_height)
+                .for_each(|row| chunk.extend_from_slice(&row));
+
+            if chunk.iter().all(|&b| b == 0) {
+                let mut n = chunk.len() / 48;
+                log::debug!("skipping {n} blank rows");
+                while n > 0 {
+                    let k = n.min(0xff);
+                    self.push(k as u8).await?;
+                    n -= k;
+                }
+            } else {
+                self.send_chunks(&chunk, 384, chunk_height as u16, opts.delay, |_| {})
+                    .await?;
+            }
+        }
+        self.end_job().await?;
+
+        if opts.feed > 0 {
+            self.push(opts.feed).await?;
+        }
+        Ok(())
+    }
+
+    /// Print `doc`, starting at row `from`, feeding blank bands,
+    /// and call `progress` with the number of rows done.
+    async fn send_document(
+        &mut self,
+        doc: &Document,
+        from: usize,
+        chunk_height: u16,
+        delay: Option<Duration>,
+        mut progress: impl FnMut(usize) + Send,
+    ) -> Result<()> {
+        self.check_width(doc.width())?;
+        let mut done = 0;
+        for band in doc.bands(24) {
+            match band {
+                Band::Ink(rows) => {
+                    let first = rows.start.max(from);
+                    if first < rows.end {
+                        let pixels = doc.rows(first..rows.end);
+                        self.send_chunks(pixels, doc.width(), chunk_height, delay, |p| {
+                            progress(first + p.rows)
+                        })
+                        .await?;
+                    }
+                    done = rows.end;
+                }
+                Band::Blank(n) => {
+                    let end = done + n;
+                    let mut n = end.saturating_sub(done.max(from));
+                    done = end;
+                    if n == 0 {
+                        continue;
+                    }
+                    log::debug!("skipping {n} blank rows");
+                    while n > 0 {
+                        let k = n.min(0xff);
+                        self.push(k as u8).await?;
+                        n -= k;
+                    }
+                    progress(done);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// See [`Printer::send_batch()`](crate::Printer::send_batch()).
+    pub async fn send_batch(&mut self, batch: &CommandBatch) -> Result<()> {
+        self.send_once(batch.as_bytes(), self.config.timeouts.image)
+            .await
+    }
+
+    /// See [`Printer::push()`](crate::Printer::push()).
+    pub async fn push(&mut self, num: u8) -> Result<()> {
+        self.send_once(
+            &[proto::PUSH[0], proto::PUSH[1], num],
+            self.config.timeouts.feed,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// See [`Printer::feed_mm()`](crate::Printer::feed_mm()).
+    pub async fn feed_mm(&mut self, mm: f32) -> Result<()> {
+        if !mm.is_finite() || mm < 0.0 {
+            return Err(Error::InvalidArgument(format!("invalid length: {mm}mm")));
+        }
+
+        let dpi = self.capabilities().dpi as f32;
+        let mut rows = (mm / 25.4 * dpi).round() as usize;
+        while rows > 0 {
+            let n = rows.min(0xff);
+            self.push(n as u8).await?;
+            rows -= n;
+        }
+        Ok(())
+    }
+
+    /// See [`Printer::feed_to_tear_off()`](crate::Printer::feed_to_tear_off()).
+    pub async fn feed_to_tear_off(&mut self) -> Result<()> {
+        self.feed_mm(TEAR_OFF_MM).await
+    }
+}
@@ -0,0 +1,184 @@
+//! Printing backends, which transfer data between [`Printer`](crate::Printer) and the device.
+
+use std::time::Duration;
+
+use crate::{Error, Model, Result};
+
+pub use crate::{
+    aio::{AsyncBackend, BoxFuture},
+    backoff::Backoff,
+    emulator::EmulatorBackend,
+    idle::IdleBackend,
+    lock::DeviceLock,
+    record::{RecordingBackend, ReplayBackend},
+    stream::{Interest, IoBackend, NoTimeout, PollStrategy, SetTimeout},
+};
+
+#[cfg(all(unix, feature = "poll"))]
+pub use crate::stream::FdPoll;
+
+#[cfg(feature = "usb")]
+pub use crate::usb::{watch_devices, DeviceWatcher, HotplugEvent, UsbBackend};
+
+#[cfg(feature = "file")]
+pub use crate::file::FileBackend;
+
+#[cfg(all(feature = "bluetooth", target_os = "linux"))]
+pub use crate::bt::{BtBackend, BtDevice};
+
+#[cfg(feature = "ble")]
+pub use crate::ble::{BleBackend, BleDevice};
+
+#[cfg(feature = "tcp")]
+pub use crate::tcp::TcpBackend;
+
+#[cfg(any(test, feature = "mock"))]
+pub use crate::mock::MockBackend;
+
+/// Printing backend.
+///
+/// [`Printer`](crate::Printer) requires backends to be [`Send`], see [`Printer::new()`](crate::Printer::new()).
+pub trait Backend {
+    /// Send data to the printer.
+    /// TODO: return number of bytes sent
+    fn send(&mut self, buf: &[u8], timeout: Duration) -> Result<()>;
+
+    /// Receive at most `buf.len()` bytes of data from the printer.
+    ///
+    /// # Return value
+    /// This functions the number of bytes received from the printer.
+    /// If nothing arrived within `timeout`, it fails with [`Error::Timeout`],
+    /// and if the printer is gone, with [`Error::Disconnected`], instead of returning `Ok(0)`.
+    /// [`Printer`](crate::Printer) relies on this to retry queries, and to drop late replies.
+    fn recv(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize>;
+
+    /// Hand the device back to the operating system, after which the backend can't be used anymore.
+    fn release(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Statistics about the transfers done by this backend, if it keeps any.
+    fn stats(&self) -> Option<TransportStats> {
+        None
+    }
+
+    /// The printer model, if the backend can tell it from the device, e.g. by USB descriptors.
+    fn model(&self) -> Option<Model> {
+        None
+    }
+}
+
+impl<B: Backend + ?Sized> Backend for Box<B> {
+    fn send(&mut self, buf: &[u8], timeout: Duration) -> Result<()> {
+        (**self).send(buf, timeout)
+    }
+
+    fn recv(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        (**self).recv(buf, timeout)
+    }
+
+    fn release(&mut self) -> Result<()> {
+        (**self).release()
+    }
+
+    fn stats(&self) -> Option<TransportStats> {
+        (**self).stats()
+    }
+
+    fn model(&self) -> Option<Model> {
+        (**self).model()
+    }
+}
+
+/// Transfer statistics of a [`Backend`], see [`Printer::transport_stats()`](crate::Printer::transport_stats()).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[non_exhaustive]
+pub struct TransportStats {
+    /// Number of transfers, in both directions.
+    pub transfers: u64,
+
+    /// Number of bytes sent to the printer.
+    pub bytes_sent: u64,
+
+    /// Number of bytes received from the printer.
+    pub bytes_received: u64,
+
+    /// Total time spent sending data.
+    pub send_time: Duration,
+
+    /// Lowest throughput of any large transfer to the printer, in bytes per second.
+    pub slowest_send: Option<f64>,
+}
+
+impl TransportStats {
+    /// Average throughput of sending data to the printer, in bytes per second.
+    pub fn send_throughput(&self) -> Option<f64> {
+        let secs = self.send_time.as_secs_f64();
+        (secs > 0.0).then(|| self.bytes_sent as f64 / secs)
+    }
+}
+
+/// Find a printer, connected using any backend, see [`Printer::find()`](crate::Printer::find()).
+/// If `wait` is set, take the [`DeviceLock`] of USB printers first.
+pub(crate) fn find_backend(wait: Option<Duration>) -> Result<Box<dyn Backend + Send>> {
+    #[cfg(feature = "usb")]
+    {
+        match crate::usb::UsbBackend::list() {
+            Ok(devs) => {
+                if let Some(dev) = devs.first() {
+                    let backend = match wait {
+                        Some(wait) => UsbBackend::open_exclusive(dev, wait)?,
+                        None => UsbBackend::open(dev)?,
+                    };
+                    return Ok(Box::new(backend));
+                }
+            }
+            Err(e) => log::error!("cannot get list of usb devices: {e}"),
+        }
+    }
+
+    // libusb can't see the printer, e.g. without access to /dev/bus/usb, try the kernel's usblp driver
+    #[cfg(feature = "file")]
+    {
+        match FileBackend::list() {
+            Ok(paths) => {
+                if let Some(path) = paths.first() {
+                    log::info!("using {}", path.display());
+                    return Ok(Box::new(FileBackend::open(path)?));
+                }
+            }
+            Err(e) => log::error!("cannot get list of usblp devices: {e}"),
+        }
+    }
+
+    #[cfg(all(feature = "bluetooth", target_os = "linux"))]
+    {
+        match crate::bt::BtBackend::list() {
+            Ok(devs) => {
+                for dev in devs {
+                    match BtBackend::open(dev.addr) {
+                        Ok(backend) => return Ok(Box::new(backend)),
+                        Err(e) => log::debug!("{} ({}): {e:#}", dev.name, dev.addr),
+                    }
+                }
+            }
+            Err(e) => log::error!("cannot get list of bluetooth devices: {e}"),
+        }
+    }
+
+    #[cfg(feature = "ble")]
+    {
+        match crate::ble::BleBackend::list() {
+            Ok(devs) => {
+                if let Some(dev) = devs.first() {
+                    let backend = BleBackend::open(dev)?;
+                    return Ok(Box::new(backend));
+                }
+            }
+            Err(e) => log::error!("cannot scan for BLE devices: {e}"),
+        }
+    }
+
+    let _ = wait;
+    Err(Error::NotFound)
+}
@@ -0,0 +1,103 @@
+use std::{
+    hash::{BuildHasher, RandomState},
+    time::Duration,
+};
+
+/// Exponential backoff with jitter, for polling devices.
+///
+/// The `n`-th delay is `initial * factor^n`, capped at `max`,
+/// and randomly shortened by up to `jitter` (`0.0..=1.0`) of its length,
+/// so that multiple processes polling the same bus don't do so in lockstep.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    /// Delay before the first retry.
+    pub initial: Duration,
+
+    /// Upper bound for any delay.
+    pub max: Duration,
+
+    /// Growth factor between retries, a negative or NaN factor is taken as `1.0`.
+    pub factor: f64,
+
+    /// Fraction of each delay that is randomized, clamped to `0.0..=1.0`, NaN is taken as `0.0`.
+    pub jitter: f64,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(5),
+            factor: 2.0,
+            jitter: 0.25,
+        }
+    }
+}
+
+impl Backoff {
+    /// Get the delay before retry number `attempt`, starting at `0`.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = if self.factor >= 0.0 { self.factor } else { 1.0 };
+        let jitter = if self.jitter.is_nan() {
+            0.0
+        } else {
+            self.jitter.clamp(0.0, 1.0)
+        };
+        let base = self.initial.as_secs_f64() * factor.powi(attempt.min(64) as i32);
+        let base = base.min(self.max.as_secs_f64());
+        // e.g. an infinite factor with an initial delay of zero is NaN
+        Duration::try_from_secs_f64(base - jitter * base * random()).unwrap_or(self.max)
+    }
+
+    /// Get an endless iterator over the delays.
+    pub fn delays(self) -> impl Iterator<Item = Duration> {
+        (0..).map(move |attempt| self.delay(attempt))
+    }
+}
+
+/// Get a random number in `0.0..1.0`.
+/// This is not cryptographically secure, but good enough for jitter.
+fn random() -> f64 {
+    let x = RandomState::new().hash_one(std::time::Instant::now());
+    (x >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays() {
+        let backoff = Backoff {
+            jitter: 0.0,
+            ..Backoff::default()
+        };
+        let delays: Vec<_> = backoff.delays().take(8).map(|d| d.as_millis()).collect();
+        assert_eq!(delays, [100, 200, 400, 800, 1600, 3200, 5000, 5000]);
+
+        for d in Backoff::default().delays().take(100) {
+            assert!(d <= Duration::from_secs(5));
+        }
+    }
+
+    #[test]
+    fn invalid_values() {
+        let cases = [
+            (f64::NAN, 0.25, Duration::from_millis(100)),
+            (-2.0, f64::NAN, Duration::from_millis(100)),
+            (f64::INFINITY, -1.0, Duration::ZERO),
+            (2.0, f64::INFINITY, Duration::from_millis(100)),
+        ];
+        for (factor, jitter, initial) in cases {
+            let backoff = Backoff {
+                initial,
+                factor,
+                jitter,
+                ..Backoff::default()
+            };
+            for d in backoff.delays().take(70) {
+                assert!(d <= backoff.max, "{factor} {jitter}: {d:?}");
+            }
+        }
+    }
+}
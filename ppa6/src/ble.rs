@@ -0,0 +1,188 @@
+use std::{
+	collections::VecDeque,
+	pin::Pin,
+	sync::OnceLock,
+	time::Duration,
+};
+use btleplug::{
+	api::{Central, CharPropFlags, Characteristic, Manager as _, Peripheral as _, ScanFilter, ValueNotification, WriteType},
+	platform::{Manager, Peripheral},
+};
+use futures::{Stream, StreamExt};
+use tokio::runtime::Runtime;
+
+use crate::{Backend, Error, Result};
+
+/// How long [`BleBackend::list()`] scans for printers.
+const SCAN_TIME: Duration = Duration::from_secs(5);
+
+/// Once a response started arriving, wait at most this long for the rest of it.
+const RECV_GAP: Duration = Duration::from_millis(50);
+
+/// Size of the ATT header, which is part of the MTU.
+const ATT_HEADER: usize = 3;
+
+/// btleplug needs a running async runtime, even between calls.
+fn runtime() -> Result<&'static Runtime> {
+	static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+	if let Some(rt) = RUNTIME.get() {
+		return Ok(rt);
+	}
+	let rt = tokio::runtime::Builder::new_multi_thread()
+		.worker_threads(1)
+		.enable_all()
+		.build()?;
+	Ok(RUNTIME.get_or_init(|| rt))
+}
+
+/// A printer found by [`BleBackend::scan()`].
+#[derive(Debug, Clone)]
+pub struct BleDevice {
+	/// Advertised name of the printer.
+	pub name: String,
+
+	/// Platform-specific address, the MAC address on Linux and Windows, a UUID on macOS.
+	pub addr: String,
+
+	/// Signal strength in dBm, if known.
+	pub rssi: Option<i16>,
+
+	peripheral: Peripheral,
+}
+
+/// A Bluetooth Low Energy backend for [`Printer`](crate::Printer), working on Linux, macOS and Windows.
+pub struct BleBackend {
+	peripheral: Peripheral,
+	write: Characteristic,
+	write_type: WriteType,
+	notifications: Pin<Box<dyn Stream<Item = ValueNotification> + Send>>,
+	pending: VecDeque<u8>,
+}
+
+impl BleBackend {
+	/// Get a list of nearby printers, strongest signal first.
+	pub fn list() -> Result<Vec<BleDevice>> {
+		Self::scan(SCAN_TIME, "PeriPage")
+	}
+
+	/// Scan for `time` for devices, whose name contains `filter`, strongest signal first.
+	pub fn scan(time: Duration, filter: &str) -> Result<Vec<BleDevice>> {
+		runtime()?.block_on(async {
+			let manager = Manager::new().await?;
+			let mut devs = Vec::new();
+			for adapter in manager.adapters().await? {
+				adapter.start_scan(ScanFilter::default()).await?;
+				tokio::time::sleep(time).await;
+				let _ = adapter.stop_scan().await;
+
+				for peripheral in adapter.peripherals().await? {
+					let Some(props) = peripheral.properties().await? else {
+						continue;
+					};
+					let Some(name) = props.local_name.or(props.advertisement_name) else {
+						continue;
+					};
+					if !name.contains(filter) {
+						continue;
+					}
+					log::debug!("BLE: found {name} ({}), RSSI {:?}", props.address, props.rssi);
+					devs.push(BleDevice {
+						name,
+						addr: peripheral.id().to_string(),
+						rssi: props.rssi,
+						peripheral,
+					});
+				}
+			}
+			devs.sort_by_key(|dev| std::cmp::Reverse(dev.rssi));
+			Ok(devs)
+		})
+	}
+
+	/// Connect to a printer and look up its write and notify characteristics.
+	pub fn open(dev: &BleDevice) -> Result<Self> {
+		let peripheral = dev.peripheral.clone();
+		runtime()?.block_on(async move {
+			peripheral.connect().await?;
+			peripheral.discover_services().await?;
+
+			let chars = peripheral.characteristics();
+			log::debug!("BLE characteristics = {chars:#?}");
+
+			// prefer writes without response, they are a lot faster
+			let (write, write_type) = chars
+				.iter()
+				.find(|c| c.properties.contains(CharPropFlags::WRITE_WITHOUT_RESPONSE))
+				.map(|c| (c, WriteType::WithoutResponse))
+				.or_else(|| {
+					chars
+						.iter()
+						.find(|c| c.properties.contains(CharPropFlags::WRITE))
+						.map(|c| (c, WriteType::WithResponse))
+				})
+				.ok_or_else(|| Error::Unsupported(format!("{} has no writable characteristic", dev.name)))?;
+			let Some(notify) = chars.iter().find(|c| c.properties.contains(CharPropFlags::NOTIFY)) else {
+				return Err(Error::Unsupported(format!("{} has no notify characteristic", dev.name)));
+			};
+
+			peripheral.subscribe(notify).await?;
+			let notifications = peripheral.notifications().await?;
+			log::info!("BLE: connected to {}, write = {}, notify = {}", dev.name, write.uuid, notify.uuid);
+
+			Ok(Self {
+				write: write.clone(),
+				write_type,
+				notifications,
+				pending: VecDeque::new(),
+				peripheral,
+			})
+		})
+	}
+}
+
+impl Backend for BleBackend {
+	fn send(&mut self, buf: &[u8], timeout: Duration) -> Result<()> {
+		let chunk = (self.peripheral.mtu() as usize).saturating_sub(ATT_HEADER).max(20);
+		let this = &*self;
+		runtime()?.block_on(async {
+			tokio::time::timeout(timeout, async {
+				for part in buf.chunks(chunk) {
+					this.peripheral.write(&this.write, part, this.write_type).await?;
+				}
+				Ok(())
+			})
+			.await
+			.map_err(|_| Error::Timeout)?
+		})
+	}
+
+	fn recv(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+		let Self { notifications, pending, .. } = self;
+		runtime()?.block_on(async {
+			let mut wait = timeout;
+			while pending.len() < buf.len() {
+				match tokio::time::timeout(wait, notifications.next()).await {
+					Ok(Some(n)) => pending.extend(n.value),
+					Ok(None) => return Err(Error::Disconnected),
+					Err(_) => break,
+				}
+				wait = RECV_GAP;
+			}
+			Ok(())
+		})?;
+
+		if pending.is_empty() && !buf.is_empty() {
+			return Err(Error::Timeout);
+		}
+		let n = buf.len().min(pending.len());
+		for (dst, src) in buf.iter_mut().zip(pending.drain(..n)) {
+			*dst = src;
+		}
+		Ok(n)
+	}
+
+	fn release(&mut self) -> Result<()> {
+		runtime()?.block_on(self.peripheral.disconnect())?;
+		Ok(())
+	}
+}
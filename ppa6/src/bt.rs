@@ -0,0 +1,143 @@
+use std::{
+	fs::File,
+	io,
+	mem,
+	os::fd::{AsRawFd, FromRawFd, OwnedFd},
+	path::Path,
+	time::Duration,
+};
+use crate::{backend::{FdPoll, IoBackend}, Backend, Error, MacAddr, Result};
+
+/// Where BlueZ keeps the paired devices, per adapter, only readable by root.
+const BLUEZ_STORAGE: &str = "/var/lib/bluetooth";
+
+/// The printer's serial port profile listens on this RFCOMM channel.
+const DEFAULT_CHANNEL: u8 = 1;
+
+const BTPROTO_RFCOMM: libc::c_int = 3;
+
+#[repr(C)]
+struct SockaddrRc {
+	rc_family: libc::sa_family_t,
+	rc_bdaddr: [u8; 6],
+	rc_channel: u8,
+}
+
+/// A paired Bluetooth printer, see [`BtBackend::list()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BtDevice {
+	pub addr: MacAddr,
+	pub name: String,
+}
+
+/// A Bluetooth (RFCOMM/SPP) backend for [`Printer`](crate::Printer).
+///
+/// Pairing is left to the operating system, e.g. `bluetoothctl pair <MAC>`.
+pub struct BtBackend {
+	io: IoBackend<File, FdPoll>,
+}
+
+impl BtBackend {
+	/// Get a list of printers paired via bluetooth.
+	///
+	/// This reads BlueZ's storage in `/var/lib/bluetooth`, which **requires root**,
+	/// other users get [`Error::PermissionDenied`].
+	/// Without root, get the address from `bluetoothctl devices Paired`, and use [`BtBackend::open()`],
+	/// which works for any user, that may use Bluetooth.
+	pub fn list() -> Result<Vec<BtDevice>> {
+		let mut devs = Vec::new();
+		let adapters = match std::fs::read_dir(BLUEZ_STORAGE) {
+			Ok(x) => x,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(devs),
+			Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+				log::debug!("{BLUEZ_STORAGE} is only readable by root, connect by address instead");
+				return Err(Error::PermissionDenied);
+			},
+			Err(e) => return Err(e.into()),
+		};
+
+		for adapter in adapters {
+			let adapter = adapter?.path();
+			let Ok(entries) = std::fs::read_dir(&adapter) else {
+				continue;
+			};
+			for entry in entries {
+				let path = entry?.path();
+				let Some(addr) = path.file_name().and_then(|s| s.to_str()).and_then(|s| s.parse().ok()) else {
+					continue;
+				};
+				let Some(name) = read_name(&path.join("info")) else {
+					continue;
+				};
+				if name.starts_with("PeriPage") {
+					devs.push(BtDevice { addr, name });
+				}
+			}
+		}
+
+		Ok(devs)
+	}
+
+	/// Connect to a paired printer.
+	pub fn open(addr: MacAddr) -> Result<Self> {
+		Self::open_channel(addr, DEFAULT_CHANNEL)
+	}
+
+	/// Like [`BtBackend::open()`], but connect to a different RFCOMM `channel`.
+	pub fn open_channel(addr: MacAddr, channel: u8) -> Result<Self> {
+		let fd = unsafe { libc::socket(libc::AF_BLUETOOTH, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, BTPROTO_RFCOMM) };
+		if fd < 0 {
+			return Err(io::Error::last_os_error().into());
+		}
+		let sock = unsafe { OwnedFd::from_raw_fd(fd) };
+
+		// bdaddr_t is stored in little endian
+		let mut bdaddr = addr.0;
+		bdaddr.reverse();
+		let sa = SockaddrRc {
+			rc_family: libc::AF_BLUETOOTH as libc::sa_family_t,
+			rc_bdaddr: bdaddr,
+			rc_channel: channel,
+		};
+		let r = unsafe {
+			libc::connect(
+				sock.as_raw_fd(),
+				&sa as *const SockaddrRc as *const libc::sockaddr,
+				mem::size_of::<SockaddrRc>() as libc::socklen_t,
+			)
+		};
+		if r < 0 {
+			return Err(io::Error::last_os_error().into());
+		}
+
+		// reads and writes must not block, the timeouts are implemented with poll(2)
+		let flags = unsafe { libc::fcntl(sock.as_raw_fd(), libc::F_GETFL) };
+		if flags < 0 || unsafe { libc::fcntl(sock.as_raw_fd(), libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+			return Err(io::Error::last_os_error().into());
+		}
+
+		log::info!("Bluetooth: connected to {addr}, channel {channel}");
+		Ok(Self {
+			io: IoBackend::with_poll(File::from(sock), FdPoll),
+		})
+	}
+}
+
+impl Backend for BtBackend {
+	fn send(&mut self, buf: &[u8], timeout: Duration) -> Result<()> {
+		self.io.send(buf, timeout)
+	}
+
+	fn recv(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+		self.io.recv(buf, timeout)
+	}
+}
+
+/// Read the `Name=` of a device from BlueZ's `info` file.
+fn read_name(path: &Path) -> Option<String> {
+	let info = std::fs::read_to_string(path).ok()?;
+	info
+		.lines()
+		.find_map(|l| l.strip_prefix("Name="))
+		.map(str::to_owned)
+}
@@ -0,0 +1,218 @@
+use std::time::Duration;
+
+use crate::{
+    aio::{AsyncBackend, AsyncPrinter, Blocking},
+    Backend, Backoff, Printer, Result,
+};
+
+/// Timeouts of the different kinds of transfers, see [`PrinterBuilder::timeouts()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Timeouts {
+    /// Short commands, like [`Printer::set_concentration()`], default: 1s.
+    pub command: Duration,
+
+    /// Queries, like [`Printer::get_name()`], and [`Printer::reset()`], default: 3s.
+    pub query: Duration,
+
+    /// Feeding paper, see [`Printer::push()`], default: 5s.
+    pub feed: Duration,
+
+    /// Printing text, see [`Printer::print_text()`], default: 30s.
+    pub text: Duration,
+
+    /// Sending an image, see [`Printer::print_image()`], default: 60s.
+    pub image: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            command: Duration::from_secs(1),
+            query: Duration::from_secs(3),
+            feed: Duration::from_secs(5),
+            text: Duration::from_secs(30),
+            image: Duration::from_secs(60),
+        }
+    }
+}
+
+/// When to send [`END_OF_IMAGE`](crate::proto::END_OF_IMAGE), see [`PrinterBuilder::end_of_image()`]
+/// and [`PrintOptions::end_of_image`](crate::PrintOptions::end_of_image).
+///
+/// The Windows driver sends it after every image, nobody knows what it does.
+/// Skipping it saves one transfer per chunk, but no differences in the printed output
+/// have been observed and documented yet, and it's unknown whether every firmware copes with that,
+/// so check the output when changing it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EndOfImage {
+    /// After every image and chunk, like the Windows driver.
+    #[default]
+    Always,
+
+    /// Once after each job, i.e. [`Printer::print_image()`], [`Printer::print_image_chunked()`],
+    /// or [`Printer::print_document()`].
+    OncePerJob,
+
+    /// Never.
+    Never,
+}
+
+/// How images are framed into [`PRINT_IMAGE`](crate::proto::PRINT_IMAGE) commands,
+/// see [`PrinterBuilder::framing()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Framing {
+    /// Every chunk is a separate image of at most 255 rows.
+    #[default]
+    PerChunk,
+
+    /// Up to 65535 rows share one header, like in captures of the Windows driver,
+    /// the chunks are streamed without headers of their own.
+    /// This saves a header and [`END_OF_IMAGE`](crate::proto::END_OF_IMAGE) per chunk,
+    /// but [`Printer::set_check_status()`] can only check before each header.
+    PerJob,
+}
+
+/// Delay between chunks, depending on the black coverage of the chunk, that was just printed,
+/// because dense chunks need more time for the print head to cool down, see [`PrinterBuilder::thermal_policy()`].
+///
+/// The delay is `min + coverage * per_coverage`, but at most `max`, with `coverage` between 0 and 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThermalPolicy {
+    /// Delay after white chunks, default: 10ms.
+    pub min: Duration,
+
+    /// Additional delay for completely black chunks, default: 200ms.
+    pub per_coverage: Duration,
+
+    /// Longest delay, default: 300ms.
+    pub max: Duration,
+}
+
+impl Default for ThermalPolicy {
+    fn default() -> Self {
+        Self {
+            min: Duration::from_millis(10),
+            per_coverage: Duration::from_millis(200),
+            max: Duration::from_millis(300),
+        }
+    }
+}
+
+impl ThermalPolicy {
+    /// Delay after a chunk, where `coverage` of the pixels are black.
+    pub fn delay(&self, coverage: f64) -> Duration {
+        (self.min + self.per_coverage.mul_f64(coverage.clamp(0.0, 1.0))).min(self.max)
+    }
+}
+
+/// Settings of a [`Printer`], see [`PrinterBuilder`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Config {
+    pub timeouts: Timeouts,
+    pub retries: u32,
+    pub retry_backoff: Backoff,
+    pub chunk_delay: Duration,
+    pub end_of_image: EndOfImage,
+    pub framing: Framing,
+    pub thermal: Option<ThermalPolicy>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            timeouts: Timeouts::default(),
+            retries: 0,
+            retry_backoff: Backoff {
+                initial: Duration::from_millis(50),
+                max: Duration::from_secs(1),
+                ..Backoff::default()
+            },
+            chunk_delay: Duration::from_millis(50),
+            end_of_image: EndOfImage::Always,
+            framing: Framing::PerChunk,
+            thermal: None,
+        }
+    }
+}
+
+/// Builds a [`Printer`] or [`AsyncPrinter`] with non-default timeouts and retries.
+#[derive(Debug, Clone, Default)]
+pub struct PrinterBuilder {
+    config: Config,
+}
+
+impl PrinterBuilder {
+    /// Start with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the timeouts of all transfers.
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.config.timeouts = timeouts;
+        self
+    }
+
+    /// Retry transfers, that failed with a transient error, this many times, default: 0.
+    /// See [`Error::is_transient()`](crate::Error::is_transient()).
+    ///
+    /// Only queries and short commands, that may be repeated, like the reset or the concentration, are sent again.
+    /// Text, images and paper feeds are not, since a transfer that failed may have been printed partially,
+    /// and sending it again would print the rest as garbage.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.config.retries = retries;
+        self
+    }
+
+    /// Wait between retries, see [`PrinterBuilder::retries()`], default: 50ms to 1s.
+    pub fn retry_backoff(mut self, backoff: Backoff) -> Self {
+        self.config.retry_backoff = backoff;
+        self
+    }
+
+    /// Wait this long between chunks, see [`Printer::print_image_chunked()`], default: 50ms.
+    pub fn chunk_delay(mut self, delay: Duration) -> Self {
+        self.config.chunk_delay = delay;
+        self
+    }
+
+    /// When to send [`END_OF_IMAGE`](crate::proto::END_OF_IMAGE), default: [`EndOfImage::Always`].
+    pub fn end_of_image(mut self, mode: EndOfImage) -> Self {
+        self.config.end_of_image = mode;
+        self
+    }
+
+    /// Adapt the delay between chunks to their black coverage, instead of [`PrinterBuilder::chunk_delay()`].
+    pub fn thermal_policy(mut self, policy: ThermalPolicy) -> Self {
+        self.config.thermal = Some(policy);
+        self
+    }
+
+    /// How images are framed, default: [`Framing::PerChunk`].
+    pub fn framing(mut self, framing: Framing) -> Self {
+        self.config.framing = framing;
+        self
+    }
+
+    /// Build a printer using `backend`, see [`Printer::new()`].
+    pub fn build(self, backend: impl Backend + Send + 'static) -> Printer {
+        Printer {
+            inner: self.build_async(Blocking(backend)),
+        }
+    }
+
+    /// Build an async printer using `backend`, see [`AsyncPrinter::new()`].
+    pub fn build_async(self, backend: impl AsyncBackend + 'static) -> AsyncPrinter {
+        AsyncPrinter::with_config(backend, self.config)
+    }
+
+    /// Find any printer, see [`Printer::find()`].
+    pub fn find(self) -> Result<Printer> {
+        let mut printer = self.build(crate::backend::find_backend(None)?);
+        printer.set_reconnect(|| crate::backend::find_backend(None));
+        Ok(printer)
+    }
+}
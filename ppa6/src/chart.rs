@@ -0,0 +1,172 @@
+//! Sparklines and simple charts, e.g. for printing sensor dashboards.
+
+use crate::{draw::Point, font, Document, Error, Result};
+
+/// Label font scale.
+const SCALE: f32 = 2.0;
+
+/// Space between labels and the plot, in pixels.
+const PADDING: f32 = 4.0;
+
+/// How the data is drawn.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChartKind {
+    /// Points connected by lines.
+    #[default]
+    Line,
+
+    /// One bar per point, growing from zero, or the lowest value, if it's above zero.
+    Bar,
+}
+
+/// Options of [`render()`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChartOptions {
+    /// Width in pixels, default: 384.
+    pub width: u16,
+
+    /// Height in pixels, default: 96.
+    pub height: usize,
+
+    /// How the data is drawn, default: [`ChartKind::Line`].
+    pub kind: ChartKind,
+
+    /// Draw the axes, default: `true`.
+    pub axes: bool,
+
+    /// Label the axes with the ranges of the data, default: `true`.
+    pub labels: bool,
+
+    /// Width of lines in pixels, default: 2.0.
+    pub line_width: f32,
+}
+
+impl Default for ChartOptions {
+    fn default() -> Self {
+        Self {
+            width: 384,
+            height: 96,
+            kind: ChartKind::Line,
+            axes: true,
+            labels: true,
+            line_width: 2.0,
+        }
+    }
+}
+
+/// Render `values` as a sparkline, a chart without axes or labels.
+pub fn sparkline(values: &[f64], kind: ChartKind, width: u16, height: usize) -> Result<Document> {
+    let points: Vec<(f64, f64)> = values
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| (i as f64, v))
+        .collect();
+    render(
+        &points,
+        &ChartOptions {
+            width,
+            height,
+            kind,
+            axes: false,
+            labels: false,
+            ..ChartOptions::default()
+        },
+    )
+}
+
+/// Render `points` as `(x, y)`, e.g. `(time, value)`, into a chart.
+pub fn render(points: &[(f64, f64)], opts: &ChartOptions) -> Result<Document> {
+    if points.is_empty() {
+        return Err(Error::InvalidArgument("no data to chart".into()));
+    }
+    if points.iter().any(|(x, y)| !x.is_finite() || !y.is_finite()) {
+        return Err(Error::InvalidArgument("chart data must be finite".into()));
+    }
+
+    let (xmin, xmax) = range(points.iter().map(|p| p.0));
+    let (mut ymin, ymax) = range(points.iter().map(|p| p.1));
+    if opts.kind == ChartKind::Bar {
+        ymin = ymin.min(0.0);
+    }
+    let (ylo, yhi) = (label(ymin), label(ymax));
+    let (xlo, xhi) = (label(xmin), label(xmax));
+
+    let (w, h) = (opts.width as f32, opts.height as f32);
+    let glyph_h = font::HEIGHT * SCALE;
+    let (mut left, mut bottom) = (opts.line_width, h - opts.line_width);
+    if opts.labels {
+        left = font::width(&ylo, SCALE).max(font::width(&yhi, SCALE)) + PADDING;
+        bottom = h - glyph_h - PADDING;
+    }
+    let (right, top) = (w - opts.line_width, opts.line_width);
+    if right - left < 1.0 || bottom - top < 1.0 {
+        return Err(Error::InvalidArgument(format!(
+            "chart doesn't fit into {}x{} pixels",
+            opts.width, opts.height
+        )));
+    }
+
+    let map = |(x, y): (f64, f64)| -> Point {
+        (
+            left + ((x - xmin) / (xmax - xmin)) as f32 * (right - left),
+            bottom - ((y - ymin) / (ymax - ymin)) as f32 * (bottom - top),
+        )
+    };
+
+    let mut doc = Document::blank(opts.width, opts.height)?;
+    doc.draw(0.5, |c| {
+        match opts.kind {
+            ChartKind::Line => {
+                let line: Vec<Point> = points.iter().map(|&p| map(p)).collect();
+                c.stroke_polyline(&line, opts.line_width);
+            }
+            ChartKind::Bar => {
+                let slot = (right - left) / points.len() as f32;
+                let base = map((xmin, 0.0f64.clamp(ymin, ymax))).1;
+                for (i, &(_, y)) in points.iter().enumerate() {
+                    let x = left + slot * i as f32;
+                    let y = map((xmin, y)).1;
+                    c.fill_rect((x + slot * 0.1, y.min(base)), (x + slot * 0.9, y.max(base)));
+                }
+            }
+        }
+
+        if opts.axes {
+            c.line((left, top), (left, bottom), 1.0);
+            c.line((left, bottom), (right, bottom), 1.0);
+        }
+        if opts.labels {
+            font::draw(c, (0.0, top), SCALE, &yhi);
+            font::draw(c, (0.0, bottom - glyph_h), SCALE, &ylo);
+            font::draw(c, (left, h - glyph_h), SCALE, &xlo);
+            font::draw(
+                c,
+                (right - font::width(&xhi, SCALE), h - glyph_h),
+                SCALE,
+                &xhi,
+            );
+        }
+    });
+    Ok(doc)
+}
+
+/// Lowest and highest value, widened if they are equal.
+fn range(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+        (min.min(v), max.max(v))
+    });
+    if min == max {
+        (min - 1.0, max + 1.0)
+    } else {
+        (min, max)
+    }
+}
+
+/// Format a value for an axis label, shorter for large values.
+fn label(v: f64) -> String {
+    if v.abs() >= 100.0 {
+        format!("{v:.0}")
+    } else {
+        format!("{v:.1}")
+    }
+}
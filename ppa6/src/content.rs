@@ -0,0 +1,382 @@
+//! Turning files into [`Document`]s by their content type.
+//!
+//! A [`Registry`] maps MIME types, file extensions, and magic bytes to [`ContentHandler`]s,
+//! so every frontend picks the same handler for the same file.
+//! Applications register handlers for the formats they can render, e.g. images or text,
+//! later registrations take precedence over earlier ones.
+
+use std::path::Path;
+
+use crate::{proto, Backend, Document, EmulatorBackend, Error, Model, Result};
+
+/// Renders one kind of content into a [`Document`].
+pub trait ContentHandler {
+    /// Short name for log messages, e.g. `"image"`.
+    fn name(&self) -> &str;
+
+    /// MIME types, that this handler renders, e.g. `"text/plain"`, or `"image/*"` for all images.
+    fn mime_types(&self) -> &[&str];
+
+    /// File extensions, that this handler renders, lowercase and without the dot.
+    fn extensions(&self) -> &[&str] {
+        &[]
+    }
+
+    /// Whether `data` looks like content of this handler, if neither the MIME type nor the extension is known.
+    fn sniff(&self, _data: &[u8]) -> bool {
+        false
+    }
+
+    /// Render `data` into a document, which is at most `width` pixels wide.
+    fn render(&self, data: &[u8], width: u16) -> Result<Document>;
+}
+
+/// Whether `mime` matches `pattern`, which may end in `/*`.
+fn mime_matches(pattern: &str, mime: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(kind) => mime
+            .split_once('/')
+            .is_some_and(|(k, _)| k.eq_ignore_ascii_case(kind)),
+        None => pattern.eq_ignore_ascii_case(mime),
+    }
+}
+
+/// A set of [`ContentHandler`]s, see the [module documentation](self).
+#[derive(Default)]
+pub struct Registry<'a> {
+    handlers: Vec<Box<dyn ContentHandler + 'a>>,
+}
+
+impl<'a> Registry<'a> {
+    /// Create a registry without handlers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a registry with the handlers of this crate, see [`EscPosHandler`] and [`PbmHandler`].
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        registry
+            .register(EscPosHandler::default())
+            .register(PbmHandler);
+        registry
+    }
+
+    /// Add `handler`, it takes precedence over the handlers registered before.
+    pub fn register(&mut self, handler: impl ContentHandler + 'a) -> &mut Self {
+        self.handlers.push(Box::new(handler));
+        self
+    }
+
+    fn handlers(&self) -> impl Iterator<Item = &(dyn ContentHandler + 'a)> {
+        self.handlers.iter().rev().map(|h| h.as_ref())
+    }
+
+    /// Find the handler for a MIME type, parameters like `; charset=utf-8` are ignored.
+    pub fn for_mime(&self, mime: &str) -> Option<&(dyn ContentHandler + 'a)> {
+        let mime = mime.split(';').next().unwrap_or(mime).trim();
+        self.handlers()
+            .find(|h| h.mime_types().iter().any(|p| mime_matches(p, mime)))
+    }
+
+    /// Find the handler for the extension of `path`.
+    pub fn for_path(&self, path: &Path) -> Option<&(dyn ContentHandler + 'a)> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        self.handlers()
+            .find(|h| h.extensions().contains(&ext.as_str()))
+    }
+
+    /// Find a handler, that recognizes `data`.
+    pub fn for_data(&self, data: &[u8]) -> Option<&(dyn ContentHandler + 'a)> {
+        self.handlers().find(|h| h.sniff(data))
+    }
+
+    /// Find the handler by the MIME type, then by the extension of `path`, then by sniffing `data`.
+    pub fn resolve(
+        &self,
+        data: &[u8],
+        mime: Option<&str>,
+        path: Option<&Path>,
+    ) -> Option<&(dyn ContentHandler + 'a)> {
+        mime.and_then(|m| self.for_mime(m))
+            .or_else(|| path.and_then(|p| self.for_path(p)))
+            .or_else(|| self.for_data(data))
+    }
+
+    /// Render `data` with the handler found by [`Registry::resolve()`].
+    pub fn render(
+        &self,
+        data: &[u8],
+        mime: Option<&str>,
+        path: Option<&Path>,
+        width: u16,
+    ) -> Result<Document> {
+        let Some(handler) = self.resolve(data, mime, path) else {
+            return Err(Error::InvalidArgument(format!(
+                "no handler for {}",
+                mime.unwrap_or("this content")
+            )));
+        };
+        log::debug!("rendering with the {} handler", handler.name());
+        handler.render(data, width)
+    }
+}
+
+/// Renders binary (`P4`) PBM bitmaps as they are, without scaling.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PbmHandler;
+
+impl ContentHandler for PbmHandler {
+    fn name(&self) -> &str {
+        "PBM"
+    }
+
+    fn mime_types(&self) -> &[&str] {
+        &["image/x-portable-bitmap"]
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["pbm"]
+    }
+
+    fn sniff(&self, data: &[u8]) -> bool {
+        data.starts_with(b"P4")
+    }
+
+    fn render(&self, data: &[u8], width: u16) -> Result<Document> {
+        let invalid = || Error::InvalidArgument("invalid PBM bitmap".into());
+        let mut rest = data.strip_prefix(b"P4").ok_or_else(invalid)?;
+
+        // width and height are separated by whitespace and comments, the pixels by a single whitespace
+        let mut header = [0usize; 2];
+        for value in &mut header {
+            loop {
+                rest = rest.trim_ascii_start();
+                match rest.strip_prefix(b"#") {
+                    Some(comment) => {
+                        let end = comment
+                            .iter()
+                            .position(|&b| b == b'\n')
+                            .unwrap_or(comment.len());
+                        rest = &comment[end..];
+                    }
+                    None => break,
+                }
+            }
+            let end = rest
+                .iter()
+                .position(|b| !b.is_ascii_digit())
+                .ok_or_else(invalid)?;
+            *value = std::str::from_utf8(&rest[..end])
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(invalid)?;
+            rest = &rest[end..];
+        }
+        let [w, h] = header;
+        rest = rest.get(1..).ok_or_else(invalid)?;
+        if w == 0 {
+            return Err(invalid());
+        }
+
+        if w > width as usize {
+            return Err(Error::InvalidArgument(format!(
+                "bitmap is {w}px wide, but at most {width}px fit"
+            )));
+        }
+        let row = w.div_ceil(8);
+        let rows = rest.get(..row * h).ok_or_else(invalid)?;
+
+        let mut doc = Document::blank(width, h)?;
+        let stride = doc.stride();
+        let pixels = doc.pixels_mut();
+        for (y, src) in rows.chunks(row).enumerate() {
+            pixels[y * stride..y * stride + row].copy_from_slice(src);
+            // clear the padding bits of the last byte
+            if w % 8 != 0 {
+                pixels[y * stride + row - 1] &= 0xff << (8 - w % 8);
+            }
+        }
+        Ok(doc)
+    }
+}
+
+/// Renders printer commands, e.g. a job written to a file instead of the printer,
+/// by running them through an [`EmulatorBackend`].
+///
+/// The emulator is the widest model, that fits into the width.
+/// Text is printed with the emulator's built-in font, unknown commands are skipped.
+/// Jobs feeding more than [`EscPosHandler::max_rows`] rows fail with [`Error::DocumentTooLong`],
+/// since a few bytes of `ESC J` can feed lots of paper.
+#[derive(Debug, Clone, Copy)]
+pub struct EscPosHandler {
+    /// Most rows of a document, default: 100000, about 12m of paper.
+    pub max_rows: usize,
+}
+
+impl Default for EscPosHandler {
+    fn default() -> Self {
+        Self { max_rows: 100_000 }
+    }
+}
+
+impl ContentHandler for EscPosHandler {
+    fn name(&self) -> &str {
+        "ESC/POS"
+    }
+
+    fn mime_types(&self) -> &[&str] {
+        &["application/vnd.escpos", "application/x-escpos"]
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["escpos", "prn"]
+    }
+
+    /// Jobs start with `ESC @`, or with the reset of [`Printer::reset()`](crate::Printer::reset()).
+    fn sniff(&self, data: &[u8]) -> bool {
+        data.starts_with(&[0x1b, 0x40]) || data.starts_with(&proto::RESET)
+    }
+
+    fn render(&self, data: &[u8], width: u16) -> Result<Document> {
+        let model = [Model::A6, Model::A6Plus, Model::A8, Model::A9, Model::A9Max]
+            .into_iter()
+            .filter(|m| m.capabilities().width <= width)
+            .min_by_key(|m| width - m.capabilities().width)
+            .ok_or_else(|| {
+                Error::InvalidArgument(format!("no printer is at most {width}px wide"))
+            })?;
+        let mut emulator = EmulatorBackend::new(model).with_max_rows(self.max_rows);
+        emulator.send(data, std::time::Duration::ZERO)?;
+        Ok(emulator.take_document())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pbm() {
+        let data = b"P4\n# made by hand\n# two lines\n12 2\n\xff\xff\x81\xff";
+        let doc = PbmHandler.render(data, 16).unwrap();
+        assert_eq!(doc.width(), 16);
+        // the padding bits of the last byte of each row are cleared
+        assert_eq!(doc.pixels(), [0xff, 0xf0, 0x81, 0xf0]);
+    }
+
+    #[test]
+    fn pbm_is_padded_to_the_width() {
+        let doc = PbmHandler.render(b"P4 8 1 \xaa", 384).unwrap();
+        assert_eq!(doc.width(), 384);
+        assert_eq!(doc.pixels()[0], 0xaa);
+        assert!(doc.pixels()[1..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn invalid_pbm() {
+        let invalid: &[&[u8]] = &[
+            b"P1\n8 1\n0",
+            b"P4",
+            b"P4 8",
+            b"P4 x 1 \x00",
+            b"P4 0 1 \x00",
+            b"P4 16 2 \x00\x00\x00",
+            b"P4 24 1 \x00\x00\x00",
+        ];
+        for data in invalid {
+            assert!(
+                PbmHandler.render(data, 16).is_err(),
+                "{}",
+                String::from_utf8_lossy(data)
+            );
+        }
+    }
+
+    #[test]
+    fn resolve() {
+        struct Text;
+        impl ContentHandler for Text {
+            fn name(&self) -> &str {
+                "text"
+            }
+            fn mime_types(&self) -> &[&str] {
+                &["text/*"]
+            }
+            fn extensions(&self) -> &[&str] {
+                &["txt", "pbm"]
+            }
+            fn render(&self, _data: &[u8], width: u16) -> Result<Document> {
+                Document::blank(width, 1)
+            }
+        }
+
+        let mut registry = Registry::with_builtins();
+        registry.register(Text);
+        let name = |h: Option<&dyn ContentHandler>| h.map(|h| h.name().to_owned());
+
+        assert_eq!(
+            name(registry.for_mime("TEXT/plain; charset=utf-8")),
+            Some("text".into())
+        );
+        assert_eq!(
+            name(registry.for_mime("image/x-portable-bitmap")),
+            Some("PBM".into())
+        );
+        assert_eq!(name(registry.for_mime("image/png")), None);
+        // later registrations win
+        assert_eq!(
+            name(registry.for_path(Path::new("a.PBM"))),
+            Some("text".into())
+        );
+        assert_eq!(name(registry.for_data(b"P4 1 1 \x00")), Some("PBM".into()));
+        // the MIME type comes before the extension and the content
+        let pbm = b"P4 1 1 \x00";
+        let found = registry.resolve(
+            pbm,
+            Some("image/x-portable-bitmap"),
+            Some(Path::new("a.txt")),
+        );
+        assert_eq!(name(found), Some("PBM".into()));
+        assert!(registry.render(b"\x00", None, None, 384).is_err());
+    }
+
+    #[test]
+    fn escpos() {
+        let mut data = vec![0x1b, 0x40];
+        proto::Command::Image {
+            width: 384,
+            pixels: &[0xff; 48 * 2],
+        }
+        .encode(&mut data)
+        .unwrap();
+        data.extend([0x1b, 0x4a, 3]);
+
+        let registry = Registry::with_builtins();
+        let doc = registry.render(&data, None, None, 400).unwrap();
+        assert_eq!(doc.width(), 384);
+        assert_eq!(doc.height(), 5);
+        assert!(!doc.is_blank_row(1));
+        assert!(doc.is_blank_row(2));
+
+        let found = registry.resolve(&proto::RESET, None, None);
+        assert_eq!(found.map(|h| h.name()), Some("ESC/POS"));
+        assert!(EscPosHandler::default().render(&data, 256).is_err());
+    }
+
+    #[test]
+    fn escpos_feed_is_limited() {
+        let handler = EscPosHandler { max_rows: 1000 };
+        let data = [[0x1b, 0x40].as_slice(), &[0x1b, 0x4a, 0xff].repeat(3)].concat();
+        assert_eq!(handler.render(&data, 384).unwrap().height(), 3 * 255);
+
+        let data = [[0x1b, 0x40].as_slice(), &[0x1b, 0x4a, 0xff].repeat(1000)].concat();
+        assert!(matches!(
+            handler.render(&data, 384),
+            Err(Error::DocumentTooLong {
+                rows: 1020,
+                max: 1000
+            })
+        ));
+    }
+}
@@ -0,0 +1,268 @@
+use std::ops::Range;
+
+use crate::{draw::Canvas, font, Error, Result};
+
+/// Glyph scale of [`Document::stamp()`], glyphs are 10 rows high.
+const STAMP_SCALE: f32 = 2.0;
+
+/// A monochrome raster image, in the pixel format of [`Printer::print_image()`](crate::Printer::print_image).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Document {
+    pixels: Vec<u8>,
+    width: u16,
+}
+
+/// A run of rows in a [`Document`], see [`Document::bands()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Band {
+    /// Rows that contain black pixels and have to be printed.
+    Ink(Range<usize>),
+
+    /// A number of completely white rows, which can be fed instead of printed.
+    Blank(usize),
+}
+
+impl Document {
+    /// Create a document from packed pixels, `width` must be non-zero and divisible by 8.
+    pub fn new(pixels: Vec<u8>, width: u16) -> Result<Self> {
+        if width == 0 || !width.is_multiple_of(8) {
+            return Err(Error::InvalidArgument(
+                "width must be non-zero and divisible by 8".into(),
+            ));
+        }
+
+        if !pixels.len().is_multiple_of(width as usize / 8) {
+            return Err(Error::InvalidArgument(format!(
+                "invalid length of pixels: {}",
+                pixels.len()
+            )));
+        }
+
+        Ok(Self { pixels, width })
+    }
+
+    /// Create a white document of `height` rows.
+    pub fn blank(width: u16, height: usize) -> Result<Self> {
+        Self::new(vec![0; width as usize / 8 * height], width)
+    }
+
+    /// Width in pixels.
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    /// Number of bytes in a row.
+    pub fn stride(&self) -> usize {
+        self.width as usize / 8
+    }
+
+    /// Height in pixels.
+    pub fn height(&self) -> usize {
+        self.pixels.len() / self.stride()
+    }
+
+    /// All packed pixels.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub(crate) fn pixels_mut(&mut self) -> &mut [u8] {
+        &mut self.pixels
+    }
+
+    /// Get the packed pixels of the rows in `rows`.
+    pub fn rows(&self, rows: Range<usize>) -> &[u8] {
+        &self.pixels[rows.start * self.stride()..rows.end * self.stride()]
+    }
+
+    /// Check if row `y` is completely white.
+    pub fn is_blank_row(&self, y: usize) -> bool {
+        self.rows(y..y + 1).iter().all(|&b| b == 0)
+    }
+
+    /// Remove blank rows at the top and bottom, but keep margins of `top` and `bottom` blank rows.
+    /// A completely blank document becomes empty.
+    pub fn trim_whitespace(&mut self, top: usize, bottom: usize) {
+        let h = self.height();
+        let Some(first) = (0..h).find(|&y| !self.is_blank_row(y)) else {
+            self.pixels.clear();
+            return;
+        };
+        let last = (0..h).rev().find(|&y| !self.is_blank_row(y)).unwrap();
+
+        let start = first.saturating_sub(top);
+        let end = (last + 1 + bottom).min(h);
+        let stride = self.stride();
+        self.pixels.truncate(end * stride);
+        self.pixels.drain(..start * stride);
+    }
+
+    /// Split the document into runs of printed and blank rows.
+    /// Blank runs shorter than `min_blank` rows are kept in the surrounding [`Band::Ink`],
+    /// as printing them is cheaper than an extra feed command.
+    pub fn bands(&self, min_blank: usize) -> Vec<Band> {
+        let mut bands = Vec::new();
+        let mut ink_start = 0;
+        let mut y = 0;
+        let h = self.height();
+
+        while y < h {
+            if !self.is_blank_row(y) {
+                y += 1;
+                continue;
+            }
+
+            let start = y;
+            while y < h && self.is_blank_row(y) {
+                y += 1;
+            }
+
+            // leading and trailing whitespace is always worth skipping
+            if y - start >= min_blank || start == 0 || y == h {
+                if ink_start < start {
+                    bands.push(Band::Ink(ink_start..start));
+                }
+                bands.push(Band::Blank(y - start));
+                ink_start = y;
+            }
+        }
+
+        if ink_start < h {
+            bands.push(Band::Ink(ink_start..h));
+        }
+        bands
+    }
+
+    /// Draw shapes onto the document, see [`Canvas`].
+    /// Pixels covered by at least `threshold` (between `0.0` and `1.0`) become black,
+    /// `0.5` is a good default, lower values make thin lines bolder.
+    pub fn draw(&mut self, threshold: f32, f: impl FnOnce(&mut Canvas)) {
+        let mut canvas = Canvas::new(self.width() as usize, self.height());
+        f(&mut canvas);
+
+        let stride = self.stride();
+        let (xs, ys) = canvas.bounds();
+        for y in ys {
+            for x in xs.clone() {
+                if canvas.coverage(x, y) >= threshold {
+                    self.pixels[y * stride + x / 8] |= 0x80 >> (x % 8);
+                }
+            }
+        }
+    }
+
+    /// Stamp `text` in small letters into the top right corner of the first non-blank row,
+    /// e.g. a job ID, to match printed strips back to the job log, see [`PrintOptions::stamp`](crate::PrintOptions::stamp).
+    /// The stamp is printed on a white box, so it stays readable on dark content.
+    pub fn stamp(&mut self, text: &str) {
+        let top = (0..self.height())
+            .find(|&y| !self.is_blank_row(y))
+            .unwrap_or(0);
+        let x = self.width() as f32 - font::width(text, STAMP_SCALE) - STAMP_SCALE;
+
+        // clear the box, with a margin of one glyph pixel at the left and bottom
+        let left = (x - STAMP_SCALE).max(0.0) as usize;
+        let bottom = top + ((font::HEIGHT + 1.0) * STAMP_SCALE) as usize;
+        let stride = self.stride();
+        for y in top..bottom.min(self.height()) {
+            for x in left..self.width() as usize {
+                self.pixels[y * stride + x / 8] &= !(0x80 >> (x % 8));
+            }
+        }
+
+        self.draw(0.5, |c| {
+            font::draw(c, (x.max(0.0), top as f32), STAMP_SCALE, text)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A document 16px wide, with the rows in `ink` black.
+    fn doc(height: usize, ink: &[usize]) -> Document {
+        let mut doc = Document::blank(16, height).unwrap();
+        for &y in ink {
+            doc.pixels_mut()[y * 2..y * 2 + 2].fill(0xff);
+        }
+        doc
+    }
+
+    #[test]
+    fn invalid_documents() {
+        assert!(Document::new(vec![0; 4], 0).is_err());
+        assert!(Document::new(vec![0; 4], 12).is_err());
+        assert!(Document::new(vec![0; 3], 16).is_err());
+    }
+
+    #[test]
+    fn trim_whitespace() {
+        let mut d = doc(10, &[3, 5]);
+        d.trim_whitespace(1, 2);
+        assert_eq!(d, doc(6, &[1, 3]));
+
+        // margins can't add rows
+        let mut d = doc(10, &[0, 9]);
+        d.trim_whitespace(5, 5);
+        assert_eq!(d, doc(10, &[0, 9]));
+
+        let mut d = doc(10, &[]);
+        d.trim_whitespace(1, 1);
+        assert_eq!(d.height(), 0);
+    }
+
+    #[test]
+    fn bands() {
+        let d = doc(20, &[2, 3, 5, 15]);
+        assert_eq!(
+            d.bands(3),
+            [
+                Band::Blank(2),
+                Band::Ink(2..6),
+                Band::Blank(9),
+                Band::Ink(15..16),
+                Band::Blank(4),
+            ]
+        );
+        assert_eq!(doc(3, &[0, 1, 2]).bands(1), [Band::Ink(0..3)]);
+        assert_eq!(doc(3, &[]).bands(24), [Band::Blank(3)]);
+    }
+
+    #[test]
+    fn stamp() {
+        let mut doc = Document::blank(384, 40).unwrap();
+        doc.pixels_mut()[10 * 48] = 0x80;
+        let before = doc.clone();
+        doc.stamp("JOB-1");
+        assert_ne!(doc, before);
+
+        // the stamp starts at the first printed row, and stays in the right half
+        let top = (font::HEIGHT * STAMP_SCALE) as usize;
+        for y in 0..doc.height() {
+            let row = doc.rows(y..y + 1);
+            let inside = (10..10 + top).contains(&y);
+            for (x, (a, b)) in row.iter().zip(before.rows(y..y + 1)).enumerate() {
+                if a != b {
+                    assert!(inside && x >= 24, "stamp at byte {x} of row {y}");
+                }
+            }
+        }
+        assert!(doc.rows(10..10 + top)[24..].iter().any(|&b| b != 0));
+    }
+
+    #[test]
+    fn stamp_on_black() {
+        let mut doc = doc(40, &(0..40).collect::<Vec<_>>());
+        doc.stamp("1");
+
+        // a white box with the stamp in it, and the rest untouched
+        let bottom = ((font::HEIGHT + 1.0) * STAMP_SCALE) as usize;
+        let stamp = doc.rows(0..bottom);
+        assert!(stamp.iter().any(|&b| b != 0xff));
+        assert!(stamp.iter().any(|&b| b != 0x00 && b != 0xff));
+        assert_eq!(stamp[bottom * 2 - 1] & 0x01, 0);
+        assert!(doc.rows(bottom..40).iter().all(|&b| b == 0xff));
+        assert!(stamp.chunks(2).all(|row| row[0] & 0xf0 == 0xf0));
+    }
+}
@@ -0,0 +1,347 @@
+//! Vector graphics, like charts or logos, drawn straight into a [`Document`](crate::Document).
+//!
+//! Shapes are rasterized with 4x4 supersampling into a [`Canvas`],
+//! whose coverage is then thresholded into black and white, see [`Document::draw()`](crate::Document::draw()).
+//! Coordinates are in pixels, with `(0, 0)` at the top left corner of the top left pixel.
+//! Only the bounding box of the shapes drawn so far is stored, so a small drawing on a long document stays cheap.
+
+use std::ops::Range;
+
+/// Subsamples per pixel, in each direction.
+const SS: usize = 4;
+
+/// Longest line segment, in pixels, when flattening curves.
+const FLATNESS: f32 = 2.0;
+
+/// A point in pixels.
+pub type Point = (f32, f32);
+
+/// A layer for drawing shapes, see [`Document::draw()`](crate::Document::draw()).
+#[derive(Debug, Clone)]
+pub struct Canvas {
+    width: usize,
+    height: usize,
+    /// Columns of subsamples, that are stored.
+    xs: Range<usize>,
+    /// Rows of subsamples, that are stored.
+    ys: Range<usize>,
+    samples: Vec<bool>,
+}
+
+impl Canvas {
+    /// Create an empty canvas.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            xs: 0..0,
+            ys: 0..0,
+            samples: Vec::new(),
+        }
+    }
+
+    /// Width in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Height in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The pixels, that may be covered by shapes, as columns and rows.
+    /// Everything outside is blank.
+    pub fn bounds(&self) -> (Range<usize>, Range<usize>) {
+        (
+            self.xs.start / SS..self.xs.end.div_ceil(SS),
+            self.ys.start / SS..self.ys.end.div_ceil(SS),
+        )
+    }
+
+    /// Fraction of pixel `(x, y)` covered by shapes, between `0.0` and `1.0`.
+    pub fn coverage(&self, x: usize, y: usize) -> f32 {
+        let n = (0..SS)
+            .flat_map(|sy| (0..SS).map(move |sx| (x * SS + sx, y * SS + sy)))
+            .filter(|&(sx, sy)| self.get(sx, sy))
+            .count();
+        n as f32 / (SS * SS) as f32
+    }
+
+    fn get(&self, sx: usize, sy: usize) -> bool {
+        self.xs.contains(&sx)
+            && self.ys.contains(&sy)
+            && self.samples[(sy - self.ys.start) * self.xs.len() + sx - self.xs.start]
+    }
+
+    /// Subsample rows or columns between `min` and `max` pixels, clamped to `0..len` subsamples.
+    fn span(min: f32, max: f32, len: usize) -> Range<usize> {
+        let start = ((min * SS as f32).floor().max(0.0) as usize).min(len);
+        let end = ((max * SS as f32).ceil().max(0.0) as usize).min(len);
+        start..end
+    }
+
+    /// Grow the stored area to include the subsamples `xs` by `ys`.
+    /// It at least doubles in every direction it grows, so drawing many shapes doesn't copy it over and over.
+    fn reserve(&mut self, xs: Range<usize>, ys: Range<usize>) {
+        if xs.is_empty() || ys.is_empty() {
+            return;
+        }
+        if self.samples.is_empty() {
+            self.samples = vec![false; xs.len() * ys.len()];
+            (self.xs, self.ys) = (xs, ys);
+            return;
+        }
+
+        fn grow(old: &Range<usize>, new: Range<usize>, len: usize) -> Range<usize> {
+            let mut r = old.clone();
+            if new.start < old.start {
+                r.start = new.start.min(old.start.saturating_sub(old.len()));
+            }
+            if new.end > old.end {
+                r.end = new.end.max(old.end + old.len()).min(len);
+            }
+            r
+        }
+        let new_xs = grow(&self.xs, xs, self.width * SS);
+        let new_ys = grow(&self.ys, ys, self.height * SS);
+        if new_xs == self.xs && new_ys == self.ys {
+            return;
+        }
+
+        let mut samples = vec![false; new_xs.len() * new_ys.len()];
+        let (old_w, new_w) = (self.xs.len(), new_xs.len());
+        let dx = self.xs.start - new_xs.start;
+        for sy in self.ys.clone() {
+            let old = (sy - self.ys.start) * old_w;
+            let new = (sy - new_ys.start) * new_w + dx;
+            samples[new..new + old_w].copy_from_slice(&self.samples[old..old + old_w]);
+        }
+        (self.xs, self.ys, self.samples) = (new_xs, new_ys, samples);
+    }
+
+    /// Set the subsamples `xs` of row `sy`, which must have been reserved.
+    fn set_span(&mut self, sy: usize, xs: Range<usize>) {
+        let row = (sy - self.ys.start) * self.xs.len();
+        let (start, end) = (xs.start - self.xs.start, xs.end - self.xs.start);
+        self.samples[row + start..row + end].fill(true);
+    }
+
+    /// Set every subsample within the bounding box, for which `inside` returns true.
+    fn fill_where(&mut self, min: Point, max: Point, inside: impl Fn(f32, f32) -> bool) {
+        let xs = Self::span(min.0, max.0, self.width * SS);
+        let ys = Self::span(min.1, max.1, self.height * SS);
+        self.reserve(xs.clone(), ys.clone());
+        for sy in ys {
+            let y = (sy as f32 + 0.5) / SS as f32;
+            for sx in xs.clone() {
+                let x = (sx as f32 + 0.5) / SS as f32;
+                if inside(x, y) {
+                    self.set_span(sy, sx..sx + 1);
+                }
+            }
+        }
+    }
+
+    /// Fill a polygon, using the non-zero winding rule.
+    pub fn fill_polygon(&mut self, points: &[Point]) {
+        if points.len() < 3 {
+            return;
+        }
+
+        let w = self.width * SS;
+        let min = |f: fn(&Point) -> f32| points.iter().map(f).fold(f32::INFINITY, f32::min);
+        let max = |f: fn(&Point) -> f32| points.iter().map(f).fold(f32::NEG_INFINITY, f32::max);
+        let xs = Self::span(min(|p| p.0), max(|p| p.0), w);
+        let ys = Self::span(min(|p| p.1), max(|p| p.1), self.height * SS);
+        self.reserve(xs.clone(), ys.clone());
+
+        let mut crossings = Vec::new();
+        for sy in ys {
+            let y = (sy as f32 + 0.5) / SS as f32;
+            crossings.clear();
+            for (i, &(ax, ay)) in points.iter().enumerate() {
+                let (bx, by) = points[(i + 1) % points.len()];
+                if (ay <= y) == (by <= y) {
+                    continue;
+                }
+                let x = ax + (y - ay) / (by - ay) * (bx - ax);
+                crossings.push((x, if by > ay { 1 } else { -1 }));
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += pair[0].1;
+                if winding == 0 {
+                    continue;
+                }
+                let start = ((pair[0].0 * SS as f32 - 0.5).ceil().max(0.0) as usize).min(w);
+                let end = ((pair[1].0 * SS as f32 - 0.5).ceil().max(0.0) as usize).min(w);
+                if start < end {
+                    self.set_span(sy, start..end);
+                }
+            }
+        }
+    }
+
+    /// Fill the rectangle between the corners `min` and `max`.
+    pub fn fill_rect(&mut self, min: Point, max: Point) {
+        self.fill_where(min, max, |x, y| {
+            x >= min.0 && x < max.0 && y >= min.1 && y < max.1
+        });
+    }
+
+    /// Fill a circle around `center`.
+    pub fn fill_circle(&mut self, center: Point, radius: f32) {
+        let (cx, cy) = center;
+        self.fill_where(
+            (cx - radius, cy - radius),
+            (cx + radius, cy + radius),
+            |x, y| (x - cx).powi(2) + (y - cy).powi(2) <= radius * radius,
+        );
+    }
+
+    /// Draw the outline of a circle around `center`, with a line `width` pixels wide.
+    pub fn stroke_circle(&mut self, center: Point, radius: f32, width: f32) {
+        let (cx, cy) = center;
+        let (inner, outer) = ((radius - width / 2.0).max(0.0), radius + width / 2.0);
+        self.fill_where(
+            (cx - outer, cy - outer),
+            (cx + outer, cy + outer),
+            |x, y| {
+                let d = (x - cx).powi(2) + (y - cy).powi(2);
+                d >= inner * inner && d <= outer * outer
+            },
+        );
+    }
+
+    /// Draw connected lines through `points`, `width` pixels wide, with round joins and caps.
+    pub fn stroke_polyline(&mut self, points: &[Point], width: f32) {
+        let r = width / 2.0;
+        for &p in points {
+            self.fill_circle(p, r);
+        }
+        for pair in points.windows(2) {
+            let ((ax, ay), (bx, by)) = (pair[0], pair[1]);
+            let len = ((bx - ax).powi(2) + (by - ay).powi(2)).sqrt();
+            if len == 0.0 {
+                continue;
+            }
+            let (nx, ny) = (-(by - ay) / len * r, (bx - ax) / len * r);
+            self.fill_polygon(&[
+                (ax + nx, ay + ny),
+                (bx + nx, by + ny),
+                (bx - nx, by - ny),
+                (ax - nx, ay - ny),
+            ]);
+        }
+    }
+
+    /// Draw the outline of a polygon, `width` pixels wide.
+    pub fn stroke_polygon(&mut self, points: &[Point], width: f32) {
+        let mut closed = points.to_vec();
+        closed.extend(points.first());
+        self.stroke_polyline(&closed, width);
+    }
+
+    /// Draw a line from `a` to `b`, `width` pixels wide.
+    pub fn line(&mut self, a: Point, b: Point, width: f32) {
+        self.stroke_polyline(&[a, b], width);
+    }
+
+    /// Draw an arc of a circle around `center`, from angle `start` to `end` in radians,
+    /// clockwise starting at 3 o'clock, because y grows downwards.
+    pub fn stroke_arc(&mut self, center: Point, radius: f32, start: f32, end: f32, width: f32) {
+        let n = segments((end - start).abs() * radius);
+        let points: Vec<Point> = (0..=n)
+            .map(|i| {
+                let a = start + (end - start) * i as f32 / n as f32;
+                (center.0 + radius * a.cos(), center.1 + radius * a.sin())
+            })
+            .collect();
+        self.stroke_polyline(&points, width);
+    }
+
+    /// Draw a quadratic Bézier curve from `p0` to `p2`, with control point `p1`.
+    pub fn stroke_quad(&mut self, p0: Point, p1: Point, p2: Point, width: f32) {
+        let n = segments(distance(p0, p1) + distance(p1, p2));
+        let points: Vec<Point> = (0..=n)
+            .map(|i| {
+                let t = i as f32 / n as f32;
+                let u = 1.0 - t;
+                (
+                    u * u * p0.0 + 2.0 * u * t * p1.0 + t * t * p2.0,
+                    u * u * p0.1 + 2.0 * u * t * p1.1 + t * t * p2.1,
+                )
+            })
+            .collect();
+        self.stroke_polyline(&points, width);
+    }
+
+    /// Draw a cubic Bézier curve from `p0` to `p3`, with control points `p1` and `p2`.
+    pub fn stroke_cubic(&mut self, p0: Point, p1: Point, p2: Point, p3: Point, width: f32) {
+        let n = segments(distance(p0, p1) + distance(p1, p2) + distance(p2, p3));
+        let points: Vec<Point> = (0..=n)
+            .map(|i| {
+                let t = i as f32 / n as f32;
+                let u = 1.0 - t;
+                let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+                (
+                    a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0,
+                    a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1,
+                )
+            })
+            .collect();
+        self.stroke_polyline(&points, width);
+    }
+}
+
+fn distance(a: Point, b: Point) -> f32 {
+    ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt()
+}
+
+/// Number of line segments for a curve of about `length` pixels.
+fn segments(length: f32) -> usize {
+    ((length / FLATNESS).ceil() as usize).clamp(1, 10_000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stores_only_the_drawn_area() {
+        let mut c = Canvas::new(384, 100_000);
+        assert_eq!(c.bounds(), (0..0, 0..0));
+
+        c.fill_rect((10.0, 50_000.0), (12.0, 50_002.0));
+        assert_eq!(c.bounds(), (10..12, 50_000..50_002));
+        assert_eq!(c.samples.len(), 2 * SS * 2 * SS);
+
+        // growing keeps what was drawn before
+        c.fill_polygon(&[
+            (100.0, 49_990.0),
+            (104.0, 49_990.0),
+            (104.0, 49_994.0),
+            (100.0, 49_994.0),
+        ]);
+        let (xs, ys) = c.bounds();
+        assert!(xs.contains(&10) && xs.contains(&103));
+        assert!(ys.contains(&49_990) && ys.contains(&50_001));
+        assert_eq!(c.coverage(11, 50_001), 1.0);
+        assert_eq!(c.coverage(102, 49_992), 1.0);
+        assert_eq!(c.coverage(50, 49_995), 0.0);
+        assert_eq!(c.coverage(0, 0), 0.0);
+    }
+
+    #[test]
+    fn clamps_to_the_canvas() {
+        let mut c = Canvas::new(16, 16);
+        c.fill_circle((-5.0, -5.0), 2.0);
+        assert_eq!(c.bounds(), (0..0, 0..0));
+        c.fill_rect((-4.0, 14.0), (40.0, 40.0));
+        assert_eq!(c.bounds(), (0..16, 14..16));
+        assert_eq!(c.coverage(15, 15), 1.0);
+    }
+}
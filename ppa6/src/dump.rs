@@ -0,0 +1,183 @@
+use std::{
+    fmt::{self, Display, Formatter, Write},
+    time::Duration,
+};
+
+use crate::proto;
+
+/// Bytes shown of payloads and responses.
+const LIMIT: usize = 64;
+
+/// Target for pixel and text payloads, which are only logged at the trace level.
+const PAYLOAD_TARGET: &str = "ppa6::payload";
+
+/// Known commands, more specific prefixes first, with the length of their header.
+/// Anything after the header is payload.
+const OPCODES: &[(&[u8], &str, usize)] = &[
+    (&proto::RESET, "reset", usize::MAX),
+    (&proto::END_OF_IMAGE, "end-of-image", usize::MAX),
+    (&proto::SET_CONCENTRATION, "set-concentration", usize::MAX),
+    (&proto::GET_IP, "get-ip", usize::MAX),
+    (&proto::GET_FIRMWARE_VER, "get-firmware-ver", usize::MAX),
+    (&proto::GET_SERIAL, "get-serial", usize::MAX),
+    (&proto::GET_HARDWARE_VER, "get-hardware-ver", usize::MAX),
+    (&proto::GET_NAME, "get-name", usize::MAX),
+    (&proto::GET_MAC, "get-mac", usize::MAX),
+    (&proto::GET_BATTERY, "get-battery", usize::MAX),
+    (&proto::GET_STATUS, "get-status", usize::MAX),
+    (&proto::PRINT_IMAGE, "print-image", 8),
+    (&proto::PUSH, "push", usize::MAX),
+];
+
+/// Which bytes of the reply to `query` are hidden, because they identify the printer.
+/// MAC addresses keep their vendor prefix, which tells apart the radio modules, serial numbers are hidden entirely.
+fn redacted(query: &[u8]) -> fn(usize) -> bool {
+    if query.starts_with(&proto::GET_MAC) {
+        // the printer sends the address twice
+        |i| i % 6 >= 3
+    } else if query.starts_with(&proto::GET_SERIAL) {
+        |_| true
+    } else {
+        |_| false
+    }
+}
+
+/// Name and header length of the command in `buf`.
+fn opcode(buf: &[u8]) -> (&'static str, usize) {
+    OPCODES
+        .iter()
+        .find(|(prefix, _, _)| buf.starts_with(prefix))
+        .map(|&(_, name, header)| (name, header.min(buf.len())))
+        .unwrap_or_else(|| {
+            if buf
+                .iter()
+                .all(|b| b.is_ascii_graphic() || b.is_ascii_whitespace())
+            {
+                ("text", 0)
+            } else {
+                ("unknown", buf.len())
+            }
+        })
+}
+
+/// Hexdump in the style of `hexdump -C`, cut off after `limit` bytes.
+/// Bytes at the offsets, for which `hidden` returns true, are shown as `xx`.
+struct HexDump<'a> {
+    buf: &'a [u8],
+    limit: usize,
+    hidden: fn(usize) -> bool,
+}
+
+impl<'a> HexDump<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self {
+            buf,
+            limit: LIMIT,
+            hidden: |_| false,
+        }
+    }
+}
+
+impl Display for HexDump<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let shown = &self.buf[..self.buf.len().min(self.limit)];
+        for (i, line) in shown.chunks(16).enumerate() {
+            write!(f, "\n  {:08x}  ", i * 16)?;
+            for (j, b) in line.iter().enumerate() {
+                if (self.hidden)(i * 16 + j) {
+                    f.write_str("xx ")?;
+                } else {
+                    write!(f, "{b:02x} ")?;
+                }
+            }
+            for _ in line.len()..16 {
+                f.write_str("   ")?;
+            }
+            f.write_str(" |")?;
+            for (j, &b) in line.iter().enumerate() {
+                let ch = if (self.hidden)(i * 16 + j) {
+                    'x'
+                } else if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                };
+                f.write_char(ch)?;
+            }
+            f.write_char('|')?;
+        }
+        if shown.len() < self.buf.len() {
+            write!(f, "\n  ... {} more bytes", self.buf.len() - shown.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Log a packet sent to the printer.
+/// Only the command is dumped, the payload is summarized and dumped to [`PAYLOAD_TARGET`].
+pub(crate) fn sent(buf: &[u8], timeout: Duration) {
+    let (name, header) = opcode(buf);
+    let (header, payload) = buf.split_at(header);
+    if log::log_enabled!(log::Level::Debug) {
+        let dump = HexDump::new(header);
+        match payload.len() {
+            0 => log::debug!("send {name} ({timeout:?}):{dump}"),
+            n => log::debug!("send {name} with {n} bytes of payload ({timeout:?}):{dump}"),
+        }
+    }
+    if !payload.is_empty() && log::log_enabled!(target: PAYLOAD_TARGET, log::Level::Trace) {
+        let dump = HexDump::new(payload);
+        log::trace!(target: PAYLOAD_TARGET, "{name} payload:{dump}");
+    }
+}
+
+/// Log pixels following an image header, which are all payload.
+pub(crate) fn pixels(buf: &[u8], timeout: Duration) {
+    log::debug!("send {} bytes of pixels ({timeout:?})", buf.len());
+    if log::log_enabled!(target: PAYLOAD_TARGET, log::Level::Trace) {
+        let dump = HexDump::new(buf);
+        log::trace!(target: PAYLOAD_TARGET, "pixels:{dump}");
+    }
+}
+
+/// Log a response of the printer to `query`, with MAC addresses and serial numbers redacted.
+pub(crate) fn received(query: &[u8], buf: &[u8], timeout: Duration) {
+    if log::log_enabled!(log::Level::Debug) {
+        let dump = HexDump {
+            hidden: redacted(query),
+            ..HexDump::new(buf)
+        };
+        log::debug!("recv ({} bytes, {timeout:?}):{dump}", buf.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dump(query: &[u8], buf: &[u8]) -> String {
+        HexDump {
+            hidden: redacted(query),
+            ..HexDump::new(buf)
+        }
+        .to_string()
+    }
+
+    #[test]
+    fn redacts_mac_and_serial() {
+        let mac = [0x11, 0x22, 0x33, 0x44, 0x55, 0x66];
+        let reply = [mac, mac].concat();
+        assert_eq!(
+            dump(&proto::GET_MAC, &reply),
+            "\n  00000000  11 22 33 xx xx xx 11 22 33 xx xx xx              |.\"3xxx.\"3xxx|"
+        );
+        assert_eq!(
+            dump(&proto::GET_SERIAL, b"A6123"),
+            "\n  00000000  xx xx xx xx xx                                   |xxxxx|"
+        );
+        assert_eq!(
+            dump(&proto::GET_NAME, b"A6"),
+            "\n  00000000  41 36                                            |A6|"
+        );
+    }
+}
@@ -0,0 +1,233 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{font, proto, Backend, Document, Error, Model, Result};
+
+/// Glyph scale of text, glyphs are 12x24 pixels including spacing, like the printer's font.
+const TEXT_SCALE: f32 = 3.0;
+
+/// Rows of a line of text.
+const LINE_HEIGHT: usize = 24;
+
+/// Fake MAC address, the printer sends it twice.
+const MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0xa6];
+
+struct State {
+    model: Model,
+    /// Received bytes, that don't form a complete command yet.
+    pending: Vec<u8>,
+    /// Packed pixels of the paper, as wide as the print head.
+    paper: Vec<u8>,
+    /// Text, that wasn't ended by a newline yet.
+    line: String,
+    response: Option<Vec<u8>>,
+    concentration: Option<u8>,
+    /// Most rows of paper, see [`EmulatorBackend::with_max_rows()`].
+    max_rows: usize,
+}
+
+/// A virtual printer, that renders the commands it receives into an image in memory,
+/// to print end-to-end without a device, e.g. in CI or for demos.
+///
+/// Queries are answered with fake data for `model`, and the status is always fine.
+/// Clones share their state, so keep one to look at the paper with [`EmulatorBackend::document()`],
+/// after the other was moved into a [`Printer`](crate::Printer).
+#[derive(Clone)]
+pub struct EmulatorBackend {
+    state: Arc<Mutex<State>>,
+}
+
+impl EmulatorBackend {
+    /// Create a virtual printer of `model` with blank paper.
+    pub fn new(model: Model) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                model,
+                pending: Vec::new(),
+                paper: Vec::new(),
+                line: String::new(),
+                response: None,
+                concentration: None,
+                max_rows: usize::MAX,
+            })),
+        }
+    }
+
+    /// Fail with [`Error::DocumentTooLong`] instead of printing more than `rows` rows,
+    /// e.g. when rendering untrusted input, where a few bytes can feed lots of paper.
+    /// There is no limit by default.
+    pub fn with_max_rows(self, rows: usize) -> Self {
+        self.state.lock().unwrap().max_rows = rows;
+        self
+    }
+
+    /// Everything printed so far, as wide as the print head.
+    pub fn document(&self) -> Document {
+        let state = self.state.lock().unwrap();
+        Document::new(state.paper.clone(), state.model.capabilities().width)
+            .expect("paper has whole rows")
+    }
+
+    /// Like [`EmulatorBackend::document()`], but start over with blank paper,
+    /// e.g. to get each job as a separate document.
+    pub fn take_document(&self) -> Document {
+        let mut state = self.state.lock().unwrap();
+        let paper = std::mem::take(&mut state.paper);
+        Document::new(paper, state.model.capabilities().width).expect("paper has whole rows")
+    }
+
+    /// The last concentration set, see [`Printer::set_concentration()`](crate::Printer::set_concentration()).
+    pub fn concentration(&self) -> Option<u8> {
+        self.state.lock().unwrap().concentration
+    }
+}
+
+impl Default for EmulatorBackend {
+    fn default() -> Self {
+        Self::new(Model::default())
+    }
+}
+
+/// Length of the command at the start of `buf`, `None` if it's incomplete.
+fn command_len(buf: &[u8]) -> Option<usize> {
+    let len = match buf {
+        [0x10, 0xff, 0xfe, 0x01, ..] => proto::RESET.len(),
+        [0x10, 0xff, 0x10, 0x00, ..] => proto::SET_CONCENTRATION.len() + 1,
+        [0x10, 0xff, ..] => 4,
+        [0x10, 0x04, ..] | [0x1b, 0x4a, ..] => 3,
+        [0x1b, 0x40, ..] => 2,
+        [0x1d, 0x76, 0x30, _, xl, xh, yl, yh, ..] => {
+            let (w, h) = (
+                u16::from_le_bytes([*xl, *xh]),
+                u16::from_le_bytes([*yl, *yh]),
+            );
+            8 + w as usize * h as usize
+        }
+        [0x10] | [0x1b] | [0x1d] | [0x1d, 0x76] | [0x1d, 0x76, 0x30, ..] => return None,
+        _ => 1,
+    };
+    (buf.len() >= len).then_some(len)
+}
+
+impl State {
+    fn stride(&self) -> usize {
+        self.model.capabilities().width as usize / 8
+    }
+
+    /// Check, that `rows` more rows fit on the paper.
+    fn check_rows(&self, rows: usize) -> Result<()> {
+        let total = self.paper.len() / self.stride() + rows;
+        if total > self.max_rows {
+            return Err(Error::DocumentTooLong {
+                rows: total,
+                max: self.max_rows,
+            });
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, cmd: &[u8]) -> Result<()> {
+        match cmd {
+            [0x10, 0xff, 0xfe, 0x01, ..] => {
+                self.line.clear();
+                self.response = None;
+            }
+            [0x10, 0xff, 0xfe, 0x45] => {}
+            [0x10, 0xff, 0x10, 0x00, c] => self.concentration = Some(*c),
+            [0x10, 0xff, ..] => self.response = self.answer(cmd),
+            [0x10, 0x04, _] => self.response = Some(vec![0x12]),
+            [0x1b, 0x40] => self.line.clear(),
+            [0x1b, 0x4a, n] => {
+                self.check_rows(*n as usize)?;
+                let len = self.paper.len() + *n as usize * self.stride();
+                self.paper.resize(len, 0);
+            }
+            [0x1d, 0x76, 0x30, _, xl, xh, ..] => {
+                let width = u16::from_le_bytes([*xl, *xh]) as usize;
+                if width == 0 {
+                    return Ok(());
+                }
+                self.check_rows(cmd[8..].len().div_ceil(width))?;
+                let stride = self.stride();
+                for row in cmd[8..].chunks(width) {
+                    let n = row.len().min(stride);
+                    self.paper.extend_from_slice(&row[..n]);
+                    self.paper.resize(self.paper.len() + stride - n, 0);
+                }
+            }
+            [b'\n'] => self.print_line()?,
+            [b @ 0x20..=0x7f] => {
+                self.line.push(*b as char);
+                let width = self.model.capabilities().width as f32;
+                if font::width(&self.line, TEXT_SCALE) > width {
+                    let last = self.line.pop();
+                    self.print_line()?;
+                    self.line.extend(last);
+                }
+            }
+            _ => log::warn!("emulator: ignoring unknown command {cmd:02x?}"),
+        }
+        Ok(())
+    }
+
+    /// Fake answer to a query.
+    fn answer(&self, cmd: &[u8]) -> Option<Vec<u8>> {
+        let answer = match <[u8; 4]>::try_from(cmd).ok()? {
+            proto::GET_IP => b"0.0.0.0".to_vec(),
+            proto::GET_FIRMWARE_VER => b"V1.0.0".to_vec(),
+            proto::GET_SERIAL => b"EMULATOR".to_vec(),
+            proto::GET_HARDWARE_VER => b"V1.0".to_vec(),
+            proto::GET_NAME => self.model.to_string().into_bytes(),
+            proto::GET_MAC => [MAC, MAC].concat(),
+            proto::GET_BATTERY => vec![0, 100],
+            _ => {
+                log::warn!("emulator: unknown query {cmd:02x?}");
+                return None;
+            }
+        };
+        Some(answer)
+    }
+
+    /// Print the pending text as one line.
+    fn print_line(&mut self) -> Result<()> {
+        self.check_rows(LINE_HEIGHT)?;
+        let mut doc = Document::blank(self.model.capabilities().width, LINE_HEIGHT)
+            .expect("print head width is valid");
+        let top = (LINE_HEIGHT as f32 - font::HEIGHT * TEXT_SCALE) / 2.0;
+        doc.draw(0.5, |c| font::draw(c, (0.0, top), TEXT_SCALE, &self.line));
+        self.paper.extend_from_slice(doc.pixels());
+        self.line.clear();
+        Ok(())
+    }
+}
+
+impl Backend for EmulatorBackend {
+    fn send(&mut self, buf: &[u8], _timeout: Duration) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.pending.extend_from_slice(buf);
+        let pending = std::mem::take(&mut state.pending);
+        let mut rest = &pending[..];
+        while let Some(len) = command_len(rest) {
+            state.execute(&rest[..len])?;
+            rest = &rest[len..];
+        }
+        state.pending = rest.to_vec();
+        Ok(())
+    }
+
+    fn recv(&mut self, buf: &mut [u8], _timeout: Duration) -> Result<usize> {
+        let mut state = self.state.lock().unwrap();
+        let Some(data) = state.response.take() else {
+            return Err(Error::Timeout);
+        };
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        Ok(n)
+    }
+
+    fn model(&self) -> Option<Model> {
+        Some(self.state.lock().unwrap().model)
+    }
+}
@@ -0,0 +1,223 @@
+use std::{fmt::Write, io, path::PathBuf};
+
+/// Result type of this crate.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Errors returned by this crate, match on them to handle specific failure modes.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// No printer was found, see [`Printer::find()`](crate::Printer::find()).
+    #[error("no printer found")]
+    NotFound,
+
+    /// The operating system denied access to the printer,
+    /// e.g. because of missing udev rules or group membership.
+    #[error("permission denied, does your user have access to the printer?")]
+    PermissionDenied,
+
+    /// The printer didn't respond, or didn't accept data in time.
+    #[error("timed out")]
+    Timeout,
+
+    /// The printer was unplugged, switched off, or went out of range.
+    #[error("printer disconnected")]
+    Disconnected,
+
+    /// Another process or a kernel driver (usually `usblp`) holds the printer's interface.
+    #[error("printer is busy{}", busy_hint(.driver, .pid))]
+    DeviceBusy {
+        /// Name of the kernel driver bound to the interface, if known.
+        driver: Option<String>,
+
+        /// Process ID of another process that has the device open, if known.
+        pid: Option<u32>,
+    },
+
+    /// Another process holds the lock on the printer, see [`DeviceLock`](crate::DeviceLock).
+    #[error("printer is locked by another process ({})", .path.display())]
+    DeviceLocked {
+        /// Path of the lock file.
+        path: PathBuf,
+    },
+
+    /// The lock file of a [`DeviceLock`](crate::DeviceLock) can't be opened or locked.
+    #[error("cannot lock {}: {source}", .path.display())]
+    LockFile {
+        /// Path of the lock file.
+        path: PathBuf,
+
+        source: io::Error,
+    },
+
+    /// The printer is out of paper.
+    #[error("printer is out of paper")]
+    PaperOut,
+
+    /// The printer's lid is open.
+    #[error("printer's lid is open")]
+    CoverOpen,
+
+    /// The printer's head is too hot, let it cool down.
+    #[error("printer is overheated, let it cool down")]
+    Overheated,
+
+    /// The printer sent a response, that doesn't make sense.
+    #[error("invalid {what} response: {data:x?}")]
+    InvalidResponse {
+        /// The request, that was answered.
+        what: &'static str,
+
+        /// The response.
+        data: Vec<u8>,
+    },
+
+    /// A document has more rows, than can be printed at once.
+    #[error("document too long: {rows} rows, at most {max} are possible")]
+    DocumentTooLong {
+        /// Number of rows in the document.
+        rows: usize,
+
+        /// Maximum number of rows.
+        max: usize,
+    },
+
+    /// An argument is out of range, or pixel data has the wrong size.
+    #[error("{0}")]
+    InvalidArgument(String),
+
+    /// The device doesn't look like a supported printer.
+    #[error("unsupported device: {0}")]
+    Unsupported(String),
+
+    /// The backend was lost, because a transfer running on another thread was cancelled.
+    #[error("backend was lost in a cancelled transfer")]
+    BackendLost,
+
+    /// Any other I/O error.
+    #[error(transparent)]
+    Io(io::Error),
+
+    /// Any other USB error.
+    #[cfg(feature = "usb")]
+    #[error("usb: {0}")]
+    Usb(rusb::Error),
+
+    /// Any other Bluetooth Low Energy error.
+    #[cfg(feature = "ble")]
+    #[error("ble: {0}")]
+    Ble(btleplug::Error),
+
+    /// The data doesn't fit into a QR code.
+    #[cfg(feature = "qr")]
+    #[error("qr: {0}")]
+    Qr(#[from] qrcode::types::QrError),
+
+    /// Errors of third-party [`Backend`](crate::Backend)s.
+    #[error(transparent)]
+    Other(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl Error {
+    /// Whether the operation may succeed, if it is retried,
+    /// e.g. after a timeout or a stalled USB endpoint, whose halt the USB backend clears.
+    /// Errors like [`Error::Disconnected`] or [`Error::PermissionDenied`] are fatal,
+    /// and so is [`Error::DeviceBusy`], as another process or driver won't let go of the printer
+    /// within a few retries, see `UsbBackend::open_exclusive()` to wait for it.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Timeout => true,
+            Self::Io(e) => matches!(
+                e.kind(),
+                io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+            ),
+            #[cfg(feature = "usb")]
+            Self::Usb(e) => matches!(
+                e,
+                rusb::Error::Pipe | rusb::Error::Interrupted | rusb::Error::Overflow
+            ),
+            _ => false,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::TimedOut => Self::Timeout,
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied,
+            io::ErrorKind::NotConnected
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted => Self::Disconnected,
+            _ => Self::Io(e),
+        }
+    }
+}
+
+impl From<crate::proto::EncodeError> for Error {
+    fn from(e: crate::proto::EncodeError) -> Self {
+        match e {
+            crate::proto::EncodeError::TooManyRows { rows, max } => {
+                Self::DocumentTooLong { rows, max }
+            }
+            e => Self::InvalidArgument(e.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "usb")]
+impl From<rusb::Error> for Error {
+    fn from(e: rusb::Error) -> Self {
+        match e {
+            rusb::Error::Timeout => Self::Timeout,
+            rusb::Error::Access => Self::PermissionDenied,
+            rusb::Error::NoDevice => Self::Disconnected,
+            rusb::Error::Busy => Self::DeviceBusy {
+                driver: None,
+                pid: None,
+            },
+            e => Self::Usb(e),
+        }
+    }
+}
+
+#[cfg(feature = "ble")]
+impl From<btleplug::Error> for Error {
+    fn from(e: btleplug::Error) -> Self {
+        match e {
+            btleplug::Error::PermissionDenied => Self::PermissionDenied,
+            btleplug::Error::NotConnected => Self::Disconnected,
+            btleplug::Error::TimedOut(_) => Self::Timeout,
+            e => Self::Ble(e),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl From<tokio::task::JoinError> for Error {
+    fn from(e: tokio::task::JoinError) -> Self {
+        match e.try_into_panic() {
+            Ok(panic) => std::panic::resume_unwind(panic),
+            Err(_) => Self::BackendLost,
+        }
+    }
+}
+
+fn busy_hint(driver: &Option<String>, pid: &Option<u32>) -> String {
+    let mut s = String::new();
+    if let Some(pid) = pid {
+        let _ = write!(s, ", it is opened by process {pid}");
+    }
+    match driver {
+        Some(driver) => {
+            let _ = write!(
+                s,
+                ", the interface is bound to the kernel driver {driver:?}, try `sudo rmmod {driver}`"
+            );
+        }
+        None if pid.is_none() => s.push_str(", is another program using it?"),
+        None => {}
+    }
+    s
+}
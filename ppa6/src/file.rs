@@ -1,51 +1,188 @@
 use std::{
-    fs::File,
-    io::{Read, Write},
-    path::{Path, PathBuf},
-    time::Duration,
+	fs::{File, OpenOptions},
+	io::{self, Read, Write},
+	path::{Path, PathBuf},
+	time::Duration,
 };
-use anyhow::Result;
+use crate::{backend::IoBackend, Backend, Model, Result};
 
-use crate::Backend;
+/// Timeouts are implemented with poll(2), without it reads and writes block.
+#[cfg(unix)]
+type Poll = crate::backend::FdPoll;
+#[cfg(not(unix))]
+type Poll = crate::backend::NoTimeout;
 
-/// A USB backend for [`Printer`](crate::Printer).
+/// Device nodes of the kernel's printer drivers, `usblp` and `lp`.
+#[cfg(target_os = "linux")]
+const DEVICE_PATTERNS: &[(&str, &str)] = &[
+	("/dev/usb", "lp"),
+	("/dev", "lp"),
+];
+
+/// `LPIOC_GET_DEVICE_ID(len)` from `<linux/usb/usblp.h>`, i.e. `_IOC(_IOC_READ, 'P', 1, len)`.
+#[cfg(target_os = "linux")]
+const fn lpioc_get_device_id(len: usize) -> libc::c_ulong {
+	(2 << 30) | ((len as libc::c_ulong) << 16) | ((b'P' as libc::c_ulong) << 8) | 1
+}
+
+/// A printer device node.
+///
+/// `usblp` completes reads with zero bytes, e.g. for an empty USB packet, which isn't the end of the stream,
+/// so they are reported as [`io::ErrorKind::WouldBlock`] to [`IoBackend`], which waits for more.
+struct Device(File);
+
+impl Read for Device {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		match self.0.read(buf)? {
+			0 if !buf.is_empty() => Err(io::ErrorKind::WouldBlock.into()),
+			n => Ok(n),
+		}
+	}
+}
+
+impl Write for Device {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.0.write(buf)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.0.flush()
+	}
+}
+
+#[cfg(unix)]
+impl std::os::fd::AsRawFd for Device {
+	fn as_raw_fd(&self) -> std::os::fd::RawFd {
+		self.0.as_raw_fd()
+	}
+}
+
+/// A USB backend for [`Printer`](crate::Printer), that uses the kernel's `usblp` driver.
 pub struct FileBackend {
-        file: File,
+	io: IoBackend<Device, Poll>,
+	model: Option<Model>,
 }
 
 impl FileBackend {
-	/// Get a list of printer devices connected via usb.
+	/// Get a list of printer devices connected via usb, that are handled by the kernel,
+	/// e.g. `/dev/usb/lp0`.
+	///
+	/// Every device is asked for its IEEE-1284 device ID, and only PeriPage printers are returned.
+	#[cfg(target_os = "linux")]
+	pub fn list() -> Result<Vec<PathBuf>> {
+		let mut devs = Vec::new();
+		for &(dir, prefix) in DEVICE_PATTERNS {
+			let entries = match std::fs::read_dir(dir) {
+				Ok(x) => x,
+				Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+				Err(e) => return Err(e.into()),
+			};
+			let mut paths: Vec<PathBuf> = entries
+				.filter_map(|e| e.ok())
+				.map(|e| e.path())
+				.filter(|p| {
+					p.file_name()
+						.and_then(|s| s.to_str())
+						.and_then(|s| s.strip_prefix(prefix))
+						.is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+				})
+				.collect();
+			paths.sort();
+
+			for path in paths {
+				match device_id(&path) {
+					Ok(id) if is_peripage(&id) => {
+						log::debug!("{}: {id}", path.display());
+						devs.push(path);
+					},
+					Ok(id) => log::debug!("{}: not a PeriPage: {id}", path.display()),
+					Err(e) => log::debug!("{}: cannot get device ID: {e}", path.display()),
+				}
+			}
+		}
+
+		Ok(devs)
+	}
+
+	/// Get a list of printer devices connected via usb, that are handled by the kernel.
+	///
+	/// Only Linux is supported, on other systems the list is always empty.
+	#[cfg(not(target_os = "linux"))]
 	pub fn list() -> Result<Vec<PathBuf>> {
-                Ok(vec![])
+		Ok(Vec::new())
 	}
 
 	/// Open a USB printing device.
 	pub fn open(path: &Path) -> Result<Self> {
-                let file = File::open(path)?;
-                Ok(Self {
-                        file
-                })
+		let mut options = OpenOptions::new();
+		options.read(true).write(true);
+		// reads and writes must not block, the timeouts are implemented with poll(2)
+		#[cfg(unix)]
+		std::os::unix::fs::OpenOptionsExt::custom_flags(&mut options, libc::O_NONBLOCK);
+		let file = options.open(path)?;
+
+		#[cfg(target_os = "linux")]
+		let model = device_id(path).ok().and_then(|id| {
+			let mdl = field(&id, &["MDL", "MODEL"])?;
+			// the model may be given without the brand, which is in MFG then
+			Model::from_name(mdl).or_else(|| {
+				Model::from_name(&format!("PeriPage {mdl}")).filter(|_| is_peripage(&id))
+			})
+		});
+		#[cfg(not(target_os = "linux"))]
+		let model = None;
+
+		Ok(Self {
+			io: IoBackend::with_poll(Device(file), Poll::default()),
+			model,
+		})
 	}
 }
 
 impl Backend for FileBackend {
-	fn send(&mut self, buf: &[u8], _timeout: Duration) -> anyhow::Result<()> {
-                // TODO: timeout
-                self.file.write_all(buf)?;
-		Ok(())
+	fn send(&mut self, buf: &[u8], timeout: Duration) -> Result<()> {
+		self.io.send(buf, timeout)
+	}
+
+	fn recv(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+		self.io.recv(buf, timeout)
 	}
 
-	fn recv(&mut self, buf: &mut [u8], _timeout: Duration) -> anyhow::Result<usize> {
-                // TODO: timeout
-                let mut nr = 0;
-                while nr < buf.len() {
-                        let n = self.file.read(&mut buf[nr..])?;
-                        if n == 0 {
-                            break;
-                        }
-                        nr += n;
-                }
-		Ok(nr)
+	fn model(&self) -> Option<Model> {
+		self.model
 	}
 }
 
+/// Ask the printer at `path` for its IEEE-1284 device ID, e.g. `MFG:PeriPage;MDL:A6;CMD:...;`.
+#[cfg(target_os = "linux")]
+fn device_id(path: &Path) -> io::Result<String> {
+	use std::os::fd::AsRawFd;
+
+	let file = File::open(path)?;
+	let mut buf = [0u8; 1024];
+	let r = unsafe { libc::ioctl(file.as_raw_fd(), lpioc_get_device_id(buf.len()) as _, buf.as_mut_ptr()) };
+	if r < 0 {
+		return Err(io::Error::last_os_error());
+	}
+
+	// the ID starts with its length, including the length itself, in big endian
+	let len = (u16::from_be_bytes([buf[0], buf[1]]) as usize).clamp(2, buf.len());
+	Ok(String::from_utf8_lossy(&buf[2..len]).trim_end_matches('\0').to_owned())
+}
+
+/// Get the value of the first of `keys` in an IEEE-1284 device ID.
+#[cfg(target_os = "linux")]
+fn field<'a>(id: &'a str, keys: &[&str]) -> Option<&'a str> {
+	id.split(';')
+		.filter_map(|f| f.split_once(':'))
+		.find(|(k, _)| keys.iter().any(|key| k.trim().eq_ignore_ascii_case(key)))
+		.map(|(_, v)| v.trim())
+}
+
+#[cfg(target_os = "linux")]
+fn is_peripage(id: &str) -> bool {
+	[&["MFG", "MANUFACTURER"][..], &["MDL", "MODEL"]]
+		.into_iter()
+		.filter_map(|keys| field(id, keys))
+		.any(|v| v.to_ascii_uppercase().contains("PERIPAGE"))
+}
@@ -0,0 +1,100 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+use crate::{Error, Model, Result};
+
+/// Firmware version, see [`Printer::get_firmware_version()`](crate::Printer::get_firmware_version()).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FirmwareVersion {
+    /// Major version.
+    pub major: u32,
+
+    /// Minor version.
+    pub minor: u32,
+
+    /// Patch level.
+    pub patch: u32,
+}
+
+impl FirmwareVersion {
+    /// Create a version from its parts.
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+/// Parses versions like `V1.0.8` or `2.1`, missing parts are zero.
+impl FromStr for FirmwareVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidArgument(format!("invalid firmware version: {s:?}"));
+        let version = s.trim().trim_start_matches(['V', 'v']);
+        let parts: Vec<&str> = version.split('.').collect();
+        if parts.len() > 3 {
+            return Err(invalid());
+        }
+
+        let mut nums = [0u32; 3];
+        for (num, part) in nums.iter_mut().zip(parts) {
+            *num = part.parse().map_err(|_| invalid())?;
+        }
+        let [major, minor, patch] = nums;
+        Ok(Self::new(major, minor, patch))
+    }
+}
+
+impl Display for FirmwareVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Differences in behavior between firmware versions, see [`Printer::set_quirks()`](crate::Printer::set_quirks()).
+///
+/// No firmware version is known to need quirks yet, see [`Quirks::for_firmware()`],
+/// so these only change anything, if set explicitly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Quirks {
+    /// Most rows, that can be sent as one chunk.
+    pub max_chunk_rows: u16,
+
+    /// Send [`INIT`](crate::proto::INIT) after [`RESET`](crate::proto::RESET),
+    /// like the Windows driver does.
+    pub init_after_reset: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self {
+            max_chunk_rows: 0xff,
+            init_after_reset: false,
+        }
+    }
+}
+
+/// Firmware versions, that need quirks.
+///
+/// No firmware is known to need any yet, so every printer gets the defaults.
+/// Add a version here, once it was shown on hardware to need something else,
+/// until then use [`Printer::set_quirks()`](crate::Printer::set_quirks()).
+const KNOWN_QUIRKS: &[(Model, FirmwareVersion, Quirks)] = &[];
+
+impl Quirks {
+    /// Get the known quirks of a firmware version, the defaults for unknown versions.
+    /// As no firmware with quirks is known yet, these are always the defaults.
+    pub fn for_firmware(model: Model, version: FirmwareVersion) -> Self {
+        KNOWN_QUIRKS
+            .iter()
+            .find(|(m, v, _)| *m == model && *v == version)
+            .map(|(_, _, quirks)| *quirks)
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,185 @@
+use std::{
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{proto, Backend, Model, Result, TransportStats};
+
+/// Timeout for restoring the concentration after reconnecting.
+const RESTORE_TIMEOUT: Duration = Duration::from_secs(1);
+
+struct State<B> {
+    backend: Option<B>,
+    last_used: Instant,
+    dropped: bool,
+}
+
+struct Shared<B> {
+    state: Mutex<State<B>>,
+    cvar: Condvar,
+}
+
+/// A [`Backend`], that is released after being idle for a while, and reconnected on the next transfer.
+///
+/// Keeping the connection open keeps the printer awake, which drains its battery.
+/// The printer forgets its concentration while it sleeps,
+/// so the last concentration command sent through this backend is sent again after reconnecting.
+pub struct IdleBackend<B, F> {
+    shared: Arc<Shared<B>>,
+    connect: F,
+    /// The last concentration command, see [`proto::SET_CONCENTRATION`].
+    concentration: Option<Vec<u8>>,
+}
+
+impl<B, F> IdleBackend<B, F>
+where
+    B: Backend + Send + 'static,
+    F: FnMut() -> Result<B>,
+{
+    /// Connect using `connect`, and release the backend after `idle` without transfers.
+    pub fn new(idle: Duration, mut connect: F) -> Result<Self> {
+        let backend = connect()?;
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                backend: Some(backend),
+                last_used: Instant::now(),
+                dropped: false,
+            }),
+            cvar: Condvar::new(),
+        });
+
+        let watchdog = Arc::clone(&shared);
+        thread::spawn(move || {
+            let mut state = watchdog.state.lock().unwrap();
+            loop {
+                if state.dropped {
+                    return;
+                }
+
+                if state.backend.is_none() {
+                    state = watchdog.cvar.wait(state).unwrap();
+                    continue;
+                }
+
+                let left = idle.saturating_sub(state.last_used.elapsed());
+                if !left.is_zero() {
+                    state = watchdog.cvar.wait_timeout(state, left).unwrap().0;
+                    continue;
+                }
+
+                log::info!("releasing printer after {idle:?} of inactivity");
+                let mut backend = state.backend.take().unwrap();
+                if let Err(e) = backend.release() {
+                    log::warn!("cannot release printer: {e}");
+                }
+            }
+        });
+
+        Ok(Self {
+            shared,
+            connect,
+            concentration: None,
+        })
+    }
+
+    /// Whether the backend is currently connected.
+    pub fn is_connected(&self) -> bool {
+        self.shared.state.lock().unwrap().backend.is_some()
+    }
+
+    fn backend(&mut self) -> Result<MutexGuard<'_, State<B>>> {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.backend.is_none() {
+            log::info!("reconnecting to printer...");
+            let mut backend = (self.connect)()?;
+            if let Some(cmd) = &self.concentration {
+                backend.send(cmd, RESTORE_TIMEOUT)?;
+            }
+            state.backend = Some(backend);
+        }
+        state.last_used = Instant::now();
+        self.shared.cvar.notify_one();
+        Ok(state)
+    }
+}
+
+impl<B, F> Backend for IdleBackend<B, F>
+where
+    B: Backend + Send + 'static,
+    F: FnMut() -> Result<B>,
+{
+    fn send(&mut self, buf: &[u8], timeout: Duration) -> Result<()> {
+        let mut state = self.backend()?;
+        state.backend.as_mut().unwrap().send(buf, timeout)?;
+        drop(state);
+
+        if buf.len() == proto::SET_CONCENTRATION.len() + 1
+            && buf.starts_with(&proto::SET_CONCENTRATION)
+        {
+            self.concentration = Some(buf.to_vec());
+        }
+        Ok(())
+    }
+
+    fn recv(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        let mut state = self.backend()?;
+        state.backend.as_mut().unwrap().recv(buf, timeout)
+    }
+
+    fn release(&mut self) -> Result<()> {
+        let backend = self.shared.state.lock().unwrap().backend.take();
+        match backend {
+            Some(mut backend) => backend.release(),
+            None => Ok(()),
+        }
+    }
+
+    fn stats(&self) -> Option<TransportStats> {
+        let state = self.shared.state.lock().unwrap();
+        state.backend.as_ref().and_then(|b| b.stats())
+    }
+
+    fn model(&self) -> Option<Model> {
+        let state = self.shared.state.lock().unwrap();
+        state.backend.as_ref().and_then(|b| b.model())
+    }
+}
+
+impl<B, F> Drop for IdleBackend<B, F> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.shared.state.lock() {
+            state.dropped = true;
+        }
+        self.shared.cvar.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockBackend;
+
+    #[test]
+    fn restores_concentration() {
+        let concentration = [0x10, 0xff, 0x10, 0x00, 2];
+        let mock = MockBackend::new();
+        mock.expect_send(concentration)
+            .expect_send(concentration)
+            .expect_send(*b"hello");
+
+        let connect = mock.clone();
+        let mut backend =
+            IdleBackend::new(Duration::from_millis(10), move || Ok(connect.reconnect())).unwrap();
+        backend.send(&concentration, RESTORE_TIMEOUT).unwrap();
+
+        let start = Instant::now();
+        while backend.is_connected() {
+            assert!(start.elapsed() < Duration::from_secs(5), "not released");
+            thread::sleep(Duration::from_millis(5));
+        }
+        backend.send(b"hello", RESTORE_TIMEOUT).unwrap();
+        assert!(backend.is_connected());
+        mock.assert_done();
+    }
+}
@@ -0,0 +1,62 @@
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use crate::{Backoff, Error, Result};
+
+/// An advisory lock on a printer, shared by all processes using this crate.
+/// The lock is held until this value is dropped.
+#[derive(Debug)]
+pub struct DeviceLock {
+    _file: File,
+    path: PathBuf,
+}
+
+impl DeviceLock {
+    /// Lock the printer identified by `id` (e.g. its serial number).
+    /// If another process holds the lock, wait for at most `wait`.
+    pub fn acquire(id: &str, wait: Duration) -> Result<Self> {
+        let name: String = id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let path = std::env::temp_dir().join(format!("ppa6-{name}.lock"));
+
+        // another user may have created the lock file, locking works on read-only files too
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .or_else(|_| File::open(&path))
+            .map_err(|source| Error::LockFile {
+                path: path.clone(),
+                source,
+            })?;
+
+        let start = Instant::now();
+        for delay in Backoff::default().delays() {
+            match file.try_lock() {
+                Ok(()) => {
+                    log::debug!("locked {}", path.display());
+                    return Ok(Self { _file: file, path });
+                }
+                Err(TryLockError::WouldBlock) if start.elapsed() + delay <= wait => {
+                    log::debug!("{} is locked, retrying in {delay:.2?}", path.display());
+                    std::thread::sleep(delay);
+                }
+                Err(TryLockError::WouldBlock) => return Err(Error::DeviceLocked { path }),
+                Err(TryLockError::Error(e)) => return Err(Error::LockFile { path, source: e }),
+            }
+        }
+        unreachable!()
+    }
+}
+
+impl Drop for DeviceLock {
+    fn drop(&mut self) {
+        log::debug!("unlocking {}", self.path.display());
+    }
+}
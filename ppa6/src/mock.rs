@@ -0,0 +1,241 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{Backend, Error, Model, Result};
+
+enum Direction {
+    Send(Option<Vec<u8>>),
+    Recv(Vec<u8>),
+}
+
+struct Expectation {
+    dir: Direction,
+    timeout: Option<Duration>,
+    error: Option<Error>,
+    /// Only this many bytes of a send arrive, see [`MockBackend::short_write()`].
+    accept: Option<usize>,
+}
+
+#[derive(Default)]
+struct State {
+    expected: VecDeque<Expectation>,
+    sent: Vec<Vec<u8>>,
+    model: Option<Model>,
+    /// Bytes, that can be sent before the printer disconnects, see [`MockBackend::disconnect_after()`].
+    disconnect_after: Option<usize>,
+    disconnected: bool,
+}
+
+/// A scriptable [`Backend`] for unit tests of code, that uses a [`Printer`](crate::Printer).
+///
+/// Tests enqueue the packets, that are expected to be sent, and the responses of the printer, in order.
+/// Sending anything else panics, so does [`MockBackend::assert_done()`], if expectations are left.
+/// Receiving, when no response is expected next, fails with [`Error::Timeout`],
+/// like a printer, that doesn't answer.
+///
+/// Faults are injected with [`MockBackend::fail()`], [`MockBackend::short_write()`],
+/// [`MockBackend::drop_response()`], and [`MockBackend::disconnect_after()`],
+/// to test retries, resuming, and reconnecting deterministically.
+///
+/// Clones share their state, so keep one to check it, after the other was moved into a [`Printer`](crate::Printer).
+#[derive(Clone, Default)]
+pub struct MockBackend {
+    state: Arc<Mutex<State>>,
+}
+
+impl MockBackend {
+    /// Create a mock, that expects nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&self, dir: Direction) -> &Self {
+        self.state.lock().unwrap().expected.push_back(Expectation {
+            dir,
+            timeout: None,
+            error: None,
+            accept: None,
+        });
+        self
+    }
+
+    fn modify_last(&self, f: impl FnOnce(&mut Expectation)) -> &Self {
+        let mut state = self.state.lock().unwrap();
+        let last = state
+            .expected
+            .back_mut()
+            .expect("no expectation to modify, call expect_send() or respond() first");
+        f(last);
+        self
+    }
+
+    /// Expect exactly `data` to be sent next.
+    pub fn expect_send(&self, data: impl Into<Vec<u8>>) -> &Self {
+        self.push(Direction::Send(Some(data.into())))
+    }
+
+    /// Expect anything to be sent next, e.g. image data, that isn't interesting for the test.
+    pub fn expect_send_any(&self) -> &Self {
+        self.push(Direction::Send(None))
+    }
+
+    /// Answer the next receive with `data`.
+    pub fn respond(&self, data: impl Into<Vec<u8>>) -> &Self {
+        self.push(Direction::Recv(data.into()))
+    }
+
+    /// Let the last expected transfer fail with `error`.
+    pub fn fail(&self, error: Error) -> &Self {
+        self.modify_last(|e| e.error = Some(error))
+    }
+
+    /// Let only the first `len` bytes of the last expected send arrive, like a printer, that stops accepting data.
+    /// Only those bytes are recorded in [`MockBackend::sent()`], and the send fails with [`Error::Timeout`].
+    pub fn short_write(&self, len: usize) -> &Self {
+        self.modify_last(|e| {
+            assert!(
+                matches!(e.dir, Direction::Send(_)),
+                "short_write() needs an expected send"
+            );
+            e.accept = Some(len);
+            e.error = Some(Error::Timeout);
+        })
+    }
+
+    /// Lose the last expected response, like a printer, whose answer never arrives.
+    /// The receive fails with [`Error::Timeout`].
+    pub fn drop_response(&self) -> &Self {
+        self.modify_last(|e| {
+            assert!(
+                matches!(e.dir, Direction::Recv(_)),
+                "drop_response() needs an expected response"
+            );
+            e.error = Some(Error::Timeout);
+        })
+    }
+
+    /// Disconnect after `bytes` more bytes were sent, in the middle of a send, if it's longer.
+    /// The send, that crosses the limit, still uses up its expectation.
+    /// Afterwards, every transfer fails with [`Error::Disconnected`], until [`MockBackend::reconnect()`].
+    pub fn disconnect_after(&self, bytes: usize) -> &Self {
+        self.state.lock().unwrap().disconnect_after = Some(bytes);
+        self
+    }
+
+    /// Connect the printer again after [`MockBackend::disconnect_after()`], and get a clone of the mock,
+    /// e.g. for `printer.set_reconnect(move || Ok(mock.reconnect()))`.
+    pub fn reconnect(&self) -> Self {
+        let mut state = self.state.lock().unwrap();
+        state.disconnected = false;
+        state.disconnect_after = None;
+        self.clone()
+    }
+
+    /// Assert, that the last expected transfer uses `timeout`.
+    pub fn with_timeout(&self, timeout: Duration) -> &Self {
+        self.modify_last(|e| e.timeout = Some(timeout))
+    }
+
+    /// Report `model` from [`Backend::model()`].
+    pub fn set_model(&self, model: Model) -> &Self {
+        self.state.lock().unwrap().model = Some(model);
+        self
+    }
+
+    /// Everything sent so far.
+    pub fn sent(&self) -> Vec<Vec<u8>> {
+        self.state.lock().unwrap().sent.clone()
+    }
+
+    /// Panic, if expected transfers didn't happen.
+    #[track_caller]
+    pub fn assert_done(&self) {
+        let state = self.state.lock().unwrap();
+        let left = state.expected.len();
+        assert!(left == 0, "{left} expected transfers didn't happen");
+    }
+}
+
+fn check_timeout(expected: Option<Duration>, actual: Duration, what: &str) {
+    if let Some(expected) = expected {
+        assert_eq!(
+            expected, actual,
+            "{what} with timeout {actual:?}, but {expected:?} was expected"
+        );
+    }
+}
+
+impl Backend for MockBackend {
+    fn send(&mut self, buf: &[u8], timeout: Duration) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.disconnected {
+            return Err(Error::Disconnected);
+        }
+        let Some(expectation) = state.expected.pop_front() else {
+            panic!("unexpected send of {buf:02x?}, nothing more was expected");
+        };
+
+        match &expectation.dir {
+            Direction::Send(Some(data)) => {
+                assert!(data == buf, "sent {buf:02x?}, but {data:02x?} was expected")
+            }
+            Direction::Send(None) => {}
+            Direction::Recv(data) => {
+                panic!("sent {buf:02x?}, but a receive of {data:02x?} was expected")
+            }
+        }
+        check_timeout(expectation.timeout, timeout, "sent");
+
+        let mut len = expectation.accept.unwrap_or(buf.len()).min(buf.len());
+        let mut error = expectation.error;
+        if let Some(left) = state.disconnect_after {
+            if len > left {
+                len = left;
+                error = Some(Error::Disconnected);
+            }
+            state.disconnect_after = Some(left - len);
+            state.disconnected = left == len;
+        }
+        state.sent.push(buf[..len].to_vec());
+
+        match error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn recv(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        let mut state = self.state.lock().unwrap();
+        if state.disconnected {
+            return Err(Error::Disconnected);
+        }
+        if !matches!(
+            state.expected.front(),
+            Some(Expectation {
+                dir: Direction::Recv(_),
+                ..
+            })
+        ) {
+            return Err(Error::Timeout);
+        }
+
+        let expectation = state.expected.pop_front().unwrap();
+        check_timeout(expectation.timeout, timeout, "received");
+        if let Some(e) = expectation.error {
+            return Err(e);
+        }
+        let Direction::Recv(data) = expectation.dir else {
+            unreachable!()
+        };
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        Ok(n)
+    }
+
+    fn model(&self) -> Option<Model> {
+        self.state.lock().unwrap().model
+    }
+}
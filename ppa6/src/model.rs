@@ -0,0 +1,216 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+use crate::Error;
+
+/// PeriPage printer models, see [`Printer::detect_model()`](crate::Printer::detect_model()).
+///
+/// Only the A6 was tested. The widths and resolutions of the other models are taken from
+/// the paper widths and resolutions in the vendor's product listings, and were never checked on a device,
+/// see [`Model::capabilities()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Model {
+    /// PeriPage A6, 58mm paper at 203dpi.
+    #[default]
+    A6,
+
+    /// PeriPage A6+, 58mm paper at 304dpi.
+    A6Plus,
+
+    /// PeriPage A8, 58mm paper at 203dpi.
+    A8,
+
+    /// PeriPage A9, 80mm paper at 203dpi.
+    A9,
+
+    /// PeriPage A9 Max, 80mm paper at 304dpi.
+    A9Max,
+}
+
+/// What a [`Model`] can do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Capabilities {
+    /// Width of the print head in pixels.
+    pub width: u16,
+
+    /// Resolution in dots per inch.
+    pub dpi: u16,
+
+    /// Highest value for [`Printer::set_concentration()`](crate::Printer::set_concentration()).
+    pub max_concentration: u8,
+}
+
+/// Printing concentration (darkness), see [`Printer::set_concentration()`](crate::Printer::set_concentration()).
+///
+/// The named levels are mapped to the range of the model, see [`Concentration::level()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Concentration {
+    /// The lightest level, saves battery.
+    Light,
+
+    /// A level in the middle.
+    Medium,
+
+    /// The darkest level, see [`Capabilities::max_concentration`].
+    Dark,
+
+    /// A raw level, between `0` and [`Capabilities::max_concentration`].
+    Custom(u8),
+}
+
+impl Concentration {
+    /// Get the raw level sent to a printer of `model`.
+    pub fn level(self, model: Model) -> u8 {
+        let max = model.capabilities().max_concentration;
+        match self {
+            Self::Light => 0,
+            Self::Medium => max.div_ceil(2),
+            Self::Dark => max,
+            Self::Custom(c) => c,
+        }
+    }
+}
+
+impl From<u8> for Concentration {
+    fn from(c: u8) -> Self {
+        Self::Custom(c)
+    }
+}
+
+/// Parses `light`, `medium`, `dark`, or a raw level.
+impl FromStr for Concentration {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "light" => Ok(Self::Light),
+            "medium" => Ok(Self::Medium),
+            "dark" => Ok(Self::Dark),
+            s => s
+                .parse()
+                .map(Self::Custom)
+                .map_err(|_| Error::InvalidArgument(format!("invalid concentration: {s:?}"))),
+        }
+    }
+}
+
+impl Display for Concentration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Light => f.write_str("light"),
+            Self::Medium => f.write_str("medium"),
+            Self::Dark => f.write_str("dark"),
+            Self::Custom(c) => write!(f, "{c}"),
+        }
+    }
+}
+
+impl Model {
+    /// Guess the model from the name reported by the printer, or a USB product string,
+    /// e.g. `PeriPage_A6_9A8B`, `PeriPage A9 Max`, or `PeriPage+_1F2E`.
+    /// Only the word right after `PeriPage` is looked at, so the end of the MAC address,
+    /// which follows it in Bluetooth names, isn't mistaken for a model.
+    /// A PeriPage without a known model is assumed to be an A6.
+    /// Returns `None` for names, that don't look like a PeriPage.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_uppercase();
+        let rest = &name[name.find("PERIPAGE")? + "PERIPAGE".len()..];
+        if rest.starts_with('+') {
+            return Some(Self::A6Plus);
+        }
+
+        let mut words = rest.split([' ', '_', '-']).filter(|w| !w.is_empty());
+        let model = match (words.next(), words.next()) {
+            (Some("A6+" | "A6PLUS"), _) | (Some("A6"), Some("+" | "PLUS")) => Self::A6Plus,
+            (Some("A9MAX"), _) | (Some("A9"), Some("MAX")) => Self::A9Max,
+            (Some("A9"), _) => Self::A9,
+            (Some("A8"), _) => Self::A8,
+            _ => Self::A6,
+        };
+        Some(model)
+    }
+
+    /// Get the capabilities of this model.
+    ///
+    /// The 384px print head and the concentrations `0..=2` of the A6 were tested.
+    /// The other widths are unverified: 48mm printable of 58mm paper, or 72mm of 80mm paper, at the listed resolution.
+    /// Their concentration range is assumed to be the one of the A6.
+    pub fn capabilities(self) -> Capabilities {
+        let (width, dpi) = match self {
+            Self::A6 | Self::A8 => (384, 203),
+            Self::A6Plus => (576, 304),
+            Self::A9 => (576, 203),
+            Self::A9Max => (832, 304),
+        };
+        Capabilities {
+            width,
+            dpi,
+            max_concentration: 2,
+        }
+    }
+}
+
+impl Display for Model {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::A6 => "PeriPage A6",
+            Self::A6Plus => "PeriPage A6+",
+            Self::A8 => "PeriPage A8",
+            Self::A9 => "PeriPage A9",
+            Self::A9Max => "PeriPage A9 Max",
+        };
+        f.write_str(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_name() {
+        let names = [
+            ("PeriPage_A6_9A8B", Some(Model::A6)),
+            ("PeriPage_A9A8", Some(Model::A6)),
+            ("PeriPage+_1F2E", Some(Model::A6Plus)),
+            ("PeriPage A6+", Some(Model::A6Plus)),
+            ("peripage-a6-plus", Some(Model::A6Plus)),
+            ("PeriPage_A8_A9C1", Some(Model::A8)),
+            ("PeriPage A9", Some(Model::A9)),
+            ("PeriPage A9 Max", Some(Model::A9Max)),
+            ("PERIPAGE_A9MAX_0001", Some(Model::A9Max)),
+            ("PeriPage", Some(Model::A6)),
+            ("Printer A9", None),
+            ("", None),
+        ];
+        for (name, model) in names {
+            assert_eq!(Model::from_name(name), model, "{name:?}");
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for model in [Model::A6, Model::A6Plus, Model::A8, Model::A9, Model::A9Max] {
+            assert_eq!(Model::from_name(&model.to_string()), Some(model));
+        }
+    }
+
+    #[test]
+    fn concentration() {
+        assert_eq!(
+            "Dark".parse::<Concentration>().unwrap(),
+            Concentration::Dark
+        );
+        assert_eq!(
+            " 1 ".parse::<Concentration>().unwrap(),
+            Concentration::Custom(1)
+        );
+        assert!("darker".parse::<Concentration>().is_err());
+        assert_eq!(Concentration::Medium.level(Model::A6), 1);
+        assert_eq!(Concentration::Dark.level(Model::A6), 2);
+    }
+}
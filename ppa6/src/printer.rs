@@ -0,0 +1,873 @@
+//! The printer and what it reports.
+
+use std::{
+    fmt::{self, Debug, Display, Formatter},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    aio::{block_on, AsyncBackend, Blocking},
+    backend::find_backend,
+    proto, Backend, Backoff, Document, Error, IdleBackend, Result, TransportStats,
+};
+
+pub use crate::{
+    aio::AsyncPrinter,
+    builder::{EndOfImage, Framing, PrinterBuilder, ThermalPolicy, Timeouts},
+    firmware::{FirmwareVersion, Quirks},
+    model::{Capabilities, Concentration, Model},
+};
+
+/// Progress of a print job, see [`Printer::print_document_ext()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Progress {
+    /// Number of rows, that have been sent.
+    pub rows: usize,
+
+    /// Total number of rows.
+    pub total: usize,
+
+    /// Time since the job started.
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// Fraction of rows sent, between `0.0` and `1.0`.
+    pub fn fraction(&self) -> f64 {
+        match self.total {
+            0 => 1.0,
+            total => self.rows as f64 / total as f64,
+        }
+    }
+
+    /// Estimated time until the job is done, assuming the remaining rows print as fast as the previous ones.
+    pub fn remaining(&self) -> Option<Duration> {
+        (self.rows > 0).then(|| {
+            self.elapsed
+                .mul_f64((self.total - self.rows.min(self.total)) as f64 / self.rows as f64)
+        })
+    }
+}
+
+/// Options of a print job, see [`Printer::print()`].
+/// Clone it to print several jobs with the same options.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrintOptions {
+    /// Number of copies, default: 1.
+    pub copies: usize,
+
+    /// Rows of paper to feed after the last copy, default: 0.
+    pub feed: u8,
+
+    /// Printing concentration, see [`Printer::set_concentration()`], default: unchanged.
+    pub concentration: Option<Concentration>,
+
+    /// Reset the printer first, default: `true`.
+    pub reset: bool,
+
+    /// Rows per chunk, default: 24.
+    pub chunk_height: u16,
+
+    /// Delay between chunks,
+    /// default: [`PrinterBuilder::chunk_delay()`] or [`PrinterBuilder::thermal_policy()`].
+    pub delay: Option<Duration>,
+
+    /// If the printer is disconnected during the job, e.g. because its battery dipped,
+    /// open it again, reset it, restore the concentration set before, and continue with the chunk that failed,
+    /// default: `false`.
+    /// This needs a way to reconnect, see [`Printer::set_reconnect()`].
+    /// Only used by [`Printer::print()`].
+    pub resume_on_reconnect: bool,
+
+    /// Stamp this text, e.g. a job ID and date, into the corner of each copy,
+    /// followed by the number of the copy, if there are several, see [`Document::stamp()`],
+    /// default: `None`.
+    /// Only letters, digits and `-./:` are printed.
+    pub stamp: Option<String>,
+
+    /// When to send [`END_OF_IMAGE`](proto::END_OF_IMAGE) during this job,
+    /// default: `None`, as set with [`Printer::set_end_of_image()`].
+    pub end_of_image: Option<EndOfImage>,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self {
+            copies: 1,
+            feed: 0,
+            concentration: None,
+            reset: true,
+            chunk_height: 24,
+            delay: None,
+            resume_on_reconnect: false,
+            stamp: None,
+            end_of_image: None,
+        }
+    }
+}
+
+/// MAC Address, see [`Printer::get_mac()`].
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MacAddr(pub [u8; 6]);
+
+/// Battery state, see [`Printer::get_battery_status()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BatteryStatus {
+    /// Charge level in percent.
+    pub percent: u8,
+
+    /// Whether the printer is connected to a charger, if the printer reports it.
+    /// This is decoded from an unverified byte, see [`Printer::get_battery_status()`].
+    pub charging: Option<bool>,
+}
+
+/// Printer status, see [`Printer::get_status()`].
+/// Every field is `None`, if the printer didn't report it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PrinterStatus {
+    /// Whether there is paper.
+    pub paper: Option<bool>,
+
+    /// Whether the lid is open.
+    pub cover_open: Option<bool>,
+
+    /// Whether the print head is too hot.
+    pub overheated: Option<bool>,
+}
+
+impl PrinterStatus {
+    /// Turn a known problem into an error.
+    pub fn check(&self) -> Result<()> {
+        if self.paper == Some(false) {
+            return Err(Error::PaperOut);
+        }
+        if self.cover_open == Some(true) {
+            return Err(Error::CoverOpen);
+        }
+        if self.overheated == Some(true) {
+            return Err(Error::Overheated);
+        }
+        Ok(())
+    }
+}
+
+/// PeriPage A6 printer.
+///
+/// This is a blocking wrapper around [`AsyncPrinter`].
+pub struct Printer {
+    pub(crate) inner: AsyncPrinter,
+}
+
+impl Printer {
+    /// Construct a new printer using `backend` as it's printing [`Backend`].
+    /// Use [`PrinterBuilder`] for non-default timeouts.
+    ///
+    /// Since `Printer` wraps an [`AsyncPrinter`], the backend must be [`Send`].
+    pub fn new(backend: impl Backend + Send + 'static) -> Self {
+        PrinterBuilder::new().build(backend)
+    }
+
+    /// Find any printer, connected using any backend.
+    /// Paired Bluetooth printers are only found as root, see `BtBackend::list()`.
+    pub fn find() -> Result<Self> {
+        let mut printer = Self::new(find_backend(None)?);
+        printer.set_reconnect(|| find_backend(None));
+        Ok(printer)
+    }
+
+    /// Like [`Printer::find()`], but take the printer's [`DeviceLock`](crate::DeviceLock) first,
+    /// waiting at most `wait` for other processes to finish with the printer.
+    /// Only USB printers are locked.
+    pub fn find_exclusive(wait: Duration) -> Result<Self> {
+        let mut printer = Self::new(find_backend(Some(wait))?);
+        printer.set_reconnect(move || find_backend(Some(wait)));
+        Ok(printer)
+    }
+
+    /// Use `reconnect` to open the printer again, if it was disconnected during a job,
+    /// see [`PrintOptions::resume_on_reconnect`].
+    /// [`Printer::find()`] and [`Printer::find_exclusive()`] set this to search for any printer again.
+    pub fn set_reconnect<B>(&mut self, mut reconnect: impl FnMut() -> Result<B> + Send + 'static)
+    where
+        B: Backend + Send + 'static,
+    {
+        self.inner.set_reconnect(move || {
+            let backend = reconnect().map(|b| Box::new(Blocking(b)) as Box<dyn AsyncBackend>);
+            Box::pin(std::future::ready(backend))
+        });
+    }
+
+    /// Like [`Printer::find()`], but disconnect from the printer after it was idle for `idle`,
+    /// so it can go to sleep. It is reconnected, reset, and gets its concentration back on the next transfer,
+    /// see [`IdleBackend`].
+    pub fn find_idle(idle: Duration) -> Result<Self> {
+        let backend = IdleBackend::new(idle, || {
+            let mut backend = find_backend(None)?;
+            backend.send(&proto::RESET, Duration::from_secs(3))?;
+            let _ = backend.recv(&mut [0u8; 128], Duration::from_secs(1));
+            Ok(backend)
+        })?;
+        Ok(Self::new(backend))
+    }
+
+    /// Like [`Printer::find()`], but keep looking for a printer until `timeout` runs out.
+    /// The bus is polled according to `backoff`.
+    pub fn find_wait(timeout: Duration, backoff: Backoff) -> Result<Self> {
+        let start = Instant::now();
+        for delay in backoff.delays() {
+            match Self::find() {
+                Ok(printer) => return Ok(printer),
+                Err(e) if start.elapsed() + delay > timeout => return Err(e),
+                Err(e) => log::debug!("{e}, retrying in {delay:.2?}"),
+            }
+            std::thread::sleep(delay);
+        }
+        unreachable!()
+    }
+
+    /// Release the printer, so that other programs or drivers can use it.
+    /// For USB printers, this reattaches the kernel driver (e.g. `usblp`).
+    pub fn release(self) -> Result<()> {
+        block_on(self.inner.release())
+    }
+
+    /// Get the transfer statistics of the backend, if it keeps any.
+    /// This can be used to tell a slow USB link apart from slow printing.
+    pub fn transport_stats(&self) -> Option<TransportStats> {
+        self.inner.transport_stats()
+    }
+
+    /// Detect the printer's model, from the backend or by asking for the printer's name,
+    /// see [`Model::from_name()`]. Unknown printers are assumed to be a [`Model::A6`].
+    ///
+    /// Once the model is known, images are validated against its [`Capabilities`].
+    pub fn detect_model(&mut self) -> Result<Model> {
+        block_on(self.inner.detect_model())
+    }
+
+    /// The model found by [`Printer::detect_model()`], if it was called.
+    pub fn model(&self) -> Option<Model> {
+        self.inner.model()
+    }
+
+    /// Capabilities of the printer's model, those of the A6, if it isn't detected yet.
+    pub fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    /// Get printer's "IP" string.
+    pub fn get_ip(&mut self) -> Result<String> {
+        block_on(self.inner.get_ip())
+    }
+
+    /// Get printer's firmware version.
+    pub fn get_firmware_ver(&mut self) -> Result<String> {
+        block_on(self.inner.get_firmware_ver())
+    }
+
+    /// Get printer's firmware version, parsed from [`Printer::get_firmware_ver()`].
+    pub fn get_firmware_version(&mut self) -> Result<FirmwareVersion> {
+        block_on(self.inner.get_firmware_version())
+    }
+
+    /// Detect the [`Quirks`] of the printer's model and firmware,
+    /// which are applied automatically from then on.
+    /// Unparsable firmware versions get the default quirks.
+    ///
+    /// No firmware is known to need quirks yet, so this returns the defaults for now,
+    /// see [`Quirks::for_firmware()`].
+    pub fn detect_quirks(&mut self) -> Result<Quirks> {
+        block_on(self.inner.detect_quirks())
+    }
+
+    /// Force a quirk profile, instead of the detected one.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.inner.set_quirks(quirks);
+    }
+
+    /// The quirks in use, the defaults if [`Printer::detect_quirks()`] wasn't called.
+    pub fn quirks(&self) -> Quirks {
+        self.inner.quirks()
+    }
+
+    /// Get printer's serial number.
+    pub fn get_serial(&mut self) -> Result<String> {
+        block_on(self.inner.get_serial())
+    }
+
+    /// Get printer's hardware version.
+    pub fn get_hardware_ver(&mut self) -> Result<String> {
+        block_on(self.inner.get_hardware_ver())
+    }
+
+    /// Get printer's name.
+    pub fn get_name(&mut self) -> Result<String> {
+        block_on(self.inner.get_name())
+    }
+
+    /// Get printer's MAC address.
+    pub fn get_mac(&mut self) -> Result<MacAddr> {
+        block_on(self.inner.get_mac())
+    }
+
+    /// Get printer's battery level in percent, see [`Printer::get_battery_status()`].
+    pub fn get_battery(&mut self) -> Result<u8> {
+        block_on(self.inner.get_battery())
+    }
+
+    /// Get printer's battery state, including whether it is charging.
+    ///
+    /// The response has two bytes, the second one is the charge level.
+    /// The first one is **unverified**: it is assumed to be 0 while running on battery
+    /// and 1 while charging, but this hasn't been confirmed on real hardware.
+    /// Other values are reported as unknown.
+    pub fn get_battery_status(&mut self) -> Result<BatteryStatus> {
+        block_on(self.inner.get_battery_status())
+    }
+
+    /// Get printer's paper, lid and temperature status.
+    ///
+    /// This uses the standard ESC/POS real-time status requests (`DLE EOT n`), with `n` being
+    /// 2 (offline cause, bit 2: cover open), 3 (error cause, bit 6: recoverable error, i.e. overheating),
+    /// and 4 (paper sensor, bits 5 and 6: paper end).
+    /// Printers, that don't know these requests, don't answer and their status is unknown.
+    ///
+    /// These requests aren't part of the reverse engineered PeriPage protocol,
+    /// and it is not verified, that PeriPage printers answer them.
+    /// Only replies, that are a single byte with the fixed bits of a status byte, are used,
+    /// and a reply, that arrives after its request timed out, is discarded before the next request.
+    pub fn get_status(&mut self) -> Result<PrinterStatus> {
+        block_on(self.inner.get_status())
+    }
+
+    /// Check the status before printing each chunk, see [`Printer::print_image_chunked()`],
+    /// so printing fails fast with [`Error::PaperOut`], [`Error::CoverOpen`] or [`Error::Overheated`],
+    /// instead of silently producing blank output.
+    /// Checking is disabled again, if the printer doesn't report its status.
+    pub fn set_check_status(&mut self, check: bool) {
+        self.inner.set_check_status(check);
+    }
+
+    /// When to send [`END_OF_IMAGE`](proto::END_OF_IMAGE), see [`PrinterBuilder::end_of_image()`].
+    pub fn set_end_of_image(&mut self, mode: EndOfImage) {
+        self.inner.set_end_of_image(mode);
+    }
+
+    /// Adapt the delay between chunks to their black coverage, or use the fixed delay for `None`,
+    /// see [`PrinterBuilder::thermal_policy()`].
+    pub fn set_thermal_policy(&mut self, policy: Option<ThermalPolicy>) {
+        self.inner.set_thermal_policy(policy);
+    }
+
+    /// How images are framed, see [`PrinterBuilder::framing()`].
+    pub fn set_framing(&mut self, framing: Framing) {
+        self.inner.set_framing(framing);
+    }
+
+    /// Set printing concentration, raw levels are between `0..=2`,
+    /// see [`Capabilities::max_concentration`].
+    /// The named levels are mapped to the range of the model, see [`Printer::detect_model()`].
+    pub fn set_concentration(&mut self, c: impl Into<Concentration>) -> Result<()> {
+        block_on(self.inner.set_concentration(c))
+    }
+
+    /// The concentration set last, `None` if it wasn't set by this [`Printer`].
+    pub fn concentration(&self) -> Option<Concentration> {
+        self.inner.concentration()
+    }
+
+    /// Reset the printer.
+    /// This command has to be sent, before printing can be done.
+    pub fn reset(&mut self) -> Result<()> {
+        block_on(self.inner.reset())
+    }
+
+    /// Print ASCII text.
+    /// Please don't use this, better use a font rasterizer, like [cosmic-text](https://docs.rs/cosmic-text).
+    ///
+    /// # Printer Bugs (PeriPage A6)
+    /// - Only ASCII, no Unicode
+    /// - No ASCII escape sequences, except '\n' (line feed)
+    /// - Line wrapping is very buggy, sometimes it works, sometimes it discards the rest of the line.
+    /// - No font size/weight settings
+    pub fn print_text(&mut self, text: &str) -> Result<()> {
+        block_on(self.inner.print_text(text))
+    }
+
+    /// Print raw pixels.
+    ///
+    /// # Overheating
+    /// The printer can overheat, if too much black is being printed at once,
+    /// therefore it's better to use the [`Printer::print_image_chunked()`] function instead.
+    ///
+    /// # Printing Limitations
+    /// While the printer has a density of 203dpi,
+    /// printing very small things and thin lines should be avoided,
+    /// as the printer is simply not precise enough.
+    ///
+    /// # "Concentration"
+    /// The printing concentration can be adjusted with [`Printer::set_concentration()`],
+    /// to make the output brighter or darker.
+    ///
+    /// # Format
+    /// TODO: describe pixel format:
+    /// - monochrome
+    /// - 0=white, 1=black
+    /// - MSB: left, LSB: right
+    /// - must be multiples of `width/8` bytes
+    /// - must not be longer than `65535` rows
+    /// - due to accuracy constraints, printing single pixels should be avoided
+    ///
+    /// # Notes
+    /// Printing gray scale pictures is possible,
+    /// by using [dithering](https://en.wikipedia.org/wiki/Dithering) to convert them to monochrome first.
+    /// Similarly, color images must be first converted to gray scale.
+    /// The [image](https://docs.rs/image/latest/image/) crate can be used, to do the conversions.
+    pub fn print_image(&mut self, pixels: &[u8], width: u16) -> Result<()> {
+        block_on(self.inner.print_image(pixels, width))
+    }
+
+    /// Just like [`Printer::print_image()`], but breaks the pixels into rows of `chunk_height`.
+    /// This may be needed, to prevent the printer from overheating, while printing a long document.
+    /// `progress` is called after every chunk, pass `|_| {}` to ignore it.
+    pub fn print_image_chunked_ext(
+        &mut self,
+        pixels: &[u8],
+        width: u16,
+        chunk_height: u16,
+        delay: Duration,
+        progress: impl FnMut(Progress) + Send,
+    ) -> Result<()> {
+        block_on(
+            self.inner
+                .print_image_chunked_ext(pixels, width, chunk_height, delay, progress),
+        )
+    }
+
+    /// Like [`Printer::print_image_chunked_ext()`], with 24 rows per chunk,
+    /// and the delay of [`PrinterBuilder::chunk_delay()`] or [`PrinterBuilder::thermal_policy()`].
+    pub fn print_image_chunked(&mut self, pixels: &[u8], width: u16) -> Result<()> {
+        block_on(self.inner.print_image_chunked(pixels, width))
+    }
+
+    /// Print a [`Document`] in chunks, like [`Printer::print_image_chunked()`].
+    /// Runs of blank rows are fed instead of printed, which is faster and saves the print head.
+    pub fn print_document(&mut self, doc: &Document) -> Result<()> {
+        block_on(self.inner.print_document(doc))
+    }
+
+    /// Like [`Printer::print_document()`], but call `progress` after every chunk or feed.
+    pub fn print_document_ext(
+        &mut self,
+        doc: &Document,
+        progress: impl FnMut(Progress) + Send,
+    ) -> Result<()> {
+        block_on(self.inner.print_document_ext(doc, progress))
+    }
+
+    /// Print a whole job: reset the printer, set the concentration,
+    /// print the copies of `doc` like [`Printer::print_document()`] and feed the paper,
+    /// as configured by `opts`.
+    pub fn print(&mut self, doc: &Document, opts: &PrintOptions) -> Result<()> {
+        block_on(self.inner.print(doc, opts))
+    }
+
+    /// Like [`Printer::print()`], but call `progress` after every chunk or feed, counting the rows of all copies.
+    pub fn print_ext(
+        &mut self,
+        doc: &Document,
+        opts: &PrintOptions,
+        progress: impl FnMut(Progress) + Send,
+    ) -> Result<()> {
+        block_on(self.inner.print_ext(doc, opts, progress))
+    }
+
+    /// Like [`Printer::print()`], but take the rows of a 384 pixel wide image from an iterator,
+    /// which is consumed lazily, one chunk at a time.
+    /// This way, very long images never have to be in memory as a whole.
+    /// Since the rows can't be repeated, [`PrintOptions::copies`] must be 1.
+    pub fn print_rows(
+        &mut self,
+        rows: impl Iterator<Item = [u8; 48]> + Send,
+        opts: &PrintOptions,
+    ) -> Result<()> {
+        block_on(self.inner.print_rows(rows, opts))
+    }
+
+    /// Send several commands in a single transfer, like the Windows driver does,
+    /// e.g. [`RESET`](proto::RESET), [`INIT`](proto::INIT) and a [`PUSH`](proto::PUSH) as one header.
+    /// The timeout is that of images, see [`Timeouts::image`].
+    pub fn send_batch(&mut self, batch: &proto::CommandBatch) -> Result<()> {
+        block_on(self.inner.send_batch(batch))
+    }
+
+    /// Push out `num` rows of paper.
+    pub fn push(&mut self, num: u8) -> Result<()> {
+        block_on(self.inner.push(num))
+    }
+
+    /// Push out `mm` millimeters of paper, converted to rows using [`Capabilities::dpi`].
+    pub fn feed_mm(&mut self, mm: f32) -> Result<()> {
+        block_on(self.inner.feed_mm(mm))
+    }
+
+    /// Push out enough paper, that the last printed row reaches the tear bar.
+    pub fn feed_to_tear_off(&mut self) -> Result<()> {
+        block_on(self.inner.feed_to_tear_off())
+    }
+}
+
+impl Display for BatteryStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}%", self.percent)?;
+        match self.charging {
+            Some(true) => write!(f, ", charging"),
+            Some(false) => write!(f, ", discharging"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Display for MacAddr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let [x0, x1, x2, x3, x4, x5] = self.0;
+        write!(f, "{x0:02x}:{x1:02x}:{x2:02x}:{x3:02x}:{x4:02x}:{x5:02x}")
+    }
+}
+
+impl Debug for MacAddr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        <Self as Display>::fmt(self, f)
+    }
+}
+
+/// Parses `01:23:45:67:89:ab`, `-` is accepted as separator as well.
+impl FromStr for MacAddr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidArgument(format!("invalid MAC address: {s:?}"));
+        let mut mac = [0u8; 6];
+        let mut parts = s.split([':', '-']);
+        for b in &mut mac {
+            let part = parts.next().ok_or_else(invalid)?;
+            if part.len() != 2 || !part.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(invalid());
+            }
+            *b = u8::from_str_radix(part, 16).map_err(|_| invalid())?;
+        }
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(Self(mac))
+    }
+}
+
+impl TryFrom<&str> for MacAddr {
+    type Error = Error;
+
+    fn try_from(s: &str) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<[u8; 6]> for MacAddr {
+    fn from(mac: [u8; 6]) -> Self {
+        Self(mac)
+    }
+}
+
+impl From<MacAddr> for [u8; 6] {
+    fn from(mac: MacAddr) -> Self {
+        mac.0
+    }
+}
+
+/// Serialized as a string, like `01:23:45:67:89:ab`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for MacAddr {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for MacAddr {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EmulatorBackend, MockBackend, RecordingBackend, ReplayBackend};
+
+    fn printer(mock: &MockBackend) -> Printer {
+        PrinterBuilder::new()
+            .chunk_delay(Duration::ZERO)
+            .build(mock.clone())
+    }
+
+    /// A black document of `rows` rows, as wide as the print head.
+    fn black(rows: usize) -> Document {
+        Document::new(vec![0xff; rows * 48], 384).unwrap()
+    }
+
+    #[test]
+    fn query() {
+        let mock = MockBackend::new();
+        mock.expect_send(proto::GET_NAME)
+            .with_timeout(Duration::from_secs(3))
+            .respond(*b"PeriPage_A6");
+        let mut printer = printer(&mock);
+        assert_eq!(printer.get_name().unwrap(), "PeriPage_A6");
+        mock.assert_done();
+    }
+
+    #[test]
+    fn dropped_response() {
+        let mock = MockBackend::new();
+        mock.expect_send(proto::GET_BATTERY)
+            .respond([0, 50])
+            .drop_response();
+        let mut printer = printer(&mock);
+        assert!(matches!(printer.get_battery(), Err(Error::Timeout)));
+        mock.assert_done();
+    }
+
+    #[test]
+    fn dropped_response_is_retried() {
+        let mock = MockBackend::new();
+        mock.expect_send(proto::GET_BATTERY)
+            .respond([0, 50])
+            .drop_response()
+            .expect_send(proto::GET_BATTERY)
+            .respond([1, 80]);
+        let mut printer = PrinterBuilder::new().retries(1).build(mock.clone());
+        let status = printer.get_battery_status().unwrap();
+        assert_eq!(status.percent, 80);
+        assert_eq!(status.charging, Some(true));
+        mock.assert_done();
+    }
+
+    #[test]
+    fn late_status_reply() {
+        let mock = MockBackend::new();
+        mock.expect_send([0x10, 0x04, 2])
+            .respond([0x16])
+            .drop_response()
+            // the reply to the first request arrives late, and must not be taken for the second
+            .respond([0x16])
+            .expect_send([0x10, 0x04, 3])
+            .respond([0x52])
+            .expect_send([0x10, 0x04, 4])
+            .respond([0x12]);
+        let mut printer = printer(&mock);
+        let status = printer.get_status().unwrap();
+        assert_eq!(status.cover_open, None);
+        assert_eq!(status.overheated, Some(true));
+        assert_eq!(status.paper, Some(true));
+        mock.assert_done();
+    }
+
+    #[test]
+    fn short_write_is_not_resent() {
+        let mock = MockBackend::new();
+        mock.expect_send(*b"hello").short_write(2);
+        let mut printer = PrinterBuilder::new().retries(1).build(mock.clone());
+        assert!(matches!(printer.print_text("hello"), Err(Error::Timeout)));
+        // the rest of the text must not follow the part, that was written
+        assert_eq!(mock.sent(), [b"he".to_vec()]);
+        mock.assert_done();
+    }
+
+    #[test]
+    fn failed_query_is_resent() {
+        let mock = MockBackend::new();
+        mock.expect_send(proto::GET_NAME)
+            .fail(Error::Timeout)
+            .expect_send(proto::GET_NAME)
+            .respond(*b"PeriPage_A6");
+        let mut printer = PrinterBuilder::new().retries(1).build(mock.clone());
+        assert_eq!(printer.get_name().unwrap(), "PeriPage_A6");
+        mock.assert_done();
+    }
+
+    #[test]
+    fn short_write_without_retries() {
+        let mock = MockBackend::new();
+        mock.expect_send(*b"hello").short_write(2);
+        let mut printer = printer(&mock);
+        assert!(matches!(printer.print_text("hello"), Err(Error::Timeout)));
+        mock.assert_done();
+    }
+
+    #[test]
+    fn print_document() {
+        let mock = MockBackend::new();
+        let concentration = [0x10, 0xff, 0x10, 0x00, 2];
+        mock.expect_send(proto::RESET)
+            .expect_send(concentration)
+            .expect_send_any()
+            .expect_send(proto::END_OF_IMAGE)
+            .expect_send([0x1b, 0x4a, 30]);
+        let mut printer = printer(&mock);
+        let opts = PrintOptions {
+            concentration: Some(Concentration::from(2)),
+            feed: 30,
+            ..PrintOptions::default()
+        };
+        printer.print(&black(10), &opts).unwrap();
+        mock.assert_done();
+
+        let image = &mock.sent()[2];
+        assert_eq!(image[..8], [0x1d, 0x76, 0x30, 0x00, 48, 0, 10, 0]);
+        assert!(image[8..].iter().all(|&b| b == 0xff));
+    }
+
+    #[test]
+    fn print_rows_skips_long_blank_chunks() {
+        let mock = MockBackend::new();
+        mock.expect_send(proto::RESET)
+            .expect_send([0x1b, 0x4a, 0xff])
+            .expect_send([0x1b, 0x4a, 45]);
+        let mut printer = printer(&mock);
+        printer.set_quirks(Quirks {
+            max_chunk_rows: 300,
+            ..Quirks::default()
+        });
+        let opts = PrintOptions {
+            chunk_height: 300,
+            feed: 0,
+            ..PrintOptions::default()
+        };
+        printer
+            .print_rows(std::iter::repeat_n([0; 48], 300), &opts)
+            .unwrap();
+        mock.assert_done();
+    }
+
+    #[test]
+    fn end_of_image_per_job() {
+        let mock = MockBackend::new();
+        mock.expect_send(proto::RESET)
+            .expect_send_any()
+            // the printer's own setting is back for the next job
+            .expect_send(proto::RESET)
+            .expect_send_any()
+            .expect_send(proto::END_OF_IMAGE);
+        let mut printer = printer(&mock);
+        let opts = PrintOptions {
+            end_of_image: Some(EndOfImage::Never),
+            ..PrintOptions::default()
+        };
+        printer.print(&black(10), &opts).unwrap();
+        printer.print(&black(10), &PrintOptions::default()).unwrap();
+        mock.assert_done();
+    }
+
+    #[test]
+    fn resume_after_disconnect() {
+        let mock = MockBackend::new();
+        let concentration = [0x10, 0xff, 0x10, 0x00, 1];
+        mock.expect_send(concentration);
+        let mut printer = printer(&mock);
+        printer.set_concentration(1).unwrap();
+        let reconnect = mock.clone();
+        printer.set_reconnect(move || Ok(reconnect.reconnect()));
+
+        let chunk = 8 + 24 * 48;
+        mock.disconnect_after(proto::RESET.len() + chunk + proto::END_OF_IMAGE.len() + 100)
+            .expect_send(proto::RESET)
+            .expect_send_any()
+            .expect_send(proto::END_OF_IMAGE)
+            .expect_send_any()
+            // the printer comes back, is reset, gets its concentration, and the second chunk again
+            .expect_send(proto::RESET)
+            .expect_send(concentration)
+            .expect_send_any()
+            .expect_send(proto::END_OF_IMAGE);
+
+        let opts = PrintOptions {
+            resume_on_reconnect: true,
+            ..PrintOptions::default()
+        };
+        printer.print(&black(48), &opts).unwrap();
+        mock.assert_done();
+
+        let sent = mock.sent();
+        assert_eq!(sent[4].len(), 100);
+        assert_eq!(sent[7].len(), chunk);
+        assert_eq!(printer.concentration(), Some(Concentration::from(1)));
+    }
+
+    #[test]
+    fn disconnect_without_resume() {
+        let mock = MockBackend::new();
+        mock.disconnect_after(proto::RESET.len() + 10)
+            .expect_send(proto::RESET)
+            .expect_send_any();
+        let mut printer = printer(&mock);
+        let reconnect = mock.clone();
+        printer.set_reconnect(move || Ok(reconnect.reconnect()));
+        let result = printer.print(&black(24), &PrintOptions::default());
+        assert!(matches!(result, Err(Error::Disconnected)));
+        mock.assert_done();
+    }
+
+    #[test]
+    fn emulator_prints_the_document() {
+        let emulator = EmulatorBackend::new(Model::A6);
+        let mut printer = PrinterBuilder::new()
+            .chunk_delay(Duration::ZERO)
+            .build(emulator.clone());
+
+        // ink, a blank band, that is fed instead of printed, and ink again
+        let mut pixels = vec![0u8; 100 * 48];
+        for (i, b) in pixels[..30 * 48].iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        pixels[90 * 48..].fill(0x81);
+        let doc = Document::new(pixels, 384).unwrap();
+
+        printer.print(&doc, &PrintOptions::default()).unwrap();
+        assert_eq!(emulator.document(), doc);
+        assert_eq!(emulator.take_document(), doc);
+        assert_eq!(emulator.document().height(), 0);
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let path = std::env::temp_dir().join(format!("ppa6-test-{}", std::process::id()));
+
+        let mock = MockBackend::new();
+        mock.expect_send(proto::GET_SERIAL)
+            .respond(*b"A6123")
+            .expect_send(proto::GET_FIRMWARE_VER)
+            .respond(*b"V2.11_304dpi")
+            .drop_response();
+        let mut printer = Printer::new(RecordingBackend::new(mock.clone(), &path).unwrap());
+        assert_eq!(printer.get_serial().unwrap(), "A6123");
+        assert!(matches!(printer.get_firmware_ver(), Err(Error::Timeout)));
+        drop(printer);
+        mock.assert_done();
+
+        let mut replay = Printer::new(ReplayBackend::open(&path).unwrap());
+        assert_eq!(replay.get_serial().unwrap(), "A6123");
+        assert!(matches!(replay.get_firmware_ver(), Err(Error::Timeout)));
+        // the recording is over
+        assert!(matches!(replay.get_name(), Err(Error::Disconnected)));
+
+        let mut replay = Printer::new(ReplayBackend::open(&path).unwrap());
+        assert!(replay.get_name().is_err());
+
+        let _ = std::fs::remove_file(path.with_extension("bin"));
+        let _ = std::fs::remove_file(path.with_extension("jsonl"));
+    }
+}
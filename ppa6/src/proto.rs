@@ -0,0 +1,14 @@
+//! Raw commands of the printer's protocol, as sent by [`AsyncPrinter`](crate::AsyncPrinter).
+//!
+//! Most of them were found by sniffing the official Windows driver,
+//! the names describe what they seem to do.
+//! A [`CommandBatch`] combines several [`Command`]s into one transfer, like the Windows driver does.
+//!
+//! This is the [`ppa6-proto`](ppa6_proto) crate, which is `no_std` and only needs `alloc`,
+//! depend on it directly to build packets on embedded targets.
+
+pub use ppa6_proto::{
+    Command, CommandBatch, EncodeError, END_OF_IMAGE, GET_BATTERY, GET_FIRMWARE_VER,
+    GET_HARDWARE_VER, GET_IP, GET_MAC, GET_NAME, GET_SERIAL, GET_STATUS, INIT, PRINT_IMAGE, PUSH,
+    RESET, SET_CONCENTRATION,
+};
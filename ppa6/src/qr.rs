@@ -0,0 +1,378 @@
+//! QR codes and the payload formats commonly encoded in them.
+use std::fmt::{self, Display, Formatter};
+
+#[cfg(feature = "qr")]
+use qrcode::{Color, QrCode};
+
+#[cfg(feature = "qr")]
+use crate::{Document, Error, Result};
+
+/// Width of the white border around the code in modules, as required by the QR code spec.
+#[cfg(feature = "qr")]
+const QUIET_ZONE: usize = 4;
+
+/// Structured data, which scanner apps recognize and act upon.
+/// The encoded string is obtained with [`QrPayload::encode()`] or [`Display`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum QrPayload {
+    /// Arbitrary text.
+    Text(String),
+
+    /// A URL, opened in the browser.
+    Url(String),
+
+    /// A geographic location (RFC 5870), opened in a maps app.
+    Geo {
+        /// Latitude in degrees.
+        lat: f64,
+        /// Longitude in degrees.
+        lon: f64,
+    },
+
+    /// Wi-Fi credentials, phones join the network.
+    Wifi(Wifi),
+
+    /// A contact in the compact MECARD format.
+    MeCard(Contact),
+
+    /// A contact in the vCard 3.0 format.
+    VCard(Contact),
+}
+
+/// Wi-Fi network, see [`QrPayload::Wifi`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Wifi {
+    /// Name of the network.
+    pub ssid: String,
+
+    /// Password of the network, if any.
+    pub password: Option<String>,
+
+    /// Security of the network.
+    pub security: WifiSecurity,
+
+    /// The network doesn't broadcast its SSID.
+    pub hidden: bool,
+}
+
+/// Security of a [`Wifi`] network.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WifiSecurity {
+    /// WPA/WPA2/WPA3 personal.
+    #[default]
+    Wpa,
+    /// WEP, don't use this.
+    Wep,
+    /// Open network.
+    None,
+}
+
+/// Contact details, see [`QrPayload::MeCard`] and [`QrPayload::VCard`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Contact {
+    /// Full name, e.g. `Jane Doe`.
+    pub name: String,
+
+    /// Phone numbers.
+    pub tel: Vec<String>,
+
+    /// Email addresses.
+    pub email: Vec<String>,
+
+    /// Organization.
+    pub org: Option<String>,
+
+    /// Website.
+    pub url: Option<String>,
+
+    /// Postal address.
+    pub address: Option<String>,
+
+    /// Free-form note.
+    pub note: Option<String>,
+}
+
+/// Escape `\`, `;`, `,`, `:` and `"` with a backslash, as done by the `WIFI:` and `MECARD:` formats.
+fn escape_fields(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | ';' | ',' | ':' | '"') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Escape a vCard text value (RFC 2426, section 4).
+fn escape_vcard(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\n"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+impl Contact {
+    /// Split the full name into (family, given) names, by guessing that the last word is the family name.
+    fn split_name(&self) -> (&str, &str) {
+        match self.name.trim().rsplit_once(' ') {
+            Some((given, family)) => (family, given.trim()),
+            None => (self.name.trim(), ""),
+        }
+    }
+}
+
+impl QrPayload {
+    /// Get the string to be encoded in the QR code.
+    pub fn encode(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Display for QrPayload {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Text(s) | Self::Url(s) => f.write_str(s),
+            Self::Geo { lat, lon } => write!(f, "geo:{lat},{lon}"),
+            Self::Wifi(wifi) => {
+                write!(f, "WIFI:S:{};", escape_fields(&wifi.ssid))?;
+                match wifi.security {
+                    WifiSecurity::Wpa => f.write_str("T:WPA;")?,
+                    WifiSecurity::Wep => f.write_str("T:WEP;")?,
+                    WifiSecurity::None => f.write_str("T:nopass;")?,
+                }
+                if let Some(pass) = &wifi.password {
+                    write!(f, "P:{};", escape_fields(pass))?;
+                }
+                if wifi.hidden {
+                    f.write_str("H:true;")?;
+                }
+                f.write_str(";")
+            }
+            Self::MeCard(c) => {
+                let (family, given) = c.split_name();
+                write!(f, "MECARD:N:{}", escape_fields(family))?;
+                if !given.is_empty() {
+                    write!(f, ",{}", escape_fields(given))?;
+                }
+                f.write_str(";")?;
+                for tel in &c.tel {
+                    write!(f, "TEL:{};", escape_fields(tel))?;
+                }
+                for email in &c.email {
+                    write!(f, "EMAIL:{};", escape_fields(email))?;
+                }
+                let fields = [("ORG", &c.org), ("URL", &c.url), ("ADR", &c.address), ("NOTE", &c.note)];
+                for (key, value) in fields {
+                    if let Some(value) = value {
+                        write!(f, "{key}:{};", escape_fields(value))?;
+                    }
+                }
+                f.write_str(";")
+            }
+            Self::VCard(c) => {
+                let (family, given) = c.split_name();
+                write!(f, "BEGIN:VCARD\r\nVERSION:3.0\r\n")?;
+                write!(f, "N:{};{};;;\r\n", escape_vcard(family), escape_vcard(given))?;
+                write!(f, "FN:{}\r\n", escape_vcard(&c.name))?;
+                for tel in &c.tel {
+                    write!(f, "TEL:{}\r\n", escape_vcard(tel))?;
+                }
+                for email in &c.email {
+                    write!(f, "EMAIL:{}\r\n", escape_vcard(email))?;
+                }
+                if let Some(org) = &c.org {
+                    write!(f, "ORG:{}\r\n", escape_vcard(org))?;
+                }
+                if let Some(url) = &c.url {
+                    write!(f, "URL:{}\r\n", escape_vcard(url))?;
+                }
+                if let Some(adr) = &c.address {
+                    // the street is the only part of ADR we know
+                    write!(f, "ADR:;;{};;;;\r\n", escape_vcard(adr))?;
+                }
+                if let Some(note) = &c.note {
+                    write!(f, "NOTE:{}\r\n", escape_vcard(note))?;
+                }
+                f.write_str("END:VCARD")
+            }
+        }
+    }
+}
+
+/// Render `data` as a QR code, centered in a document `width` pixels wide.
+/// Modules are scaled up by the largest whole factor that fits.
+#[cfg(feature = "qr")]
+pub fn render(data: impl AsRef<[u8]>, width: u16) -> Result<Document> {
+    let code = QrCode::new(data)?;
+    let modules = code.width();
+    let colors = code.to_colors();
+
+    let scale = width as usize / (modules + 2 * QUIET_ZONE);
+    if scale == 0 {
+        return Err(Error::InvalidArgument(format!(
+            "QR code with {modules} modules doesn't fit into {width} pixels"
+        )));
+    }
+    if scale < 3 {
+        log::warn!("QR code modules are only {scale}px wide, scanning it may be unreliable");
+    }
+
+    let stride = width as usize / 8;
+    let size = modules * scale;
+    let x0 = (width as usize - size) / 2;
+    let y0 = QUIET_ZONE * scale;
+    let height = size + 2 * y0;
+
+    let mut pixels = vec![0u8; stride * height];
+    for (i, color) in colors.iter().enumerate() {
+        if *color == Color::Light {
+            continue;
+        }
+
+        let (mx, my) = (i % modules, i / modules);
+        for y in y0 + my * scale..y0 + (my + 1) * scale {
+            for x in x0 + mx * scale..x0 + (mx + 1) * scale {
+                pixels[y * stride + x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+    }
+
+    Document::new(pixels, width)
+}
+
+/// Render a [`QrPayload`] as a QR code, see [`render()`].
+#[cfg(feature = "qr")]
+pub fn render_payload(payload: &QrPayload, width: u16) -> Result<Document> {
+    render(payload.encode(), width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jane() -> Contact {
+        Contact {
+            name: "Jane Q. Doe".into(),
+            tel: vec!["+1 555 0100".into()],
+            email: vec!["jane@example.com".into()],
+            org: Some("Doe, Inc.".into()),
+            url: Some("https://example.com".into()),
+            ..Contact::default()
+        }
+    }
+
+    #[test]
+    fn text_and_geo() {
+        let url = QrPayload::Url("https://example.com/?a=1;b".into());
+        assert_eq!(url.encode(), "https://example.com/?a=1;b");
+        let geo = QrPayload::Geo {
+            lat: 48.2082,
+            lon: -16.3738,
+        };
+        assert_eq!(geo.encode(), "geo:48.2082,-16.3738");
+    }
+
+    #[test]
+    fn wifi() {
+        let wifi = QrPayload::Wifi(Wifi {
+            ssid: "Café;\"Guest\"".into(),
+            password: Some(r"p\a:ss,".into()),
+            hidden: true,
+            ..Wifi::default()
+        });
+        assert_eq!(
+            wifi.encode(),
+            r#"WIFI:S:Café\;\"Guest\";T:WPA;P:p\\a\:ss\,;H:true;;"#
+        );
+
+        let open = QrPayload::Wifi(Wifi {
+            ssid: "Open".into(),
+            security: WifiSecurity::None,
+            ..Wifi::default()
+        });
+        assert_eq!(open.encode(), "WIFI:S:Open;T:nopass;;");
+    }
+
+    #[test]
+    fn mecard() {
+        assert_eq!(
+            QrPayload::MeCard(jane()).encode(),
+            "MECARD:N:Doe,Jane Q.;TEL:+1 555 0100;EMAIL:jane@example.com;\
+             ORG:Doe\\, Inc.;URL:https\\://example.com;;"
+        );
+
+        let single = Contact {
+            name: "Cher".into(),
+            ..Contact::default()
+        };
+        assert_eq!(QrPayload::MeCard(single).encode(), "MECARD:N:Cher;;");
+    }
+
+    #[test]
+    fn vcard() {
+        let contact = Contact {
+            address: Some("Main St. 1".into()),
+            note: Some("line 1\nline 2".into()),
+            ..jane()
+        };
+        assert_eq!(
+            QrPayload::VCard(contact).encode(),
+            "BEGIN:VCARD\r\nVERSION:3.0\r\n\
+             N:Doe;Jane Q.;;;\r\n\
+             FN:Jane Q. Doe\r\n\
+             TEL:+1 555 0100\r\n\
+             EMAIL:jane@example.com\r\n\
+             ORG:Doe\\, Inc.\r\n\
+             URL:https://example.com\r\n\
+             ADR:;;Main St. 1;;;;\r\n\
+             NOTE:line 1\\nline 2\r\n\
+             END:VCARD"
+        );
+    }
+
+    #[cfg(feature = "qr")]
+    #[test]
+    fn render_matches_the_modules() {
+        let data = "https://example.com";
+        let doc = render(data, 384).unwrap();
+        let code = QrCode::new(data).unwrap();
+        let modules = code.width();
+        let colors = code.to_colors();
+
+        let scale = 384 / (modules + 2 * QUIET_ZONE);
+        let size = modules * scale;
+        let (x0, y0) = ((384 - size) / 2, QUIET_ZONE * scale);
+        assert_eq!(doc.height(), size + 2 * y0);
+
+        let black =
+            |x: usize, y: usize| doc.pixels()[y * doc.stride() + x / 8] & (0x80 >> (x % 8)) != 0;
+        for y in 0..doc.height() {
+            for x in 0..384 {
+                let inside = (x0..x0 + size).contains(&x) && (y0..y0 + size).contains(&y);
+                let expected =
+                    inside && colors[(y - y0) / scale * modules + (x - x0) / scale] == Color::Dark;
+                assert_eq!(black(x, y), expected, "pixel ({x}, {y})");
+            }
+        }
+    }
+
+    #[cfg(feature = "qr")]
+    #[test]
+    fn render_too_narrow() {
+        assert!(matches!(
+            render("https://example.com", 16),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+}
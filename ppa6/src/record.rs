@@ -0,0 +1,285 @@
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{Backend, Error, Model, Result, TransportStats};
+
+/// A [`Backend`], that records everything sent to and received from the printer,
+/// to reproduce protocol problems without a USB sniffer.
+///
+/// The raw bytes are appended to a `.bin` file, and a `.jsonl` index gets one JSON object per transfer,
+/// with its direction, time since the recording started (in seconds), timeout, error,
+/// and the offset and length of its bytes in the `.bin` file.
+/// The first line of the index has the start time as a UNIX timestamp.
+pub struct RecordingBackend<B> {
+    backend: B,
+    data: BufWriter<File>,
+    index: BufWriter<File>,
+    offset: u64,
+    start: Instant,
+}
+
+impl<B: Backend> RecordingBackend<B> {
+    /// Record the transfers of `backend` into `path` with the extensions `.bin` and `.jsonl`,
+    /// overwriting existing files.
+    pub fn new(backend: B, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = BufWriter::new(File::create(path.with_extension("bin"))?);
+        let mut index = BufWriter::new(File::create(path.with_extension("jsonl"))?);
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        writeln!(index, "{{\"start\":{:.6}}}", now.as_secs_f64())?;
+        index.flush()?;
+
+        Ok(Self {
+            backend,
+            data,
+            index,
+            offset: 0,
+            start: Instant::now(),
+        })
+    }
+
+    /// Get the recorded backend back.
+    pub fn into_inner(self) -> B {
+        self.backend
+    }
+
+    fn record(&mut self, dir: &str, buf: &[u8], timeout: Duration, error: Option<&Error>) {
+        let mut line = format!(
+            "{{\"dir\":\"{dir}\",\"time\":{:.6},\"timeout\":{:.3},\"offset\":{},\"len\":{}",
+            self.start.elapsed().as_secs_f64(),
+            timeout.as_secs_f64(),
+            self.offset,
+            buf.len(),
+        );
+        if let Some(e) = error {
+            line.push_str(",\"error\":");
+            json_string(&mut line, &e.to_string());
+        }
+        line.push('}');
+
+        let written = self
+            .data
+            .write_all(buf)
+            .and_then(|()| self.data.flush())
+            .and_then(|()| writeln!(self.index, "{line}"))
+            .and_then(|()| self.index.flush());
+        match written {
+            Ok(()) => self.offset += buf.len() as u64,
+            Err(e) => log::warn!("cannot record transfer: {e}"),
+        }
+    }
+}
+
+fn json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            ch if ch.is_control() => {
+                let _ = write!(out, "\\u{:04x}", ch as u32);
+            }
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+}
+
+impl<B: Backend> Backend for RecordingBackend<B> {
+    fn send(&mut self, buf: &[u8], timeout: Duration) -> Result<()> {
+        let result = self.backend.send(buf, timeout);
+        self.record("send", buf, timeout, result.as_ref().err());
+        result
+    }
+
+    fn recv(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        match self.backend.recv(buf, timeout) {
+            Ok(n) => {
+                self.record("recv", &buf[..n], timeout, None);
+                Ok(n)
+            }
+            Err(e) => {
+                self.record("recv", &[], timeout, Some(&e));
+                Err(e)
+            }
+        }
+    }
+
+    fn release(&mut self) -> Result<()> {
+        self.backend.release()
+    }
+
+    fn stats(&self) -> Option<TransportStats> {
+        self.backend.stats()
+    }
+
+    fn model(&self) -> Option<Model> {
+        self.backend.model()
+    }
+}
+
+/// One transfer of a recording.
+#[derive(Debug, Clone)]
+struct Transfer {
+    send: bool,
+    data: Vec<u8>,
+    error: Option<String>,
+}
+
+/// A [`Backend`], that plays back a session captured by [`RecordingBackend`],
+/// to exercise code without a printer.
+///
+/// Every transfer has to match the next one in the recording,
+/// [`Backend::recv()`] returns the recorded response, and recorded errors are returned again.
+/// After the end of the recording, transfers fail with [`Error::Disconnected`].
+pub struct ReplayBackend {
+    transfers: std::vec::IntoIter<Transfer>,
+    strict: bool,
+}
+
+impl ReplayBackend {
+    /// Open the recording at `path`, without extension, see [`RecordingBackend::new()`].
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read(path.with_extension("bin"))?;
+        let index = std::fs::read_to_string(path.with_extension("jsonl"))?;
+
+        let invalid = |line: &str| Error::InvalidArgument(format!("invalid recording: {line}"));
+        let mut transfers = Vec::new();
+        for line in index.lines().filter(|l| l.contains("\"dir\":")) {
+            let number = |name| -> Result<usize> {
+                field(line, name)
+                    .and_then(|v| v.parse().ok())
+                    .ok_or_else(|| invalid(line))
+            };
+            let (offset, len) = (number("offset")?, number("len")?);
+            let data = data
+                .get(offset..offset + len)
+                .ok_or_else(|| invalid(line))?;
+            transfers.push(Transfer {
+                send: field(line, "dir") == Some("\"send\""),
+                data: data.to_vec(),
+                error: field(line, "error").map(unescape),
+            });
+        }
+
+        Ok(Self {
+            transfers: transfers.into_iter(),
+            strict: true,
+        })
+    }
+
+    /// Whether sent data has to match the recording exactly, default: `true`.
+    /// Otherwise, differences are only logged.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    fn next(&mut self, send: bool) -> Result<Transfer> {
+        let Some(transfer) = self.transfers.next() else {
+            return Err(Error::Disconnected);
+        };
+        if transfer.send != send {
+            let expected = if transfer.send { "send" } else { "recv" };
+            return Err(Error::Other(
+                format!("replay out of sync, the recording has a {expected} next").into(),
+            ));
+        }
+        Ok(transfer)
+    }
+}
+
+/// Get the raw value of `"name":` in a JSON object, that the [`RecordingBackend`] wrote.
+fn field<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let start = line.find(&format!("\"{name}\":"))? + name.len() + 3;
+    let rest = &line[start..];
+    if let Some(string) = rest.strip_prefix('"') {
+        // strings end at the first unescaped quote
+        let mut escaped = false;
+        let end = string.find(|ch| {
+            let end = ch == '"' && !escaped;
+            escaped = ch == '\\' && !escaped;
+            end
+        })?;
+        Some(&rest[..end + 2])
+    } else {
+        rest.find([',', '}']).map(|end| &rest[..end])
+    }
+}
+
+/// Undo the escaping of [`json_string()`].
+fn unescape(s: &str) -> String {
+    let s = s
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(s);
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            out.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                out.extend(u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32));
+            }
+            Some(ch) => out.push(ch),
+            None => {}
+        }
+    }
+    out
+}
+
+/// Map the messages of recorded errors back to the errors, where possible.
+fn replay_error(msg: &str) -> Error {
+    match msg {
+        "timed out" => Error::Timeout,
+        "printer disconnected" => Error::Disconnected,
+        msg => Error::Other(msg.into()),
+    }
+}
+
+impl Backend for ReplayBackend {
+    fn send(&mut self, buf: &[u8], _timeout: Duration) -> Result<()> {
+        let transfer = self.next(true)?;
+        if transfer.data != buf {
+            if self.strict {
+                return Err(Error::Other(
+                    format!(
+                        "replay mismatch, sent {buf:02x?}, but the recording has {:02x?}",
+                        transfer.data
+                    )
+                    .into(),
+                ));
+            }
+            log::warn!("sent data differs from the recording");
+        }
+        match transfer.error {
+            Some(e) => Err(replay_error(&e)),
+            None => Ok(()),
+        }
+    }
+
+    fn recv(&mut self, buf: &mut [u8], _timeout: Duration) -> Result<usize> {
+        let transfer = self.next(false)?;
+        if let Some(e) = transfer.error {
+            return Err(replay_error(&e));
+        }
+        let n = transfer.data.len().min(buf.len());
+        buf[..n].copy_from_slice(&transfer.data[..n]);
+        Ok(n)
+    }
+}
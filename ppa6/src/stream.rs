@@ -0,0 +1,256 @@
+use std::{
+	io::{self, Read, Write},
+	time::{Duration, Instant},
+};
+
+use crate::{Backend, Error, Result};
+
+/// Once a response started arriving, wait at most this long for the rest of it.
+const RECV_GAP: Duration = Duration::from_millis(50);
+
+/// What [`PollStrategy::ready()`] waits for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interest {
+	/// Data can be read.
+	Read,
+
+	/// Data can be written.
+	Write,
+}
+
+/// How an [`IoBackend`] implements timeouts for its stream.
+pub trait PollStrategy<T> {
+	/// Wait at most `timeout`, until `io` is ready for `interest`, returns whether it is.
+	///
+	/// Strategies, that set the timeout of the stream instead, return `true`,
+	/// and let the next read or write fail with [`io::ErrorKind::WouldBlock`] or [`io::ErrorKind::TimedOut`].
+	fn ready(&mut self, io: &T, interest: Interest, timeout: Duration) -> Result<bool>;
+
+	/// Whether [`PollStrategy::ready()`] honors the timeout at all.
+	/// Otherwise, responses are read with a single read, because waiting for more may block forever.
+	fn has_timeouts(&self) -> bool {
+		true
+	}
+}
+
+/// Ignore timeouts, reads and writes block until the stream is ready.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoTimeout;
+
+impl<T> PollStrategy<T> for NoTimeout {
+	fn ready(&mut self, _io: &T, _interest: Interest, _timeout: Duration) -> Result<bool> {
+		Ok(true)
+	}
+
+	fn has_timeouts(&self) -> bool {
+		false
+	}
+}
+
+/// Set the timeout of the stream before every read and write with a function,
+/// e.g. `|s: &TcpStream, _, t| s.set_read_timeout(Some(t)).and(s.set_write_timeout(Some(t)))`.
+#[derive(Debug, Clone, Copy)]
+pub struct SetTimeout<F>(pub F);
+
+impl<T, F> PollStrategy<T> for SetTimeout<F>
+where
+	F: FnMut(&T, Interest, Duration) -> io::Result<()>,
+{
+	fn ready(&mut self, io: &T, interest: Interest, timeout: Duration) -> Result<bool> {
+		// a zero timeout means no timeout for most streams
+		(self.0)(io, interest, timeout.max(Duration::from_millis(1)))?;
+		Ok(true)
+	}
+}
+
+/// Wait for a file descriptor with `poll(2)`, for pipes, PTYs, sockets, and serial ports.
+#[cfg(all(unix, feature = "poll"))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FdPoll;
+
+#[cfg(all(unix, feature = "poll"))]
+impl<T: std::os::fd::AsRawFd> PollStrategy<T> for FdPoll {
+	fn ready(&mut self, io: &T, interest: Interest, timeout: Duration) -> Result<bool> {
+		let mut pfd = libc::pollfd {
+			fd: io.as_raw_fd(),
+			events: match interest {
+				Interest::Read => libc::POLLIN,
+				Interest::Write => libc::POLLOUT,
+			},
+			revents: 0,
+		};
+		// a signal interrupts the wait, which is continued for the time, that's left
+		let deadline = Instant::now() + timeout;
+		let r = loop {
+			let left = deadline.saturating_duration_since(Instant::now());
+			let ms = left.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+			let r = unsafe { libc::poll(&mut pfd, 1, ms) };
+			if r >= 0 {
+				break r;
+			}
+			let e = io::Error::last_os_error();
+			if e.kind() != io::ErrorKind::Interrupted {
+				return Err(e.into());
+			}
+		};
+		if pfd.revents & (libc::POLLERR | libc::POLLNVAL) != 0 {
+			return Err(Error::Disconnected);
+		}
+		// after a hangup, data that arrived before it can still be read
+		if pfd.revents & libc::POLLHUP != 0 && pfd.revents & pfd.events == 0 {
+			return Err(Error::Disconnected);
+		}
+		Ok(r > 0)
+	}
+}
+
+/// A [`Backend`] over any stream, e.g. an RFCOMM socket, a PTY, a serial port, or a pipe in tests,
+/// with timeouts implemented by a [`PollStrategy`].
+pub struct IoBackend<T, P = NoTimeout> {
+	io: T,
+	poll: P,
+}
+
+impl<T: Read + Write> IoBackend<T> {
+	/// Use `io` without timeouts, see [`NoTimeout`].
+	pub fn new(io: T) -> Self {
+		Self::with_poll(io, NoTimeout)
+	}
+}
+
+impl<T: Read + Write, P: PollStrategy<T>> IoBackend<T, P> {
+	/// Use `io` with timeouts implemented by `poll`.
+	pub fn with_poll(io: T, poll: P) -> Self {
+		Self { io, poll }
+	}
+
+	/// Get a reference to the stream.
+	pub fn get_ref(&self) -> &T {
+		&self.io
+	}
+
+	/// Get a mutable reference to the stream.
+	pub fn get_mut(&mut self) -> &mut T {
+		&mut self.io
+	}
+
+	/// Get the stream back.
+	pub fn into_inner(self) -> T {
+		self.io
+	}
+}
+
+fn timed_out(e: &io::Error) -> bool {
+	matches!(
+		e.kind(),
+		io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+	)
+}
+
+impl<T: Read + Write, P: PollStrategy<T>> Backend for IoBackend<T, P> {
+	fn send(&mut self, buf: &[u8], timeout: Duration) -> Result<()> {
+		let deadline = Instant::now() + timeout;
+		let mut ns = 0;
+		while ns < buf.len() {
+			let left = deadline.saturating_duration_since(Instant::now());
+			if !self.poll.ready(&self.io, Interest::Write, left)? {
+				log::debug!("timed out after sending {ns} of {} bytes", buf.len());
+				return Err(Error::Timeout);
+			}
+			match self.io.write(&buf[ns..]) {
+				Ok(0) => return Err(Error::Disconnected),
+				Ok(n) => ns += n,
+				Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+				// a non-blocking stream may not be ready after all
+				Err(e) if timed_out(&e) && Instant::now() < deadline => {}
+				Err(e) if timed_out(&e) => {
+					log::debug!("timed out after sending {ns} of {} bytes", buf.len());
+					return Err(Error::Timeout);
+				}
+				Err(e) => return Err(e.into()),
+			}
+		}
+		self.io.flush()?;
+		Ok(())
+	}
+
+	fn recv(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+		let mut deadline = Instant::now() + timeout;
+		let mut nr = 0;
+		while nr < buf.len() {
+			let left = deadline.saturating_duration_since(Instant::now());
+			if !self.poll.ready(&self.io, Interest::Read, left)? {
+				break;
+			}
+			match self.io.read(&mut buf[nr..]) {
+				Ok(0) if nr == 0 => return Err(Error::Disconnected),
+				Ok(0) => break,
+				Ok(n) => {
+					nr += n;
+					// without timeouts, waiting for more may block forever
+					if !self.poll.has_timeouts() {
+						break;
+					}
+					// the rest of the response may come in more reads, shortly after
+					deadline = Instant::now() + RECV_GAP;
+				}
+				Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+				Err(e) if timed_out(&e) && Instant::now() < deadline => {}
+				Err(e) if timed_out(&e) => break,
+				Err(e) => return Err(e.into()),
+			}
+		}
+
+		if nr == 0 && !buf.is_empty() {
+			return Err(Error::Timeout);
+		}
+		Ok(nr)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[cfg(all(unix, feature = "poll"))]
+	#[test]
+	fn fd_poll() {
+		use std::os::unix::net::UnixStream;
+
+		let (a, mut b) = UnixStream::pair().unwrap();
+		a.set_nonblocking(true).unwrap();
+		let mut backend = IoBackend::with_poll(a, FdPoll);
+		let mut buf = [0u8; 8];
+		assert!(matches!(
+			backend.recv(&mut buf, Duration::from_millis(10)),
+			Err(Error::Timeout)
+		));
+
+		backend.send(b"ping", Duration::from_secs(1)).unwrap();
+		let mut ping = [0u8; 4];
+		b.read_exact(&mut ping).unwrap();
+		assert_eq!(&ping, b"ping");
+
+		// data sent before the hangup is still received
+		b.write_all(b"pong").unwrap();
+		drop(b);
+		let n = backend.recv(&mut buf, Duration::from_secs(1)).unwrap();
+		assert_eq!(&buf[..n], b"pong");
+		assert!(matches!(
+			backend.recv(&mut buf, Duration::from_secs(1)),
+			Err(Error::Disconnected)
+		));
+	}
+
+	#[test]
+	fn no_timeout() {
+		let mut backend = IoBackend::new(io::Cursor::new(b"abc".to_vec()));
+		let mut buf = [0u8; 2];
+		assert_eq!(backend.recv(&mut buf, Duration::ZERO).unwrap(), 2);
+		assert_eq!(backend.recv(&mut buf, Duration::ZERO).unwrap(), 1);
+		assert!(matches!(
+			backend.recv(&mut buf, Duration::ZERO),
+			Err(Error::Disconnected)
+		));
+	}
+}
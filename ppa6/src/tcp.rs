@@ -0,0 +1,82 @@
+use std::{
+	io::{self, Read, Write},
+	net::{TcpStream, ToSocketAddrs},
+	time::Duration,
+};
+use crate::{Backend, Error, Result};
+
+/// Once a response started arriving, wait at most this long for the rest of it.
+const RECV_GAP: Duration = Duration::from_millis(50);
+
+/// A backend for [`Printer`](crate::Printer), that talks to a printer shared over the network by a relay,
+/// which passes the raw bytes through to the device, like port 9100 of a network printer,
+/// e.g. `socat TCP-LISTEN:9100,fork,reuseaddr FILE:/dev/usb/lp0` on a Raspberry Pi.
+pub struct TcpBackend {
+	stream: TcpStream,
+}
+
+impl TcpBackend {
+	/// Connect to a relay, e.g. `"raspberrypi.local:9100"`.
+	pub fn connect(addr: impl ToSocketAddrs) -> Result<Self> {
+		let stream = TcpStream::connect(addr)?;
+		stream.set_nodelay(true)?;
+		log::info!("TCP: connected to {}", stream.peer_addr()?);
+		Ok(Self { stream })
+	}
+
+	/// Like [`TcpBackend::connect()`], but give up after `timeout`, if the relay doesn't answer.
+	pub fn connect_timeout(addr: impl ToSocketAddrs, timeout: Duration) -> Result<Self> {
+		let mut last = None;
+		for addr in addr.to_socket_addrs()? {
+			match TcpStream::connect_timeout(&addr, timeout) {
+				Ok(stream) => {
+					stream.set_nodelay(true)?;
+					log::info!("TCP: connected to {addr}");
+					return Ok(Self { stream });
+				}
+				Err(e) => last = Some(e),
+			}
+		}
+		Err(match last {
+			Some(e) => e.into(),
+			None => Error::InvalidArgument("address resolved to nothing".into()),
+		})
+	}
+}
+
+/// Reads and writes, that ran into their timeout, fail with `WouldBlock` on some platforms,
+/// and with `TimedOut` on others, e.g. Windows.
+fn timed_out(e: io::Error) -> Error {
+	match e.kind() {
+		io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => Error::Timeout,
+		_ => e.into(),
+	}
+}
+
+impl Backend for TcpBackend {
+	fn send(&mut self, buf: &[u8], timeout: Duration) -> Result<()> {
+		self.stream.set_write_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+		self.stream.write_all(buf).map_err(timed_out)?;
+		Ok(())
+	}
+
+	fn recv(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+		self.stream.set_read_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+		let mut nr = match self.stream.read(buf).map_err(timed_out)? {
+			0 => return Err(Error::Disconnected),
+			n => n,
+		};
+
+		// the rest of the response may come in more segments
+		self.stream.set_read_timeout(Some(RECV_GAP))?;
+		while nr < buf.len() {
+			match self.stream.read(&mut buf[nr..]) {
+				Ok(0) => break,
+				Ok(n) => nr += n,
+				Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => break,
+				Err(e) => return Err(e.into()),
+			}
+		}
+		Ok(nr)
+	}
+}
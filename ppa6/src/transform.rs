@@ -0,0 +1,219 @@
+//! Transformations of [`Document`]s, applied to every job in order, e.g. for site-specific policies.
+//!
+//! A [`Chain`] holds [`Transform`]s, like trimming whitespace, stamping a header,
+//! limiting the heat of dark areas, or mirroring the image.
+
+use crate::{Document, Result};
+
+/// Bayer matrix for thinning out pixels evenly, see [`ThermalLimit`].
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// A step of a [`Chain`].
+pub trait Transform {
+    /// Short name for log messages, e.g. `"trim"`.
+    fn name(&self) -> &str;
+
+    /// Transform `doc`.
+    fn apply(&self, doc: Document) -> Result<Document>;
+}
+
+/// An ordered list of [`Transform`]s.
+#[derive(Default)]
+pub struct Chain {
+    transforms: Vec<Box<dyn Transform + Send + Sync>>,
+}
+
+impl Chain {
+    /// Create a chain, that doesn't change documents.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append `transform` to the chain.
+    pub fn push(&mut self, transform: impl Transform + Send + Sync + 'static) -> &mut Self {
+        self.transforms.push(Box::new(transform));
+        self
+    }
+
+    /// Whether the chain has no transforms.
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    /// Apply every transform to `doc`, in order.
+    pub fn apply(&self, mut doc: Document) -> Result<Document> {
+        for t in &self.transforms {
+            log::debug!("applying transform {}", t.name());
+            doc = t.apply(doc)?;
+        }
+        Ok(doc)
+    }
+}
+
+/// Remove blank rows at the top and bottom, but keep margins, see [`Document::trim_whitespace()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Trim {
+    /// Blank rows to keep at the top.
+    pub top: usize,
+
+    /// Blank rows to keep at the bottom.
+    pub bottom: usize,
+}
+
+impl Transform for Trim {
+    fn name(&self) -> &str {
+        "trim"
+    }
+
+    fn apply(&self, mut doc: Document) -> Result<Document> {
+        doc.trim_whitespace(self.top, self.bottom);
+        Ok(doc)
+    }
+}
+
+/// Stamp a text into the corner of the first printed row, see [`Document::stamp()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stamp(pub String);
+
+impl Transform for Stamp {
+    fn name(&self) -> &str {
+        "stamp"
+    }
+
+    fn apply(&self, mut doc: Document) -> Result<Document> {
+        doc.stamp(&self.0);
+        Ok(doc)
+    }
+}
+
+/// Flip the document horizontally, e.g. for iron-on transfers or printing on the back of transparent labels.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Mirror;
+
+impl Transform for Mirror {
+    fn name(&self) -> &str {
+        "mirror"
+    }
+
+    fn apply(&self, mut doc: Document) -> Result<Document> {
+        let stride = doc.stride();
+        for row in doc.pixels_mut().chunks_mut(stride) {
+            row.reverse();
+            row.iter_mut().for_each(|b| *b = b.reverse_bits());
+        }
+        Ok(doc)
+    }
+}
+
+/// Thin out dark areas, so no band of rows is blacker than `max_coverage` (between `0.0` and `1.0`),
+/// which keeps the print head from overheating and saves battery.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThermalLimit {
+    /// Highest fraction of black pixels in a band.
+    pub max_coverage: f32,
+
+    /// Rows per band.
+    pub band_height: usize,
+}
+
+impl Default for ThermalLimit {
+    fn default() -> Self {
+        Self {
+            max_coverage: 0.5,
+            band_height: 24,
+        }
+    }
+}
+
+impl Transform for ThermalLimit {
+    fn name(&self) -> &str {
+        "thermal-limit"
+    }
+
+    fn apply(&self, mut doc: Document) -> Result<Document> {
+        let stride = doc.stride();
+        let band = self.band_height.max(1);
+        for (i, rows) in doc.pixels_mut().chunks_mut(stride * band).enumerate() {
+            let black: usize = rows.iter().map(|b| b.count_ones() as usize).sum();
+            let coverage = black as f32 / (rows.len() * 8) as f32;
+            if coverage <= self.max_coverage {
+                continue;
+            }
+
+            // keep this many of every 16 black pixels, spread out by the Bayer matrix
+            let keep = (self.max_coverage.max(0.0) / coverage * 16.0) as u8;
+            log::debug!(
+                "band {i} is {:.0}% black, keeping {keep}/16 of its pixels",
+                coverage * 100.0
+            );
+            for (y, row) in rows.chunks_mut(stride).enumerate() {
+                for (bx, byte) in row.iter_mut().enumerate() {
+                    for bit in 0..8 {
+                        if BAYER[y % 4][(bx * 8 + bit) % 4] >= keep {
+                            *byte &= !(0x80 >> bit);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(doc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coverage(rows: &[u8]) -> f32 {
+        let black: u32 = rows.iter().map(|b| b.count_ones()).sum();
+        black as f32 / (rows.len() * 8) as f32
+    }
+
+    #[test]
+    fn mirror() {
+        let doc = Document::new(vec![0x80, 0x00, 0x0f, 0x01, 0xc0, 0x00], 16).unwrap();
+        let mirrored = Mirror.apply(doc.clone()).unwrap();
+        assert_eq!(mirrored.pixels(), [0x00, 0x01, 0x80, 0xf0, 0x00, 0x03]);
+        assert_eq!(Mirror.apply(mirrored).unwrap(), doc);
+    }
+
+    #[test]
+    fn thermal_limit() {
+        // a black band, a band at the limit, and a band above it
+        let mut pixels = vec![0xff; 48 * 24];
+        pixels.extend([0xf0; 48 * 24]);
+        pixels.extend([0xfc; 48 * 24]);
+        let doc = Document::new(pixels, 384).unwrap();
+
+        let limit = ThermalLimit::default();
+        let out = limit.apply(doc.clone()).unwrap();
+        for (i, band) in out.pixels().chunks(48 * 24).enumerate() {
+            assert!(coverage(band) <= 0.5, "band {i} is too black");
+        }
+        // bands within the limit are untouched
+        assert_eq!(out.rows(24..48), doc.rows(24..48));
+        // the others are thinned out, not cleared
+        assert!(coverage(out.rows(0..24)) >= 0.45);
+        assert!(coverage(out.rows(48..72)) >= 0.35);
+
+        // only black pixels are removed
+        for (a, b) in doc.pixels().iter().zip(out.pixels()) {
+            assert_eq!(a & b, *b);
+        }
+    }
+
+    #[test]
+    fn chain() {
+        let mut pixels = vec![0u8; 2 * 8];
+        pixels[6] = 0x80;
+        let doc = Document::new(pixels, 16).unwrap();
+
+        let mut chain = Chain::new();
+        assert!(chain.is_empty());
+        assert_eq!(chain.apply(doc.clone()).unwrap(), doc);
+
+        chain.push(Trim { top: 0, bottom: 1 }).push(Mirror);
+        let out = chain.apply(doc).unwrap();
+        assert_eq!(out.pixels(), [0x00, 0x01, 0x00, 0x00]);
+    }
+}
@@ -1,11 +1,26 @@
-use std::time::Duration;
-use anyhow::{Result, Context};
-use rusb::{Direction, GlobalContext, TransferType};
+use std::{
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		mpsc, Arc,
+	},
+	thread,
+	time::{Duration, Instant},
+};
+use rusb::{Direction, GlobalContext, Hotplug, HotplugBuilder, Registration, TransferType, UsbContext};
+#[cfg(unix)]
+use std::os::fd::RawFd;
 
 const VENDOR_ID: u16 = 0x09c5;
 const PRODUCT_ID: u16 = 0x0200;
 
-use crate::Backend;
+use crate::{Backend, Backoff, DeviceLock, Error, Model, Result, TransportStats};
+
+/// Transfers smaller than this are too short for a meaningful throughput.
+const MIN_MEASURED_SIZE: usize = 4096;
+
+/// Even while the printer is busy feeding paper, it accepts data faster than this.
+/// Anything slower usually means a bad cable or hub.
+const SLOW_THROUGHPUT: f64 = 2048.0;
 
 pub type Device = rusb::Device<GlobalContext>;
 pub type DeviceHandle = rusb::DeviceHandle<GlobalContext>;
@@ -15,6 +30,9 @@ pub struct UsbBackend {
 	handle: DeviceHandle,
 	epin: u8,
 	epout: u8,
+	stats: TransportStats,
+	model: Option<Model>,
+	_lock: Option<DeviceLock>,
 }
 
 impl UsbBackend {
@@ -36,41 +54,85 @@ impl UsbBackend {
 
 	/// Open a USB printing device.
 	pub fn open(dev: &Device) -> Result<Self> {
-		let handle = dev
-			.open()
-			.context("cannot open usb device")?;
+		Self::open_with(dev, None)
+	}
+
+	/// Like [`UsbBackend::open()`], but take the [`DeviceLock`] for the printer's serial number first,
+	/// and wait at most `wait` for the lock and for the interface to become free.
+	pub fn open_exclusive(dev: &Device, wait: Duration) -> Result<Self> {
+		Self::open_with(dev, Some(wait))
+	}
+
+	/// Use a USB device, that was opened by someone else, e.g. by Android's `UsbManager.openDevice()`,
+	/// which is the only way for apps to access USB devices on Android.
+	///
+	/// On Android, libusb's device discovery is disabled, because apps can't scan the bus,
+	/// this only works, if libusb wasn't used before.
+	/// Other USB devices than PeriPage printers are rejected with [`Error::Unsupported`].
+	///
+	/// # Safety
+	/// `fd` must be the open file descriptor of a USB device, and stay open as long as the backend.
+	#[cfg(unix)]
+	pub unsafe fn from_fd(fd: RawFd) -> Result<Self> {
+		#[cfg(target_os = "android")]
+		if let Err(e) = rusb::disable_device_discovery() {
+			log::warn!("cannot disable USB device discovery: {e}");
+		}
+
+		let handle = unsafe { GlobalContext::default().open_device_with_fd(fd)? };
+		Self::with_handle(handle, None)
+	}
+
+	fn open_with(dev: &Device, wait: Option<Duration>) -> Result<Self> {
+		Self::with_handle(dev.open()?, wait)
+	}
+
+	fn with_handle(handle: DeviceHandle, wait: Option<Duration>) -> Result<Self> {
+		let start = Instant::now();
+		let dev = &handle.device();
 
 		// automatically steal the USB device from the kernel
 		let _ = handle.set_auto_detach_kernel_driver(true);
 
-		let dd = dev
-			.device_descriptor()
-			.context("cannot get usb device descriptor")?;
+		let dd = dev.device_descriptor()?;
 
 		log::debug!("USB device descriptor = {dd:#?}");
+		// from_fd() can be given any USB device
+		if dd.vendor_id() != VENDOR_ID || dd.product_id() != PRODUCT_ID {
+			return Err(Error::Unsupported(format!("USB device {:04x}:{:04x} is not a PeriPage printer", dd.vendor_id(), dd.product_id())));
+		}
 		if let Ok(s) = handle.read_manufacturer_string_ascii(&dd) {
 			log::info!("USB Vendor: {s}");
 		}
-		if let Ok(s) = handle.read_product_string_ascii(&dd) {
+		let product = handle.read_product_string_ascii(&dd).ok();
+		if let Some(s) = &product {
 			log::info!("USB Product: {s}");
 		}
-		if let Ok(s) = handle.read_serial_number_string_ascii(&dd) {
+		let serial = handle.read_serial_number_string_ascii(&dd).ok();
+		if let Some(s) = &serial {
 			log::info!("USB Serial: {s}");
 		}
 
+		let lock = match wait {
+			Some(wait) => {
+				let id = serial.unwrap_or_else(|| format!("usb-{}-{}", dev.bus_number(), dev.address()));
+				Some(DeviceLock::acquire(&id, wait)?)
+			},
+			None => None,
+		};
+
 		// PeriPage A6 has only one config.
 		debug_assert_eq!(dd.num_configurations(), 1);
 
-		let cd = dev
-			.config_descriptor(0)
-			.context("cannot get usb config descriptor")?;
+		let cd = dev.config_descriptor(0)?;
 		log::debug!("USB configuration descriptor 0: {cd:#?}");
 
 		// PeriPage A6 has only one interface.
 		debug_assert_eq!(cd.num_interfaces(), 1);
 
-		let int = cd.interfaces().next().unwrap();
-		let id = int.descriptors().next().unwrap();
+		let unsupported = |what: &str| Error::Unsupported(format!("USB printer without {what}"));
+		let int = cd.interfaces().next().ok_or_else(|| unsupported("an interface"))?;
+		let id = int.descriptors().next().ok_or_else(|| unsupported("an interface descriptor"))?;
 		log::debug!("USB interface descriptor 0 for configuration 0: {id:#?}");
 		if let Some(sid) = id.description_string_index() {
 			log::debug!("Interface: {}", handle.read_string_descriptor_ascii(sid)?);
@@ -81,46 +143,273 @@ impl UsbBackend {
 		debug_assert_eq!(id.class_code(), 7); // Printer
 		debug_assert_eq!(id.sub_class_code(), 1); // Printer
 		debug_assert_eq!(id.protocol_code(), 2); // Bi-directional
-		assert_eq!(id.num_endpoints(), 2); 
+		if id.num_endpoints() != 2 {
+			return Err(unsupported("two endpoints"));
+		}
 
 		let mut endps = id.endpoint_descriptors();
-		let epd0 = endps.next().unwrap();
-		let epd1 = endps.next().unwrap();
-		debug_assert!(endps.next().is_none());
+		let (Some(epd0), Some(epd1)) = (endps.next(), endps.next()) else {
+			return Err(unsupported("two endpoints"));
+		};
 
 		log::debug!("USB endpoint descriptor 0: {epd0:#?}");
 		log::debug!("USB endpoint descriptor 1: {epd1:#?}");
 
-		debug_assert_eq!(epd0.address(), 129); // IN (128) + 1
-		assert_eq!(epd0.direction(), Direction::In);
-		assert_eq!(epd0.transfer_type(), TransferType::Bulk);
-
-		debug_assert_eq!(epd1.address(), 2); // OUT (0) + 2
-		assert_eq!(epd1.direction(), Direction::Out);
-		assert_eq!(epd1.transfer_type(), TransferType::Bulk);
+		// usually IN (128) + 1 and OUT (0) + 2
+		if epd0.direction() != Direction::In || epd0.transfer_type() != TransferType::Bulk {
+			return Err(unsupported("a bulk IN endpoint"));
+		}
+		if epd1.direction() != Direction::Out || epd1.transfer_type() != TransferType::Bulk {
+			return Err(unsupported("a bulk OUT endpoint"));
+		}
 
 		let epin = epd0.address();
 		let epout = epd1.address();
 
-		handle.claim_interface(0).context("cannot claim usb interface 0")?;
+		let mut delays = Backoff::default().delays();
+		loop {
+			match handle.claim_interface(0) {
+				Ok(()) => break,
+				Err(rusb::Error::Busy) => {
+					let delay = delays.next().unwrap();
+					if wait.is_some_and(|wait| start.elapsed() + delay <= wait) {
+						log::debug!("usb interface 0 is busy, retrying in {delay:.2?}");
+						std::thread::sleep(delay);
+						continue;
+					}
+
+					return Err(Error::DeviceBusy {
+						driver: interface_driver(dev, cd.number()),
+						pid: device_user(dev),
+					});
+				},
+				Err(e) => return Err(e.into()),
+			}
+		}
 
 		Ok(Self {
 			handle,
 			epin,
 			epout,
+			stats: TransportStats::default(),
+			model: product.as_deref().and_then(Model::from_name),
+			_lock: lock,
 		})
 	}
+
+	/// If `e` is a stalled endpoint, clear the halt, so that the transfer can be retried.
+	/// Otherwise the endpoint stays stalled, and every retry fails the same way.
+	fn clear_stall(&self, ep: u8, e: rusb::Error) -> Error {
+		if e != rusb::Error::Pipe {
+			return e.into();
+		}
+		match self.handle.clear_halt(ep) {
+			Ok(()) => {
+				log::debug!("USB: cleared halt of endpoint {ep:#04x}");
+				e.into()
+			},
+			Err(ce) => {
+				log::warn!("USB: cannot clear halt of endpoint {ep:#04x}: {ce}");
+				// the endpoint is stuck, only opening the device again helps
+				Error::Disconnected
+			},
+		}
+	}
+
+	/// Release the interface and reattach the kernel driver, if there was one.
+	/// Afterwards the device can be used via the kernel's lp device again,
+	/// but this backend can't be used anymore.
+	pub fn release_to_kernel(&mut self) -> Result<()> {
+		// don't let libusb reattach the driver behind our back, we do it explicitly
+		let _ = self.handle.set_auto_detach_kernel_driver(false);
+		self.handle.release_interface(0)?;
+		match self.handle.attach_kernel_driver(0) {
+			Ok(()) => log::debug!("reattached usb kernel driver"),
+			// there was no driver to begin with, or we're not on Linux
+			Err(rusb::Error::NotFound | rusb::Error::NotSupported) => {},
+			Err(e) => return Err(e.into()),
+		}
+		Ok(())
+	}
 }
 
 impl Backend for UsbBackend {
-	fn send(&mut self, buf: &[u8], timeout: Duration) -> anyhow::Result<()> {
-		self.handle.write_bulk(self.epout, buf, timeout)?;
+	fn send(&mut self, buf: &[u8], timeout: Duration) -> Result<()> {
+		let start = Instant::now();
+		let n = self.handle.write_bulk(self.epout, buf, timeout)
+			.map_err(|e| self.clear_stall(self.epout, e))?;
+		let elapsed = start.elapsed();
+
+		self.stats.transfers += 1;
+		self.stats.bytes_sent += n as u64;
+		self.stats.send_time += elapsed;
+
+		if n >= MIN_MEASURED_SIZE {
+			let throughput = n as f64 / elapsed.as_secs_f64();
+			log::debug!("USB: sent {n} bytes in {elapsed:.2?} ({:.1} KiB/s)", throughput / 1024.0);
+			if throughput < SLOW_THROUGHPUT {
+				log::warn!("USB: slow transfer, {:.1} KiB/s, check the cable and hubs", throughput / 1024.0);
+			}
+			self.stats.slowest_send = Some(self.stats.slowest_send.map_or(throughput, |t| t.min(throughput)));
+		}
 		Ok(())
 	}
 
-	fn recv(&mut self, buf: &mut [u8], timeout: Duration) -> anyhow::Result<usize> {
-		let n = self.handle.read_bulk(self.epin, buf, timeout)?;
+	fn recv(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+		let n = self.handle.read_bulk(self.epin, buf, timeout)
+			.map_err(|e| self.clear_stall(self.epin, e))?;
+		self.stats.transfers += 1;
+		self.stats.bytes_received += n as u64;
 		Ok(n)
 	}
+
+	fn release(&mut self) -> Result<()> {
+		self.release_to_kernel()
+	}
+
+	fn stats(&self) -> Option<TransportStats> {
+		Some(self.stats)
+	}
+
+	fn model(&self) -> Option<Model> {
+		self.model
+	}
 }
 
+/// A printer was plugged in or out, see [`watch_devices()`].
+#[derive(Debug, Clone)]
+pub enum HotplugEvent {
+	/// A printer was connected, or was already connected when watching started.
+	DeviceArrived(Device),
+
+	/// A printer was disconnected.
+	DeviceLeft(Device),
+}
+
+/// Yields [`HotplugEvent`]s, see [`watch_devices()`].
+/// Watching stops, when this is dropped.
+pub struct DeviceWatcher {
+	rx: mpsc::Receiver<HotplugEvent>,
+	stop: Arc<AtomicBool>,
+	_registration: Registration<GlobalContext>,
+}
+
+impl DeviceWatcher {
+	/// Wait at most `timeout` for the next event.
+	pub fn recv_timeout(&self, timeout: Duration) -> Option<HotplugEvent> {
+		self.rx.recv_timeout(timeout).ok()
+	}
+
+	/// Get the next event, if there is one, without waiting.
+	pub fn try_recv(&self) -> Option<HotplugEvent> {
+		self.rx.try_recv().ok()
+	}
+}
+
+impl Iterator for DeviceWatcher {
+	type Item = HotplugEvent;
+
+	fn next(&mut self) -> Option<HotplugEvent> {
+		self.rx.recv().ok()
+	}
+}
+
+impl Drop for DeviceWatcher {
+	fn drop(&mut self) {
+		self.stop.store(true, Ordering::Relaxed);
+	}
+}
+
+struct HotplugSender(mpsc::Sender<HotplugEvent>);
+
+impl Hotplug<GlobalContext> for HotplugSender {
+	fn device_arrived(&mut self, device: Device) {
+		log::debug!("printer arrived: {device:?}");
+		let _ = self.0.send(HotplugEvent::DeviceArrived(device));
+	}
+
+	fn device_left(&mut self, device: Device) {
+		log::debug!("printer left: {device:?}");
+		let _ = self.0.send(HotplugEvent::DeviceLeft(device));
+	}
+}
+
+/// Watch for USB printers being plugged in or out.
+/// Printers, that are already connected, are reported as [`HotplugEvent::DeviceArrived`] first.
+///
+/// Fails with [`Error::Unsupported`], if libusb has no hotplug support on this platform.
+pub fn watch_devices() -> Result<DeviceWatcher> {
+	if !rusb::has_hotplug() {
+		return Err(Error::Unsupported("libusb has no hotplug support on this platform".into()));
+	}
+
+	let (tx, rx) = mpsc::channel();
+	let registration = HotplugBuilder::new()
+		.vendor_id(VENDOR_ID)
+		.product_id(PRODUCT_ID)
+		.enumerate(true)
+		.register(GlobalContext::default(), Box::new(HotplugSender(tx)))?;
+
+	let stop = Arc::new(AtomicBool::new(false));
+	let stopped = Arc::clone(&stop);
+	thread::spawn(move || {
+		while !stopped.load(Ordering::Relaxed) {
+			if let Err(e) = GlobalContext::default().handle_events(Some(Duration::from_millis(500))) {
+				log::error!("cannot handle USB events: {e}");
+				break;
+			}
+		}
+	});
+
+	Ok(DeviceWatcher {
+		rx,
+		stop,
+		_registration: registration,
+	})
+}
+
+/// Find the name of the kernel driver bound to interface 0 of `dev`.
+#[cfg(target_os = "linux")]
+fn interface_driver(dev: &Device, config: u8) -> Option<String> {
+	let ports = dev
+		.port_numbers()
+		.ok()?
+		.iter()
+		.map(u8::to_string)
+		.collect::<Vec<_>>()
+		.join(".");
+	let path = format!("/sys/bus/usb/devices/{}-{ports}:{config}.0/driver", dev.bus_number());
+	let driver = std::fs::read_link(path).ok()?;
+	Some(driver.file_name()?.to_string_lossy().into_owned())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn interface_driver(_dev: &Device, _config: u8) -> Option<String> {
+	None
+}
+
+/// Find another process, which has `dev` open.
+/// Only processes, whose file descriptors we're allowed to see, can be found.
+#[cfg(target_os = "linux")]
+fn device_user(dev: &Device) -> Option<u32> {
+	let node = format!("/dev/bus/usb/{:03}/{:03}", dev.bus_number(), dev.address());
+	let me = std::process::id();
+	std::fs::read_dir("/proc")
+		.ok()?
+		.flatten()
+		.filter_map(|e| e.file_name().to_str()?.parse::<u32>().ok())
+		.filter(|&pid| pid != me)
+		.find(|pid| {
+			let Ok(fds) = std::fs::read_dir(format!("/proc/{pid}/fd")) else {
+				return false;
+			};
+			fds
+				.flatten()
+				.any(|fd| std::fs::read_link(fd.path()).is_ok_and(|p| p.as_os_str() == node.as_str()))
+		})
+}
+
+#[cfg(not(target_os = "linux"))]
+fn device_user(_dev: &Device) -> Option<u32> {
+	None
+}